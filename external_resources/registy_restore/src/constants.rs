@@ -1,2 +0,0 @@
-pub const LOCAL_REGISTRY_KEY: &str = "hkey_local_machine.reg";
-pub const USER_REGISTRY_KEY: &str = "hkey_current_user.reg";
\ No newline at end of file
@@ -0,0 +1,123 @@
+// serde
+use serde::{Deserialize, Serialize};
+
+// internal: constants
+use crate::constants::*;
+
+// internal: registry_backend
+use crate::registry_backend::{Hive, RegistryView};
+
+/// What the startup cleaning step does with an autostart location
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LocationAction {
+    /// Back up and disable the entries the startup policy disables
+    Clean,
+    /// Only log the entries
+    Report,
+    /// Skip the location
+    Ignore,
+}
+
+/// A registry key whose values are started automatically
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AutostartLocation {
+    /// Short name used in logs and backup file names, e.g. `run`
+    pub name: String,
+    pub hive: Hive,
+    pub view: RegistryView,
+    /// Path of the key inside the hive
+    pub path: String,
+    /// Restrict the location to these value names; all values when empty
+    #[serde(default)]
+    pub values: Vec<String>,
+    pub action: LocationAction,
+}
+
+impl AutostartLocation {
+    fn new(
+        name: &str,
+        hive: Hive,
+        view: RegistryView,
+        path: &str,
+        values: &[&str],
+        action: LocationAction,
+    ) -> Self {
+        Self {
+            name: name.to_string(),
+            hive,
+            view,
+            path: path.to_string(),
+            values: values.iter().map(|value| value.to_string()).collect(),
+            action,
+        }
+    }
+
+    /// Whether a value of the key belongs to this location
+    pub fn includes_value(&self, name: &str) -> bool {
+        self.values.is_empty()
+            || self
+                .values
+                .iter()
+                .any(|value| value.eq_ignore_ascii_case(name))
+    }
+
+    /// Whether the location is the Winlogon key, whose values must never be removed
+    pub fn is_winlogon(&self) -> bool {
+        self.path.eq_ignore_ascii_case(REGISTRY_WINLOGON_PATH)
+    }
+}
+
+/// The autostart locations processed when the config does not list any
+///
+/// `Run` is cleaned in both views of the local machine and in the current user hive, which is
+/// shared between views. `RunOnce`, `RunServices`, the policy `Run` key and the Winlogon
+/// `Userinit`/`Shell` values are only reported.
+///
+/// # Returns
+///
+/// `Vec<AutostartLocation>` - The default locations
+pub fn default_autostart_locations() -> Vec<AutostartLocation> {
+    use LocationAction::{Clean, Report};
+
+    let mut locations: Vec<AutostartLocation> = Vec::new();
+    let hive_views: [(Hive, RegistryView); 3] = [
+        (Hive::LocalMachine, RegistryView::X64),
+        (Hive::LocalMachine, RegistryView::X32),
+        (Hive::CurrentUser, RegistryView::X64),
+    ];
+    for (hive, view) in hive_views {
+        locations.extend([
+            AutostartLocation::new("run", hive, view, REGISTRY_STARTUP_PATH, &[], Clean),
+            AutostartLocation::new("run_once", hive, view, REGISTRY_RUNONCE_PATH, &[], Report),
+            AutostartLocation::new(
+                "run_services",
+                hive,
+                view,
+                REGISTRY_RUNSERVICES_PATH,
+                &[],
+                Report,
+            ),
+            AutostartLocation::new(
+                "policies_run",
+                hive,
+                view,
+                REGISTRY_POLICIES_RUN_PATH,
+                &[],
+                Report,
+            ),
+        ]);
+    }
+    for hive in [Hive::LocalMachine, Hive::CurrentUser] {
+        locations.push(AutostartLocation::new(
+            "winlogon",
+            hive,
+            RegistryView::X64,
+            REGISTRY_WINLOGON_PATH,
+            &["Userinit", "Shell"],
+            Report,
+        ));
+    }
+    locations
+}
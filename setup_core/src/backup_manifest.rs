@@ -0,0 +1,190 @@
+// +------------------+
+// |    dependencies  |
+// +------------------+
+
+// std
+use std::fs;
+use std::io;
+use std::path::Path;
+
+// serde
+use serde::{Deserialize, Serialize};
+
+// sha2
+use sha2::{Digest, Sha256};
+
+// internal: registry_backend
+use crate::registry_backend::{Hive, RegValue, RegistryView};
+
+// internal: startup_folder
+use crate::startup_folder::MovedShortcut;
+
+/// Version of the manifest layout written by this build
+pub const MANIFEST_VERSION: u32 = 1;
+
+// +------------------+
+// |      types       |
+// +------------------+
+
+/// What a backed up registry key holds
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BackupKind {
+    /// Startup values that were deleted from the key
+    RemovedValues,
+    /// StartupApproved flags as they were before the entries got disabled
+    StartupApprovedFlags,
+}
+
+/// What the restore does when a backed up value exists again with different data
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictPolicy {
+    /// Leave the current value alone and only log the conflict
+    #[default]
+    KeepCurrent,
+    /// Overwrite the current value with the backed up one
+    PreferBackup,
+    /// Restore the backed up value next to the current one under a new name
+    Rename,
+}
+
+/// A registry value recorded in the manifest
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManifestValue {
+    pub name: String,
+    #[serde(flatten)]
+    pub value: RegValue,
+}
+
+/// A registry key backed up to a `.reg` file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupEntry {
+    /// Id of the run that made the backup
+    pub run_id: String,
+    /// UTC time the backup was made at, RFC 3339
+    pub created_at: String,
+    pub kind: BackupKind,
+    pub hive: Hive,
+    pub view: RegistryView,
+    /// Path of the key inside the hive, as opened in `view`
+    pub key_path: String,
+    /// Name of the `.reg` file, relative to the manifest
+    pub file: String,
+    /// Lowercase hex SHA-256 of the `.reg` file
+    pub sha256: String,
+    pub values: Vec<ManifestValue>,
+}
+
+/// Everything a run took out of the startup, written next to the backups
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupManifest {
+    pub version: u32,
+    #[serde(default)]
+    pub conflict_policy: ConflictPolicy,
+    #[serde(default)]
+    pub entries: Vec<BackupEntry>,
+    #[serde(default)]
+    pub startup_shortcuts: Vec<MovedShortcut>,
+}
+
+impl Default for BackupManifest {
+    fn default() -> Self {
+        Self {
+            version: MANIFEST_VERSION,
+            conflict_policy: ConflictPolicy::default(),
+            entries: Vec::new(),
+            startup_shortcuts: Vec::new(),
+        }
+    }
+}
+
+impl BackupManifest {
+    /// Read a manifest from disk
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path of the manifest file
+    ///
+    /// # Returns
+    ///
+    /// `std::io::Result<Option<BackupManifest>>` - The manifest, or `None` if there is no file
+    pub fn load(path: &Path) -> io::Result<Option<BackupManifest>> {
+        let content: String = match fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err),
+        };
+        let manifest: BackupManifest = serde_json::from_str(&content)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        if manifest.version > MANIFEST_VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "manifest version {} is newer than the supported version {}",
+                    manifest.version, MANIFEST_VERSION
+                ),
+            ));
+        }
+        Ok(Some(manifest))
+    }
+
+    /// Read a manifest from disk, starting an empty one if there is no file yet
+    ///
+    /// Entries of an earlier run that were not restored yet are kept, so their backups stay
+    /// reachable.
+    pub fn load_or_default(path: &Path) -> io::Result<BackupManifest> {
+        Ok(Self::load(path)?.unwrap_or_default())
+    }
+
+    /// Serialize the manifest the way it is stored on disk
+    pub fn to_json(&self) -> io::Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    /// Write the manifest to disk
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, self.to_json()?)
+    }
+
+    /// Whether nothing is left to restore
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty() && self.startup_shortcuts.is_empty()
+    }
+}
+
+// +------------------+
+// | public functions |
+// +------------------+
+
+/// Hash data the way backup files are recorded in the manifest
+///
+/// # Arguments
+///
+/// * `data` - The bytes to be hashed
+///
+/// # Returns
+///
+/// `String` - The lowercase hex SHA-256 of the data
+pub fn sha256_hex(data: &[u8]) -> String {
+    Sha256::digest(data)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Hash a backup file
+///
+/// # Arguments
+///
+/// * `path` - Path of the file
+///
+/// # Returns
+///
+/// `std::io::Result<String>` - The lowercase hex SHA-256 of the file content
+pub fn file_sha256(path: &Path) -> io::Result<String> {
+    Ok(sha256_hex(&fs::read(path)?))
+}
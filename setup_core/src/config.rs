@@ -0,0 +1,621 @@
+// +------------------+
+// |    dependencies  |
+// +------------------+
+
+// std
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+// serde
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+// internal: autostart
+use crate::autostart::{default_autostart_locations, AutostartLocation};
+
+// internal: backup_manifest
+use crate::backup_manifest::ConflictPolicy;
+
+// internal: config_schema
+use crate::config_schema::{
+    migrate_config, parse_config, ConfigError, ConfigIssue, CONFIG_SCHEMA_VERSION,
+};
+
+// internal: constants
+use crate::constants::CONFIG_ENV_PREFIX;
+
+// internal: locale
+use crate::locale::LocaleSettings;
+
+// internal: regional
+use crate::regional::RegionalSettings;
+
+// internal: run_state
+use crate::run_state::SetupStep;
+
+// internal: startup_policy
+use crate::startup_policy::{StartupCleanMode, StartupPolicy};
+
+// internal: step
+use crate::step::{default_steps, StepSettings};
+
+// +------------------+
+// |      types       |
+// +------------------+
+
+/// Settings of a setup run, see `ConfigLayers` for where they are read from
+///
+/// Unknown fields are rejected so a misspelled setting is not silently replaced by its default.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Version of the config schema, see `config_schema` for the migrations
+    pub schema_version: u32,
+    pub clean_startup_apps: bool,
+    pub restore_startup_apps: bool,
+    pub first_time_reboot: bool,
+    pub reboot_timer: u32,
+    pub change_locale: bool,
+    /// What the locale step applies
+    #[serde(default)]
+    pub locale: LocaleSettings,
+    /// Off unless asked for, the step changes the time zone and date formats of the machine
+    #[serde(default)]
+    pub change_regional_settings: bool,
+    /// What the regional settings step applies
+    #[serde(default)]
+    pub regional: RegionalSettings,
+    #[serde(default = "default_autostart_locations")]
+    pub autostart_locations: Vec<AutostartLocation>,
+    #[serde(default)]
+    pub startup_clean_mode: StartupCleanMode,
+    #[serde(default)]
+    pub startup_policy: StartupPolicy,
+    #[serde(default)]
+    pub restore_conflict_policy: ConflictPolicy,
+    /// The steps of a run in order, with what to do when one fails
+    #[serde(default = "default_steps")]
+    pub steps: Vec<StepSettings>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            schema_version: CONFIG_SCHEMA_VERSION,
+            clean_startup_apps: true,
+            restore_startup_apps: true,
+            first_time_reboot: true,
+            reboot_timer: 60,
+            change_locale: true,
+            locale: LocaleSettings::default(),
+            change_regional_settings: false,
+            regional: RegionalSettings::default(),
+            autostart_locations: default_autostart_locations(),
+            startup_clean_mode: StartupCleanMode::default(),
+            startup_policy: StartupPolicy::default(),
+            restore_conflict_policy: ConflictPolicy::default(),
+            steps: default_steps(),
+        }
+    }
+}
+
+impl Config {
+    /// The configured steps, in order
+    pub fn step_order(&self) -> Vec<SetupStep> {
+        self.steps.iter().map(|settings| settings.step).collect()
+    }
+
+    /// The settings of a step, `None` if the step is not configured
+    pub fn step_settings(&self, step: SetupStep) -> Option<&StepSettings> {
+        self.steps.iter().find(|settings| settings.step == step)
+    }
+}
+
+/// Where the effective value of a config field comes from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigSource {
+    /// The built-in default
+    Default,
+    /// The shared config file
+    File(PathBuf),
+    /// The machine-specific overlay file
+    MachineOverlay(PathBuf),
+    /// A `MOEIN_ASSISTANT_*` environment variable
+    Environment(String),
+    /// A command-line flag
+    CommandLine(String),
+}
+
+impl fmt::Display for ConfigSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigSource::Default => write!(f, "default"),
+            ConfigSource::File(path) => write!(f, "file {}", path.display()),
+            ConfigSource::MachineOverlay(path) => write!(f, "machine overlay {}", path.display()),
+            ConfigSource::Environment(name) => write!(f, "environment variable {}", name),
+            ConfigSource::CommandLine(flag) => write!(f, "command line {}", flag),
+        }
+    }
+}
+
+/// The effective config together with the source of every field
+#[derive(Debug, Clone)]
+pub struct LayeredConfig {
+    pub config: Config,
+    /// The layers that changed the config, in the order they were applied
+    pub layers: Vec<ConfigSource>,
+    value: Value,
+    sources: BTreeMap<String, ConfigSource>,
+}
+
+impl LayeredConfig {
+    /// The source of a field, e.g. `$.startup_policy.default_action`
+    pub fn source_of(&self, path: &str) -> &ConfigSource {
+        source_for(&self.sources, path)
+    }
+
+    /// Log the effective value and the source of every field
+    pub fn log_sources(&self) {
+        let mut fields: Vec<(String, &Value)> = Vec::new();
+        leaf_fields("$", &self.value, &mut fields);
+        for (path, value) in fields {
+            log::info!("config {} = {} ({})", path, value, self.source_of(&path));
+        }
+    }
+}
+
+/// Builds the config from layers, each overriding the ones before it
+///
+/// The layers are the built-in defaults, the config file, the machine overlay file,
+/// `MOEIN_ASSISTANT_*` environment variables and command-line flags. Files may set any subset
+/// of the fields; objects are merged field by field, lists replace the list below them. The
+/// config is validated after every layer, so a problem is reported against the layer that
+/// introduced it.
+#[derive(Debug, Clone)]
+pub struct ConfigLayers {
+    value: Value,
+    layers: Vec<ConfigSource>,
+    sources: BTreeMap<String, ConfigSource>,
+}
+
+impl Default for ConfigLayers {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ConfigLayers {
+    /// Start from the built-in defaults
+    pub fn new() -> ConfigLayers {
+        ConfigLayers {
+            value: serde_json::to_value(Config::default()).unwrap_or(Value::Null),
+            layers: vec![ConfigSource::Default],
+            sources: BTreeMap::new(),
+        }
+    }
+
+    /// Apply the shared config file
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path of the config file, skipped if it does not exist
+    ///
+    /// # Returns
+    ///
+    /// `Result<bool, ConfigError>` - Whether the file exists, or why it cannot be used
+    pub fn add_file(&mut self, path: &Path) -> Result<bool, ConfigError> {
+        self.add_json_file(path, ConfigSource::File(path.to_path_buf()))
+    }
+
+    /// Apply the machine-specific overlay file
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path of the overlay file, skipped if it does not exist
+    ///
+    /// # Returns
+    ///
+    /// `Result<bool, ConfigError>` - Whether the file exists, or why it cannot be used
+    pub fn add_machine_overlay(&mut self, path: &Path) -> Result<bool, ConfigError> {
+        self.add_json_file(path, ConfigSource::MachineOverlay(path.to_path_buf()))
+    }
+
+    /// Apply the `MOEIN_ASSISTANT_*` environment variables
+    ///
+    /// `MOEIN_ASSISTANT_REBOOT_TIMER=120` sets `reboot_timer`, a double underscore separates
+    /// nested fields as in `MOEIN_ASSISTANT_STARTUP_POLICY__DEFAULT_ACTION=keep`. Values are
+    /// read as JSON, or as a string if they are not valid JSON.
+    ///
+    /// # Arguments
+    ///
+    /// * `vars` - The environment variables, other variables are ignored
+    ///
+    /// # Returns
+    ///
+    /// `Result<usize, ConfigError>` - The number of variables applied
+    pub fn add_environment(
+        &mut self,
+        vars: impl IntoIterator<Item = (String, String)>,
+    ) -> Result<usize, ConfigError> {
+        let mut count: usize = 0;
+        for (name, value) in vars {
+            let Some(field) = name.strip_prefix(CONFIG_ENV_PREFIX) else {
+                continue;
+            };
+            let keys: Vec<String> = field.split("__").map(|key| key.to_lowercase()).collect();
+            self.set_field(
+                &keys,
+                parse_scalar(&value),
+                ConfigSource::Environment(name.clone()),
+            );
+            count += 1;
+        }
+        if count > 0 {
+            self.validate()?;
+        }
+        Ok(count)
+    }
+
+    /// Apply a single field set on the command line
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The field, nested fields separated by dots as in `startup_policy.default_action`
+    /// * `value` - The value, read as JSON or as a string if it is not valid JSON
+    /// * `flag` - The flag that set the field, for the logs
+    ///
+    /// # Returns
+    ///
+    /// `Result<(), ConfigError>` - Why the field cannot be set
+    pub fn add_override(&mut self, key: &str, value: &str, flag: &str) -> Result<(), ConfigError> {
+        let keys: Vec<String> = key.split('.').map(str::to_string).collect();
+        self.set_field(
+            &keys,
+            parse_scalar(value),
+            ConfigSource::CommandLine(flag.to_string()),
+        );
+        self.validate()?;
+        Ok(())
+    }
+
+    /// The effective config
+    ///
+    /// # Returns
+    ///
+    /// `Result<LayeredConfig, ConfigError>` - The config, or why it is invalid
+    pub fn finish(self) -> Result<LayeredConfig, ConfigError> {
+        let config: Config = self.validate()?;
+        Ok(LayeredConfig {
+            config,
+            layers: self.layers,
+            value: self.value,
+            sources: self.sources,
+        })
+    }
+
+    /// Read, migrate and merge a JSON config file
+    fn add_json_file(&mut self, path: &Path, source: ConfigSource) -> Result<bool, ConfigError> {
+        let content: String = match fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                log::info!("config {} not found, skipped", source);
+                return Ok(false);
+            }
+            Err(err) => return Err(ConfigError::Unreadable(path.to_path_buf(), err)),
+        };
+        let invalid = |issue: ConfigIssue| {
+            ConfigError::Invalid(vec![ConfigIssue {
+                message: format!("{} (in {})", issue.message, source),
+                ..issue
+            }])
+        };
+        let mut layer: Value = serde_json::from_str(&content).map_err(|err| {
+            invalid(ConfigIssue {
+                path: "$".to_string(),
+                message: format!("not valid JSON: {}", err),
+            })
+        })?;
+        // An overlay only changes what it sets, the fields a migration fills in are dropped
+        let own_fields: Option<Vec<String>> = match (&source, layer.as_object()) {
+            (ConfigSource::MachineOverlay(_), Some(object)) => {
+                Some(object.keys().cloned().collect())
+            }
+            _ => None,
+        };
+        migrate_config(&mut layer).map_err(invalid)?;
+        // The layers are merged into a config of the current version
+        if let Some(object) = layer.as_object_mut() {
+            if let Some(own_fields) = own_fields {
+                object.retain(|key, _| own_fields.contains(key));
+            }
+            object.remove("schema_version");
+        }
+        merge_layer(&mut self.value, layer, "$", &source, &mut self.sources);
+        self.layers.push(source);
+        self.validate()?;
+        Ok(true)
+    }
+
+    /// Set a single field, creating the objects above it
+    fn set_field(&mut self, keys: &[String], value: Value, source: ConfigSource) {
+        let mut layer: Value = value;
+        for key in keys.iter().rev() {
+            let mut object: Map<String, Value> = Map::new();
+            object.insert(key.clone(), layer);
+            layer = Value::Object(object);
+        }
+        merge_layer(&mut self.value, layer, "$", &source, &mut self.sources);
+        self.layers.push(source);
+    }
+
+    /// Parse and validate the config as layered so far, naming the source of every problem
+    fn validate(&self) -> Result<Config, ConfigError> {
+        parse_config(self.value.clone()).map_err(|issues| {
+            ConfigError::Invalid(
+                issues
+                    .into_iter()
+                    .map(|issue| {
+                        let source: String = source_for(&self.sources, &issue.path).to_string();
+                        ConfigIssue {
+                            message: format!("{} (set by {})", issue.message, source),
+                            ..issue
+                        }
+                    })
+                    .collect(),
+            )
+        })
+    }
+}
+
+// +-----------------------+
+// |  private functions    |
+// +-----------------------+
+
+/// Merge a layer into the config, recording the source of every field it sets
+fn merge_layer(
+    base: &mut Value,
+    layer: Value,
+    path: &str,
+    source: &ConfigSource,
+    sources: &mut BTreeMap<String, ConfigSource>,
+) {
+    match (base, layer) {
+        (Value::Object(base), Value::Object(layer)) => {
+            for (key, value) in layer {
+                let field_path: String = format!("{}.{}", path, key);
+                let field: &mut Value = base.entry(key).or_insert(Value::Null);
+                merge_layer(field, value, &field_path, source, sources);
+            }
+        }
+        (base, layer) => {
+            *base = layer;
+            // Fields below a replaced value now come from this layer as well
+            let nested: String = path.to_string();
+            sources.retain(|field, _| {
+                !(field.starts_with(&nested) && field[nested.len()..].starts_with(['.', '[']))
+            });
+            sources.insert(path.to_string(), source.clone());
+        }
+    }
+}
+
+/// The source of a field; a field set as a whole object or list covers everything below it
+fn source_for<'a>(sources: &'a BTreeMap<String, ConfigSource>, path: &str) -> &'a ConfigSource {
+    let mut current: &str = path;
+    loop {
+        if let Some(source) = sources.get(current) {
+            return source;
+        }
+        match current.rfind(['.', '[']) {
+            Some(end) => current = &current[..end],
+            None => return &ConfigSource::Default,
+        }
+    }
+}
+
+/// Read a value given as text: JSON if it parses, a string otherwise
+fn parse_scalar(text: &str) -> Value {
+    serde_json::from_str(text).unwrap_or_else(|_| Value::String(text.to_string()))
+}
+
+/// Collect the JSON path of every field that is not an object, with its value
+fn leaf_fields<'a>(path: &str, value: &'a Value, fields: &mut Vec<(String, &'a Value)>) {
+    match value {
+        Value::Object(object) => {
+            for (key, field) in object {
+                leaf_fields(&format!("{}.{}", path, key), field, fields);
+            }
+        }
+        _ => fields.push((path.to_string(), value)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::startup_policy::StartupAction;
+    use crate::test_support::TempFolder;
+    use serde_json::json;
+
+    fn write_json(path: &Path, value: Value) {
+        fs::write(path, value.to_string()).unwrap();
+    }
+
+    fn env(name: &str, value: &str) -> (String, String) {
+        (name.to_string(), value.to_string())
+    }
+
+    #[test]
+    fn later_layers_override_earlier_ones() {
+        let temp = TempFolder::new("config_precedence");
+        let file: PathBuf = temp.0.join("config.json");
+        let overlay: PathBuf = temp.0.join("machine.json");
+        write_json(
+            &file,
+            json!({
+                "schema_version": CONFIG_SCHEMA_VERSION,
+                "reboot_timer": 10,
+                "first_time_reboot": false,
+                "restore_startup_apps": false
+            }),
+        );
+        write_json(
+            &overlay,
+            json!({
+                "schema_version": CONFIG_SCHEMA_VERSION,
+                "reboot_timer": 20,
+                "restore_startup_apps": true
+            }),
+        );
+
+        let mut layers = ConfigLayers::new();
+        assert!(layers.add_file(&file).unwrap());
+        assert!(layers.add_machine_overlay(&overlay).unwrap());
+        assert!(!layers
+            .add_machine_overlay(&temp.0.join("missing.json"))
+            .unwrap());
+        layers
+            .add_environment([env("MOEIN_ASSISTANT_REBOOT_TIMER", "30")])
+            .unwrap();
+        layers
+            .add_override("reboot_timer", "40", "--reboot-timer")
+            .unwrap();
+        let layered: LayeredConfig = layers.finish().unwrap();
+
+        assert_eq!(layered.config.reboot_timer, 40);
+        assert!(!layered.config.first_time_reboot);
+        assert!(layered.config.restore_startup_apps);
+        assert!(layered.config.change_locale);
+        assert_eq!(
+            layered.source_of("$.reboot_timer"),
+            &ConfigSource::CommandLine("--reboot-timer".to_string())
+        );
+        assert_eq!(
+            layered.source_of("$.first_time_reboot"),
+            &ConfigSource::File(file.clone())
+        );
+        assert_eq!(
+            layered.source_of("$.restore_startup_apps"),
+            &ConfigSource::MachineOverlay(overlay.clone())
+        );
+        assert_eq!(layered.source_of("$.change_locale"), &ConfigSource::Default);
+        assert_eq!(
+            layered.layers,
+            vec![
+                ConfigSource::Default,
+                ConfigSource::File(file),
+                ConfigSource::MachineOverlay(overlay),
+                ConfigSource::Environment("MOEIN_ASSISTANT_REBOOT_TIMER".to_string()),
+                ConfigSource::CommandLine("--reboot-timer".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn environment_variables_set_nested_lower_case_fields() {
+        let mut layers = ConfigLayers::new();
+
+        let count: usize = layers
+            .add_environment([
+                env("MOEIN_ASSISTANT_STARTUP_POLICY__DEFAULT_ACTION", "keep"),
+                env("MOEIN_ASSISTANT_CHANGE_LOCALE", "false"),
+                env("PATH", "C:\\Windows"),
+            ])
+            .unwrap();
+        let layered: LayeredConfig = layers.finish().unwrap();
+
+        assert_eq!(count, 2);
+        assert_eq!(
+            layered.config.startup_policy.default_action,
+            StartupAction::Keep
+        );
+        assert!(!layered.config.change_locale);
+        assert_eq!(
+            layered.source_of("$.startup_policy.default_action"),
+            &ConfigSource::Environment(
+                "MOEIN_ASSISTANT_STARTUP_POLICY__DEFAULT_ACTION".to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn values_that_are_not_json_are_strings() {
+        assert_eq!(parse_scalar("120"), json!(120));
+        assert_eq!(parse_scalar("true"), json!(true));
+        assert_eq!(parse_scalar("[\"a\"]"), json!(["a"]));
+        assert_eq!(parse_scalar("keep"), json!("keep"));
+        assert_eq!(parse_scalar("C:\\setup"), json!("C:\\setup"));
+    }
+
+    #[test]
+    fn an_overlay_only_changes_the_fields_it_sets() {
+        let temp = TempFolder::new("config_overlay");
+        let file: PathBuf = temp.0.join("config.json");
+        let overlay: PathBuf = temp.0.join("machine.json");
+        write_json(
+            &file,
+            json!({
+                "schema_version": CONFIG_SCHEMA_VERSION,
+                "startup_clean_mode": "startup_approved"
+            }),
+        );
+        // A version 1 overlay, its migration fills in `startup_clean_mode` and more
+        write_json(&overlay, json!({ "reboot_timer": 5 }));
+
+        let mut layers = ConfigLayers::new();
+        layers.add_file(&file).unwrap();
+        layers.add_machine_overlay(&overlay).unwrap();
+        let layered: LayeredConfig = layers.finish().unwrap();
+
+        assert_eq!(layered.config.reboot_timer, 5);
+        assert_eq!(
+            layered.config.startup_clean_mode,
+            StartupCleanMode::StartupApproved
+        );
+        assert_eq!(
+            layered.source_of("$.startup_clean_mode"),
+            &ConfigSource::File(file)
+        );
+    }
+
+    #[test]
+    fn problems_name_the_layer_that_set_the_field() {
+        let mut layers = ConfigLayers::new();
+        layers
+            .add_override(
+                "startup_policy.keep",
+                r#"[{ "name": "OneDrive" }]"#,
+                "--keep",
+            )
+            .unwrap();
+
+        let error = layers
+            .add_environment([env("MOEIN_ASSISTANT_REBOOT_TIMER", "soon")])
+            .unwrap_err();
+
+        let ConfigError::Invalid(issues) = error else {
+            panic!("expected an invalid config, got {}", error);
+        };
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].path, "$.reboot_timer");
+        assert!(
+            issues[0]
+                .message
+                .ends_with("(set by environment variable MOEIN_ASSISTANT_REBOOT_TIMER)"),
+            "{}",
+            issues[0].message
+        );
+        // A field set as a whole list covers everything below it
+        assert_eq!(
+            source_for(&layers.sources, "$.startup_policy.keep[0].name"),
+            &ConfigSource::CommandLine("--keep".to_string())
+        );
+        assert_eq!(
+            source_for(&layers.sources, "$.startup_policy.default_action"),
+            &ConfigSource::Default
+        );
+    }
+}
@@ -0,0 +1,714 @@
+// +------------------+
+// |    dependencies  |
+// +------------------+
+
+// std
+use std::collections::HashSet;
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+// serde
+use serde_json::{json, Map, Value};
+
+// internal: autostart
+use crate::autostart::{default_autostart_locations, LocationAction};
+
+// internal: config
+use crate::config::Config;
+
+// internal: locale
+use crate::locale::LocaleSettings;
+use crate::locale_catalog::{check_geo_id, check_locale, resolve_input_language};
+
+// internal: regional
+use crate::regional::RegionalSettings;
+
+// internal: registry_backend
+use crate::registry_backend::{Hive, RegistryView};
+
+// internal: run_state
+use crate::run_state::{Phase, SetupStep};
+
+// internal: startup_policy
+use crate::startup_policy::StartupRule;
+
+/// Version of the config schema written by this build
+pub const CONFIG_SCHEMA_VERSION: u32 = 5;
+
+/// Longest delay `shutdown /t` accepts, in seconds
+const MAX_REBOOT_TIMER: u32 = 315_360_000;
+
+/// Stop collecting structural errors after this many, the file is likely not a config at all
+const MAX_CONFIG_ISSUES: usize = 50;
+
+// +------------------+
+// |      types       |
+// +------------------+
+
+/// A problem found in the config, `path` is a JSON path such as `$.autostart_locations[2].hive`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigIssue {
+    pub path: String,
+    pub message: String,
+}
+
+impl fmt::Display for ConfigIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.path, self.message)
+    }
+}
+
+/// Why a config file could not be used
+#[derive(Debug)]
+pub enum ConfigError {
+    /// A config file exists but could not be read
+    Unreadable(PathBuf, io::Error),
+    /// The config is not valid, every problem found is listed
+    Invalid(Vec<ConfigIssue>),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Unreadable(path, err) => {
+                write!(f, "failed to read config file {}: {}", path.display(), err)
+            }
+            ConfigError::Invalid(issues) => {
+                write!(f, "config is invalid ({} problems):", issues.len())?;
+                for issue in issues {
+                    write!(f, "\n  {}", issue)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Error for ConfigError {}
+
+/// A step in a JSON path
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum PathPart {
+    Key(String),
+    Index(usize),
+}
+
+/// Upgrade a config object by one schema version
+type Migration = fn(&mut Map<String, Value>) -> Result<(), ConfigIssue>;
+
+/// Migrations from every older schema version, `MIGRATIONS[0]` upgrades version 1 to 2
+const MIGRATIONS: [Migration; 4] = [
+    migrate_v1_to_v2,
+    migrate_v2_to_v3,
+    migrate_v3_to_v4,
+    migrate_v4_to_v5,
+];
+
+// +------------------+
+// | public functions |
+// +------------------+
+
+/// Migrate and validate a config
+///
+/// Unlike a plain `serde_json::from_value`, every problem in the config is reported, not just
+/// the first one.
+///
+/// # Arguments
+///
+/// * `value` - The config as JSON
+///
+/// # Returns
+///
+/// `Result<Config, Vec<ConfigIssue>>` - The config, or every problem found in it
+pub fn parse_config(mut value: Value) -> Result<Config, Vec<ConfigIssue>> {
+    migrate_config(&mut value).map_err(|issue| vec![issue])?;
+    match serde_json::from_value::<Config>(value.clone()) {
+        Ok(config) => {
+            let issues: Vec<ConfigIssue> = validate_config(&config);
+            if issues.is_empty() {
+                Ok(config)
+            } else {
+                Err(issues)
+            }
+        }
+        Err(_) => Err(collect_structural_issues(value)),
+    }
+}
+
+/// Upgrade a config to the current schema version
+///
+/// Files without a `schema_version` are version 1, the unversioned format used before.
+///
+/// # Arguments
+///
+/// * `value` - The config as JSON, upgraded in place
+///
+/// # Returns
+///
+/// `Result<u32, ConfigIssue>` - The schema version the file had
+pub fn migrate_config(value: &mut Value) -> Result<u32, ConfigIssue> {
+    let issue = |path: &str, message: String| ConfigIssue {
+        path: path.to_string(),
+        message,
+    };
+    let object: &mut Map<String, Value> = value
+        .as_object_mut()
+        .ok_or_else(|| issue("$", "the config must be a JSON object".to_string()))?;
+    let version: u32 = match object.get("schema_version") {
+        None => 1,
+        Some(version) => version
+            .as_u64()
+            .and_then(|version| u32::try_from(version).ok())
+            .filter(|version| *version >= 1)
+            .ok_or_else(|| {
+                issue(
+                    "$.schema_version",
+                    format!("must be a version number, found {}", version),
+                )
+            })?,
+    };
+    if version > CONFIG_SCHEMA_VERSION {
+        return Err(issue(
+            "$.schema_version",
+            format!(
+                "version {} is newer than version {} supported by this build",
+                version, CONFIG_SCHEMA_VERSION
+            ),
+        ));
+    }
+    for (from, migration) in MIGRATIONS.iter().enumerate().skip(version as usize - 1) {
+        migration(object)?;
+        log::info!(
+            "config migrated from schema version {} to {}",
+            from + 1,
+            from + 2
+        );
+    }
+    object.insert(
+        "schema_version".to_string(),
+        Value::from(CONFIG_SCHEMA_VERSION),
+    );
+    Ok(version)
+}
+
+/// Check the values of a config that parsed, e.g. ranges and names used in file names
+///
+/// # Arguments
+///
+/// * `config` - The parsed config
+///
+/// # Returns
+///
+/// `Vec<ConfigIssue>` - Every problem found, empty if the config is valid
+pub fn validate_config(config: &Config) -> Vec<ConfigIssue> {
+    let mut issues: Vec<ConfigIssue> = Vec::new();
+    let mut report = |path: String, message: &str| {
+        issues.push(ConfigIssue {
+            path,
+            message: message.to_string(),
+        })
+    };
+
+    if config.reboot_timer > MAX_REBOOT_TIMER {
+        report(
+            "$.reboot_timer".to_string(),
+            &format!("must be at most {} seconds", MAX_REBOOT_TIMER),
+        );
+    }
+
+    let mut names: HashSet<(String, Hive, RegistryView)> = HashSet::new();
+    for (index, location) in config.autostart_locations.iter().enumerate() {
+        let path: String = format!("$.autostart_locations[{}]", index);
+        if location.name.is_empty() {
+            report(format!("{}.name", path), "must not be empty");
+        } else if !location
+            .name
+            .chars()
+            .all(|character| character.is_ascii_alphanumeric() || "_-".contains(character))
+        {
+            report(
+                format!("{}.name", path),
+                "may only contain letters, digits, `_` and `-`, it is used in backup file names",
+            );
+        } else if !names.insert((location.name.to_lowercase(), location.hive, location.view)) {
+            report(
+                format!("{}.name", path),
+                "is used twice for the same hive and view, the backups would overwrite each other",
+            );
+        }
+        if location.path.trim_matches('\\').is_empty() {
+            report(format!("{}.path", path), "must not be empty");
+        } else if location.path.starts_with('\\') || location.path.ends_with('\\') {
+            report(
+                format!("{}.path", path),
+                "must not start or end with a backslash",
+            );
+        }
+        for (value_index, value) in location.values.iter().enumerate() {
+            if value.is_empty() {
+                report(
+                    format!("{}.values[{}]", path, value_index),
+                    "must not be empty",
+                );
+            }
+        }
+        if location.is_winlogon() && location.action == LocationAction::Clean {
+            report(
+                format!("{}.action", path),
+                "Winlogon values are never removed, use `report` or `ignore`",
+            );
+        }
+    }
+
+    let rule_lists: [(&str, &Vec<StartupRule>); 2] = [
+        ("keep", &config.startup_policy.keep),
+        ("disable", &config.startup_policy.disable),
+    ];
+    for (list, rules) in rule_lists {
+        for (index, rule) in rules.iter().enumerate() {
+            let path: String = format!("$.startup_policy.{}[{}]", list, index);
+            if rule.name.is_none() && rule.command.is_none() {
+                report(
+                    path.clone(),
+                    "needs a `name` or `command` pattern, an empty rule matches nothing",
+                );
+            }
+            for (field, pattern) in [("name", &rule.name), ("command", &rule.command)] {
+                if pattern.as_ref().is_some_and(|pattern| pattern.is_empty()) {
+                    report(format!("{}.{}", path, field), "must not be empty");
+                }
+            }
+        }
+    }
+
+    let locale: &LocaleSettings = &config.locale;
+    let locale_tags: [(&str, Option<&String>); 4] = [
+        ("mui_language", Some(&locale.mui_language)),
+        ("mui_fallback", locale.mui_fallback.as_ref()),
+        ("user_locale", Some(&locale.user_locale)),
+        ("system_locale", Some(&locale.system_locale)),
+    ];
+    for (field, tag) in locale_tags {
+        if let Some(Err(message)) = tag.map(|tag| check_locale(tag)) {
+            report(format!("$.locale.{}", field), &message);
+        }
+    }
+    for (index, input_language) in locale.input_languages.iter().enumerate() {
+        if let Err(message) = resolve_input_language(input_language) {
+            report(format!("$.locale.input_languages[{}]", index), &message);
+        }
+    }
+    if let Some(Err(message)) = locale
+        .home_location
+        .map(|geo_id| check_geo_id(geo_id, &locale.user_locale))
+    {
+        report("$.locale.home_location".to_string(), &message);
+    }
+
+    let regional: &RegionalSettings = &config.regional;
+    let regional_texts: [(&str, Option<&String>); 3] = [
+        ("time_zone", regional.time_zone.as_ref()),
+        ("short_date", regional.short_date.as_ref()),
+        ("long_date", regional.long_date.as_ref()),
+    ];
+    for (field, text) in regional_texts {
+        if text.is_some_and(|text| text.trim().is_empty()) {
+            report(format!("$.regional.{}", field), "must not be empty");
+        }
+    }
+    if let Some(geo_id) = regional.home_location {
+        if let Err(message) = check_geo_id(geo_id, &locale.user_locale) {
+            report("$.regional.home_location".to_string(), &message);
+        } else if config.change_locale
+            && config.change_regional_settings
+            && locale
+                .home_location
+                .is_some_and(|locale_geo_id| locale_geo_id != geo_id)
+        {
+            report(
+                "$.regional.home_location".to_string(),
+                "differs from `locale.home_location`, the step applied last would win",
+            );
+        }
+    }
+
+    let mut listed: Vec<SetupStep> = Vec::new();
+    for (index, settings) in config.steps.iter().enumerate() {
+        let path: String = format!("$.steps[{}].step", index);
+        let step: SetupStep = settings.step;
+        if listed.contains(&step) {
+            report(path, "is listed twice");
+            continue;
+        }
+        if step.phase() == Phase::PreReboot {
+            if listed.iter().any(|done| done.phase() == Phase::PostReboot) {
+                report(
+                    path,
+                    "runs before the reboot, list it before the steps that run after the reboot",
+                );
+            } else if listed.contains(&SetupStep::Reboot) {
+                report(
+                    path,
+                    "must come before `reboot`, the run stops there until the machine reboots",
+                );
+            }
+        }
+        listed.push(step);
+    }
+    issues
+}
+
+// +-----------------------+
+// |  private functions    |
+// +-----------------------+
+
+/// Version 2 adds the autostart locations, the startup policy and the restore conflict policy
+///
+/// Version 1 files disabled every entry of the default locations by deleting it, and kept the
+/// current value on a restore conflict; the fields are filled in with that behaviour so later
+/// changes to the defaults do not change what an old file does.
+fn migrate_v1_to_v2(config: &mut Map<String, Value>) -> Result<(), ConfigIssue> {
+    let autostart_locations: Value =
+        serde_json::to_value(default_autostart_locations()).map_err(|err| ConfigIssue {
+            path: "$.autostart_locations".to_string(),
+            message: format!("failed to build the version 1 locations: {}", err),
+        })?;
+    fill_field(config, "autostart_locations", autostart_locations);
+    fill_field(config, "startup_clean_mode", json!("delete"));
+    fill_field(
+        config,
+        "startup_policy",
+        json!({ "default_action": "disable", "keep": [], "disable": [] }),
+    );
+    fill_field(config, "restore_conflict_policy", json!("keep_current"));
+    Ok(())
+}
+
+/// Version 3 adds the locale settings
+///
+/// Version 2 files applied the fixed Persian locale XML, which the settings are filled in with.
+fn migrate_v2_to_v3(config: &mut Map<String, Value>) -> Result<(), ConfigIssue> {
+    fill_field(
+        config,
+        "locale",
+        json!({
+            "mui_language": "fa-IR",
+            "mui_fallback": "en-US",
+            "user_locale": "fa-IR",
+            "system_locale": "fa-IR",
+            "input_languages": ["0429:00000429"],
+            "home_location": null,
+            "copy_settings_to_system_account": true,
+            "copy_settings_to_default_user_account": true
+        }),
+    );
+    Ok(())
+}
+
+/// Version 4 adds the regional settings step, off for files written before it existed
+fn migrate_v3_to_v4(config: &mut Map<String, Value>) -> Result<(), ConfigIssue> {
+    fill_field(config, "change_regional_settings", json!(false));
+    Ok(())
+}
+
+/// Version 5 adds the list of steps, older files run every step with its default policy
+fn migrate_v4_to_v5(config: &mut Map<String, Value>) -> Result<(), ConfigIssue> {
+    fill_field(
+        config,
+        "steps",
+        json!([
+            { "step": "clean_startup" },
+            { "step": "change_locale" },
+            { "step": "change_regional_settings" },
+            { "step": "schedule_resume" },
+            { "step": "reboot" },
+            { "step": "verify_locale" },
+            { "step": "restore_startup" },
+            { "step": "run_setup" }
+        ]),
+    );
+    Ok(())
+}
+
+/// Set a field a migration adds, unless the file sets it already
+fn fill_field(config: &mut Map<String, Value>, key: &str, value: Value) {
+    config.entry(key.to_string()).or_insert(value);
+}
+
+/// Find every structural problem in a config that does not deserialize
+///
+/// serde stops at the first error, so the config is deserialized repeatedly: after each error
+/// the offending field is dropped, or a missing field is filled in from the defaults, until the
+/// config deserializes or no repair is possible.
+fn collect_structural_issues(mut value: Value) -> Vec<ConfigIssue> {
+    let defaults: Value = serde_json::to_value(Config::default()).unwrap_or(Value::Null);
+    let mut issues: Vec<ConfigIssue> = Vec::new();
+    let mut dropped: HashSet<Vec<PathPart>> = HashSet::new();
+    while issues.len() < MAX_CONFIG_ISSUES {
+        let err = match serde_path_to_error::deserialize::<_, Config>(&value) {
+            Ok(_) => break,
+            Err(err) => err,
+        };
+        let mut path: Vec<PathPart> = err
+            .path()
+            .iter()
+            .filter_map(|segment| match segment {
+                serde_path_to_error::Segment::Map { key } => Some(PathPart::Key(key.clone())),
+                serde_path_to_error::Segment::Seq { index } => Some(PathPart::Index(*index)),
+                _ => None,
+            })
+            .collect();
+        let message: String = err.inner().to_string();
+
+        if let Some(field) = missing_field(&message) {
+            path.push(PathPart::Key(field.to_string()));
+            // A field dropped for an invalid value is reported already
+            if !dropped.contains(&path) {
+                issues.push(ConfigIssue {
+                    path: json_path(&path),
+                    message: "is required".to_string(),
+                });
+            }
+            match template_for(&defaults, &path) {
+                Some(template) => set_at(&mut value, &path, template),
+                None => break,
+            }
+        } else {
+            issues.push(ConfigIssue {
+                path: json_path(&path),
+                message,
+            });
+            // List entries are replaced instead of removed, removing would shift later paths
+            match path.last() {
+                Some(PathPart::Key(_)) if remove_at(&mut value, &path) => {
+                    dropped.insert(path);
+                }
+                Some(PathPart::Index(_)) => match template_for(&defaults, &path) {
+                    Some(template) => set_at(&mut value, &path, template),
+                    None => break,
+                },
+                _ => break,
+            }
+        }
+    }
+    issues
+}
+
+/// The field name of a serde "missing field" error
+fn missing_field(message: &str) -> Option<&str> {
+    message.strip_prefix("missing field `")?.split('`').next()
+}
+
+/// Format a path as a JSON path, e.g. `$.autostart_locations[2].hive`
+fn json_path(path: &[PathPart]) -> String {
+    let mut formatted: String = "$".to_string();
+    for part in path {
+        match part {
+            PathPart::Key(key) => {
+                formatted.push('.');
+                formatted.push_str(key);
+            }
+            PathPart::Index(index) => formatted.push_str(&format!("[{}]", index)),
+        }
+    }
+    formatted
+}
+
+/// A valid value for a path, taken from the defaults; list entries use the first default entry
+fn template_for(defaults: &Value, path: &[PathPart]) -> Option<Value> {
+    let mut current: &Value = defaults;
+    for part in path {
+        current = match part {
+            PathPart::Key(key) => current.get(key)?,
+            PathPart::Index(_) => current.get(0)?,
+        };
+    }
+    Some(current.clone())
+}
+
+/// Set the value at a path whose parent exists
+fn set_at(value: &mut Value, path: &[PathPart], new_value: Value) {
+    let Some((last, parent_path)) = path.split_last() else {
+        return;
+    };
+    let Some(parent) = get_mut(value, parent_path) else {
+        return;
+    };
+    match (parent, last) {
+        (Value::Object(object), PathPart::Key(key)) => {
+            object.insert(key.clone(), new_value);
+        }
+        (Value::Array(array), PathPart::Index(index)) if *index < array.len() => {
+            array[*index] = new_value;
+        }
+        _ => {}
+    }
+}
+
+/// Remove the object field at a path
+fn remove_at(value: &mut Value, path: &[PathPart]) -> bool {
+    let Some((PathPart::Key(key), parent_path)) = path.split_last() else {
+        return false;
+    };
+    get_mut(value, parent_path)
+        .and_then(Value::as_object_mut)
+        .is_some_and(|object| object.remove(key).is_some())
+}
+
+/// The value at a path
+fn get_mut<'a>(value: &'a mut Value, path: &[PathPart]) -> Option<&'a mut Value> {
+    let mut current: &mut Value = value;
+    for part in path {
+        current = match part {
+            PathPart::Key(key) => current.get_mut(key)?,
+            PathPart::Index(index) => current.get_mut(*index)?,
+        };
+    }
+    Some(current)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::step::{default_steps, StepSettings};
+
+    #[test]
+    fn version_1_files_get_the_fields_added_since() {
+        let mut value: Value = json!({
+            "clean_startup_apps": true,
+            "restore_startup_apps": true,
+            "first_time_reboot": true,
+            "reboot_timer": 60,
+            "change_locale": true,
+            "startup_clean_mode": "startup_approved"
+        });
+
+        assert_eq!(migrate_config(&mut value).unwrap(), 1);
+
+        assert_eq!(value["schema_version"], json!(CONFIG_SCHEMA_VERSION));
+        // Fields the file sets are kept
+        assert_eq!(value["startup_clean_mode"], json!("startup_approved"));
+        assert_eq!(value["restore_conflict_policy"], json!("keep_current"));
+        assert_eq!(value["startup_policy"]["default_action"], json!("disable"));
+        assert!(value["autostart_locations"]
+            .as_array()
+            .is_some_and(|locations| !locations.is_empty()));
+        assert_eq!(value["locale"]["mui_language"], json!("fa-IR"));
+        assert_eq!(value["change_regional_settings"], json!(false));
+        let parsed = parse_config(value);
+        assert!(parsed.is_ok(), "{:?}", parsed.err());
+    }
+
+    #[test]
+    fn version_2_files_get_the_persian_locale() {
+        let mut value: Value = json!({ "schema_version": 2, "locale": { "user_locale": "en-US" } });
+        assert_eq!(migrate_config(&mut value).unwrap(), 2);
+        // A file setting the locale keeps it, the fields it leaves out come from the defaults
+        assert_eq!(value["locale"], json!({ "user_locale": "en-US" }));
+
+        let mut value: Value = json!({ "schema_version": 2 });
+        migrate_config(&mut value).unwrap();
+        let locale: LocaleSettings = serde_json::from_value(value["locale"].clone()).unwrap();
+        assert_eq!(locale.system_locale, "fa-IR");
+        assert_eq!(locale.input_languages, vec!["0429:00000429".to_string()]);
+    }
+
+    #[test]
+    fn version_3_files_keep_the_regional_settings_off() {
+        let mut off: Value = json!({ "schema_version": 3 });
+        migrate_config(&mut off).unwrap();
+        assert_eq!(off["change_regional_settings"], json!(false));
+
+        let mut on: Value = json!({ "schema_version": 3, "change_regional_settings": true });
+        migrate_config(&mut on).unwrap();
+        assert_eq!(on["change_regional_settings"], json!(true));
+    }
+
+    #[test]
+    fn version_4_files_run_every_step() {
+        let mut value: Value = json!({ "schema_version": 4 });
+        migrate_config(&mut value).unwrap();
+        let steps: Vec<StepSettings> = serde_json::from_value(value["steps"].clone()).unwrap();
+        assert_eq!(steps, default_steps());
+
+        let mut value: Value = json!({ "schema_version": 4, "steps": [{ "step": "reboot" }] });
+        migrate_config(&mut value).unwrap();
+        assert_eq!(value["steps"], json!([{ "step": "reboot" }]));
+    }
+
+    fn issue_paths(value: Value) -> Vec<String> {
+        let mut paths: Vec<String> = parse_config(value)
+            .unwrap_err()
+            .into_iter()
+            .map(|issue| issue.path)
+            .collect();
+        paths.sort();
+        paths
+    }
+
+    #[test]
+    fn every_structural_problem_is_reported_with_its_path() {
+        let value: Value = json!({
+            "schema_version": CONFIG_SCHEMA_VERSION,
+            "clean_startup_apps": true,
+            "restore_startup_apps": true,
+            "first_time_reboot": true,
+            "reboot_timer": "soon",
+            "change_locale": true,
+            "autostart_locations": [{
+                "name": "run",
+                "hive": "HKEY_NOWHERE",
+                "view": "x64",
+                "path": "SOFTWARE\\Run",
+                "action": "clean"
+            }],
+            "startup_policy": { "keep": [{ "pattern": "OneDrive" }] }
+        });
+
+        assert_eq!(
+            issue_paths(value),
+            vec![
+                "$.autostart_locations[0].hive",
+                "$.reboot_timer",
+                "$.startup_policy.keep[0].pattern",
+            ]
+        );
+    }
+
+    #[test]
+    fn every_invalid_value_is_reported_with_its_path() {
+        let mut config = Config {
+            reboot_timer: MAX_REBOOT_TIMER + 1,
+            ..Config::default()
+        };
+        config.autostart_locations[0].name = "run key".to_string();
+        config.autostart_locations[1].values = vec!["App".to_string(), String::new()];
+        let value: Value = serde_json::to_value(config).unwrap();
+
+        assert_eq!(
+            issue_paths(value),
+            vec![
+                "$.autostart_locations[0].name",
+                "$.autostart_locations[1].values[1]",
+                "$.reboot_timer",
+            ]
+        );
+    }
+
+    #[test]
+    fn newer_and_invalid_versions_are_rejected() {
+        let mut newer: Value = json!({ "schema_version": CONFIG_SCHEMA_VERSION + 1 });
+        assert_eq!(
+            migrate_config(&mut newer).unwrap_err().path,
+            "$.schema_version"
+        );
+
+        let mut invalid: Value = json!({ "schema_version": 0 });
+        assert_eq!(
+            migrate_config(&mut invalid).unwrap_err().path,
+            "$.schema_version"
+        );
+    }
+}
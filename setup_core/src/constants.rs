@@ -0,0 +1,36 @@
+pub const XML_FILE_NAME: &str = "locale.xml";
+pub const TEMP_FOLDER_PREFIX: &str = "moein_assistant_locale";
+pub const CONTROL_PANEL_PROGRAM: &str = "control.exe";
+pub const REBOOT_REGISTRY_PATH: &str = "SOFTWARE\\MoeinAssistant";
+pub const REGISTRY_STARTUP_PATH: &str = "SOFTWARE\\Microsoft\\Windows\\CurrentVersion\\Run";
+pub const REGISTRY_STARTUP_APPROVED_RUN_PATH: &str = "SOFTWARE\\Microsoft\\Windows\\CurrentVersion\\Explorer\\StartupApproved\\Run";
+pub const REGISTRY_STARTUP_APPROVED_RUN32_PATH: &str = "SOFTWARE\\Microsoft\\Windows\\CurrentVersion\\Explorer\\StartupApproved\\Run32";
+pub const REGISTRY_RUNONCE_PATH: &str = "SOFTWARE\\Microsoft\\Windows\\CurrentVersion\\RunOnce";
+pub const REGISTRY_RUNSERVICES_PATH: &str = "SOFTWARE\\Microsoft\\Windows\\CurrentVersion\\RunServices";
+pub const REGISTRY_POLICIES_RUN_PATH: &str = "SOFTWARE\\Microsoft\\Windows\\CurrentVersion\\Policies\\Explorer\\Run";
+pub const REGISTRY_WINLOGON_PATH: &str = "SOFTWARE\\Microsoft\\Windows NT\\CurrentVersion\\Winlogon";
+pub const REGISTRY_RESTORE_EXECUTABLE: &str = "registry_restore.exe";
+pub const DATA_FOLDER_NAME: &str = "data";
+pub const CONFIG_FILE_NAME: &str = "config.json";
+pub const MACHINE_CONFIG_FILE_NAME: &str = "config.machine.json";
+pub const CONFIG_ENV_PREFIX: &str = "MOEIN_ASSISTANT_";
+pub const LOG_FOLDER_NAME: &str = "logs";
+pub const LOG_FILE_NAME: &str = "setup_assistant.log";
+pub const REPORT_FOLDER_NAME: &str = "reports";
+pub const STARTUP_BACKUP_FOLDER_NAME: &str = "startup_backup";
+pub const BACKUP_MANIFEST_FILE_NAME: &str = "backup_manifest.json";
+pub const RESTORED_MANIFEST_FILE_NAME: &str = "backup_manifest.restored.json";
+pub const JOURNAL_FILE_NAME: &str = "journal.json";
+pub const STATE_FILE_NAME: &str = "state.json";
+pub const REGIONAL_SNAPSHOT_FILE_NAME: &str = "regional_snapshot.json";
+pub const REBOOTED_KEY_NAME: &str = "is_rebooted";
+pub const RESUME_TASK_NAME: &str = "MoeinAssistant";
+pub const SETUP_EXE_NAME: &str = "setup.exe";
+pub const REGISTRY_INTERNATIONAL_PATH: &str = "Control Panel\\International";
+pub const REGISTRY_GEO_PATH: &str = "Control Panel\\International\\Geo";
+pub const REGISTRY_KEYBOARD_PRELOAD_PATH: &str = "Keyboard Layout\\Preload";
+pub const REGISTRY_KEYBOARD_SUBSTITUTES_PATH: &str = "Keyboard Layout\\Substitutes";
+pub const REGISTRY_NLS_LOCALE_PATH: &str = "SYSTEM\\CurrentControlSet\\Control\\Nls\\Locale";
+pub const REGISTRY_TIME_ZONE_PATH: &str = "SYSTEM\\CurrentControlSet\\Control\\TimeZoneInformation";
+pub const TIME_ZONE_PROGRAM: &str = "tzutil.exe";
+pub const REGISTRY_WINDOWS_VERSION_PATH: &str = "SOFTWARE\\Microsoft\\Windows NT\\CurrentVersion";
@@ -0,0 +1,120 @@
+// +------------------+
+// |    dependencies  |
+// +------------------+
+
+// std
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::process::Command;
+
+// internal: registry_backend
+use crate::registry_backend::RegistryBackend;
+
+// +------------------+
+// |      types       |
+// +------------------+
+
+/// Everything the setup flow does to the machine
+///
+/// The steps only change the registry, files and processes through this trait, so the same
+/// flow can be carried out or just planned.
+pub trait Executor {
+    /// The registry the steps read and write
+    fn registry(&self) -> &dyn RegistryBackend;
+
+    /// Write a file, creating its parent folders
+    fn write_file(&self, path: &Path, data: &[u8]) -> io::Result<()>;
+
+    /// Move a file, creating the parent folders of the destination
+    fn move_file(&self, from: &Path, to: &Path) -> io::Result<()>;
+
+    /// Delete a file
+    fn remove_file(&self, path: &Path) -> io::Result<()>;
+
+    /// Delete an empty folder
+    fn remove_folder(&self, path: &Path) -> io::Result<()>;
+
+    /// Run a program and wait for it to exit
+    ///
+    /// # Returns
+    ///
+    /// `std::io::Result<i32>` - The exit code, `-1` if the program was terminated
+    fn run_command(&self, program: &str, args: &[String]) -> io::Result<i32>;
+
+    /// Run a program with a command line that is passed on unchanged and wait for it to exit
+    ///
+    /// For programs such as `control.exe` that parse their command line themselves and do not
+    /// accept the quoting `run_command` applies.
+    ///
+    /// # Returns
+    ///
+    /// `std::io::Result<i32>` - The exit code, `-1` if the program was terminated
+    fn run_raw_command(&self, program: &str, command_line: &str) -> io::Result<i32>;
+}
+
+/// Executor carrying out every action on the machine
+pub struct SystemExecutor<'a> {
+    backend: &'a dyn RegistryBackend,
+}
+
+impl<'a> SystemExecutor<'a> {
+    /// Create an executor changing the registry through `backend`
+    pub fn new(backend: &'a dyn RegistryBackend) -> SystemExecutor<'a> {
+        SystemExecutor { backend }
+    }
+}
+
+impl Executor for SystemExecutor<'_> {
+    fn registry(&self) -> &dyn RegistryBackend {
+        self.backend
+    }
+
+    fn write_file(&self, path: &Path, data: &[u8]) -> io::Result<()> {
+        if let Some(parent) = path
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+        {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, data)
+    }
+
+    fn move_file(&self, from: &Path, to: &Path) -> io::Result<()> {
+        if let Some(parent) = to.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)?;
+        }
+        // Renaming fails across volumes, copy and delete instead
+        if fs::rename(from, to).is_ok() {
+            return Ok(());
+        }
+        fs::copy(from, to)?;
+        fs::remove_file(from)
+    }
+
+    fn remove_file(&self, path: &Path) -> io::Result<()> {
+        fs::remove_file(path)
+    }
+
+    fn remove_folder(&self, path: &Path) -> io::Result<()> {
+        fs::remove_dir(path)
+    }
+
+    fn run_command(&self, program: &str, args: &[String]) -> io::Result<i32> {
+        let status = Command::new(program).args(args).status()?;
+        Ok(status.code().unwrap_or(-1))
+    }
+
+    fn run_raw_command(&self, program: &str, command_line: &str) -> io::Result<i32> {
+        let mut command: Command = Command::new(program);
+        #[cfg(windows)]
+        {
+            use std::os::windows::process::CommandExt;
+            command.raw_arg(command_line);
+        }
+        #[cfg(not(windows))]
+        command.arg(command_line);
+        let status = command.status()?;
+        Ok(status.code().unwrap_or(-1))
+    }
+}
@@ -0,0 +1,139 @@
+// +------------------+
+// |    dependencies  |
+// +------------------+
+
+// std
+use std::fmt;
+use std::process::ExitCode;
+
+// +------------------+
+// |      types       |
+// +------------------+
+
+/// The outcome setup_assistant and registry_restore exit with
+///
+/// Deployment and RMM scripts wrapping the binaries tell the outcome from the exit code, the
+/// codes must not change between versions. `setup_assistant --help` prints the table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExitStatus {
+    /// 0: everything asked for is done
+    Success,
+    /// 1: an error not covered by another status
+    Failure,
+    /// 2: done, but a step failed with the `continue` policy or backups were left to restore
+    SuccessWithWarnings,
+    /// 3: the config is invalid, nothing is changed
+    ConfigError,
+    /// 4: the data folder, state, journal or log is unusable, or a check failed
+    PreflightFailure,
+    /// 5: a step failed and the run stopped, the next launch retries it
+    StepFailure,
+    /// 6: a step failed and the changes of the run were undone
+    RolledBack,
+    /// 7: the run waits for the reboot and resumes after it
+    RebootPending,
+}
+
+impl ExitStatus {
+    /// Every status, by exit code
+    pub const ALL: [ExitStatus; 8] = [
+        ExitStatus::Success,
+        ExitStatus::Failure,
+        ExitStatus::SuccessWithWarnings,
+        ExitStatus::ConfigError,
+        ExitStatus::PreflightFailure,
+        ExitStatus::StepFailure,
+        ExitStatus::RolledBack,
+        ExitStatus::RebootPending,
+    ];
+
+    /// The process exit code
+    pub fn code(self) -> u8 {
+        match self {
+            ExitStatus::Success => 0,
+            ExitStatus::Failure => 1,
+            ExitStatus::SuccessWithWarnings => 2,
+            ExitStatus::ConfigError => 3,
+            ExitStatus::PreflightFailure => 4,
+            ExitStatus::StepFailure => 5,
+            ExitStatus::RolledBack => 6,
+            ExitStatus::RebootPending => 7,
+        }
+    }
+
+    /// Name of the status, e.g. `reboot_pending`
+    pub fn name(self) -> &'static str {
+        match self {
+            ExitStatus::Success => "success",
+            ExitStatus::Failure => "failure",
+            ExitStatus::SuccessWithWarnings => "success_with_warnings",
+            ExitStatus::ConfigError => "config_error",
+            ExitStatus::PreflightFailure => "preflight_failure",
+            ExitStatus::StepFailure => "step_failure",
+            ExitStatus::RolledBack => "rolled_back",
+            ExitStatus::RebootPending => "reboot_pending",
+        }
+    }
+
+    /// What the status means, for the help text
+    pub fn description(self) -> &'static str {
+        match self {
+            ExitStatus::Success => "everything asked for is done",
+            ExitStatus::Failure => "an error not covered by another code, see the log",
+            ExitStatus::SuccessWithWarnings => {
+                "done, but a step failed with the `continue` policy or backups were left to restore"
+            }
+            ExitStatus::ConfigError => "the config is invalid, nothing is changed",
+            ExitStatus::PreflightFailure => {
+                "the data folder, state, journal or log is unusable, or a check failed"
+            }
+            ExitStatus::StepFailure => {
+                "a step failed and the run stopped, the next launch retries it"
+            }
+            ExitStatus::RolledBack => "a step failed and the changes of the run were undone",
+            ExitStatus::RebootPending => "the run waits for the reboot and resumes after it",
+        }
+    }
+}
+
+impl fmt::Display for ExitStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({})", self.name(), self.code())
+    }
+}
+
+impl From<ExitStatus> for ExitCode {
+    fn from(status: ExitStatus) -> Self {
+        ExitCode::from(status.code())
+    }
+}
+
+// +------------------+
+// | public functions |
+// +------------------+
+
+/// The exit code table, one line per status
+///
+/// # Returns
+///
+/// `String` - The table, e.g. `  7  reboot_pending  the run waits for the reboot ...`
+pub fn exit_code_table() -> String {
+    let width: usize = ExitStatus::ALL
+        .iter()
+        .map(|status| status.name().len())
+        .max()
+        .unwrap_or_default();
+    ExitStatus::ALL
+        .iter()
+        .map(|status| {
+            format!(
+                "  {}  {:width$}  {}",
+                status.code(),
+                status.name(),
+                status.description(),
+                width = width
+            )
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
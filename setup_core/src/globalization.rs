@@ -0,0 +1,314 @@
+// +------------------+
+// |    dependencies  |
+// +------------------+
+
+// std
+use std::fmt;
+
+/// Namespace of the GlobalizationServices answer file read by `control.exe intl.cpl`
+const GS_NAMESPACE: &str = "urn:longhornGlobalizationUnattend";
+
+// +------------------+
+// |      types       |
+// +------------------+
+
+/// A `gs:GlobalizationServices` document, sections left empty are not written
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct GlobalizationServices {
+    /// Users the settings are applied to
+    pub users: Vec<GsUser>,
+    pub mui_language_preferences: Option<MuiLanguagePreferences>,
+    pub user_locale: Option<UserLocale>,
+    /// Locale for programs that do not support Unicode
+    pub system_locale: Option<String>,
+    /// Keyboards to add or remove, in order
+    pub input_preferences: Vec<InputLanguage>,
+    /// Home location as a Windows GeoID
+    pub location_preferences: Option<u32>,
+}
+
+/// A `gs:User` entry of the `gs:UserList`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GsUser {
+    /// Only `Current` is accepted by Windows
+    pub user_id: String,
+    pub copy_settings_to_system_account: bool,
+    pub copy_settings_to_default_user_account: bool,
+}
+
+/// Display language and the language used where it has no translation
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MuiLanguagePreferences {
+    pub language: String,
+    pub fallback: Option<String>,
+}
+
+/// Locale for formats of dates, numbers and currency
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UserLocale {
+    pub name: String,
+    pub set_as_current: bool,
+}
+
+/// Whether a keyboard is added or removed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputAction {
+    Add,
+    Remove,
+}
+
+impl fmt::Display for InputAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InputAction::Add => write!(f, "add"),
+            InputAction::Remove => write!(f, "remove"),
+        }
+    }
+}
+
+/// A `gs:InputLanguageID` entry, `id` is `language:layout` such as `0429:00000429`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InputLanguage {
+    pub action: InputAction,
+    pub id: String,
+    /// Make this keyboard the default one
+    pub default: bool,
+}
+
+impl GsUser {
+    /// The user running the assistant
+    ///
+    /// # Arguments
+    ///
+    /// * `copy_to_system` - Copy the settings to the system account
+    /// * `copy_to_default_user` - Copy the settings to the default user account
+    ///
+    /// # Returns
+    ///
+    /// `GsUser` - The entry for the current user
+    pub fn current(copy_to_system: bool, copy_to_default_user: bool) -> Self {
+        Self {
+            user_id: "Current".to_string(),
+            copy_settings_to_system_account: copy_to_system,
+            copy_settings_to_default_user_account: copy_to_default_user,
+        }
+    }
+}
+
+impl GlobalizationServices {
+    /// Serialize the document, attribute values are escaped
+    ///
+    /// # Returns
+    ///
+    /// `String` - The XML document
+    pub fn to_xml(&self) -> String {
+        let mut writer: XmlWriter = XmlWriter::default();
+        writer.declaration();
+        writer.open(
+            "gs:GlobalizationServices",
+            &[("xmlns:gs", GS_NAMESPACE.to_string())],
+        );
+
+        if !self.users.is_empty() {
+            writer.open("gs:UserList", &[]);
+            for user in &self.users {
+                writer.empty(
+                    "gs:User",
+                    &[
+                        ("UserID", user.user_id.clone()),
+                        (
+                            "CopySettingsToSystemAcct",
+                            user.copy_settings_to_system_account.to_string(),
+                        ),
+                        (
+                            "CopySettingsToDefaultUserAcct",
+                            user.copy_settings_to_default_user_account.to_string(),
+                        ),
+                    ],
+                );
+            }
+            writer.close("gs:UserList");
+        }
+
+        if let Some(mui) = &self.mui_language_preferences {
+            writer.comment("MUI language preferences");
+            writer.open("gs:MUILanguagePreferences", &[]);
+            writer.empty("gs:MUILanguage", &[("Value", mui.language.clone())]);
+            if let Some(fallback) = &mui.fallback {
+                writer.empty("gs:MUIFallback", &[("Value", fallback.clone())]);
+            }
+            writer.close("gs:MUILanguagePreferences");
+        }
+
+        if let Some(user_locale) = &self.user_locale {
+            writer.comment("User locale");
+            writer.open("gs:UserLocale", &[]);
+            writer.empty(
+                "gs:Locale",
+                &[
+                    ("Name", user_locale.name.clone()),
+                    ("SetAsCurrent", user_locale.set_as_current.to_string()),
+                ],
+            );
+            writer.close("gs:UserLocale");
+        }
+
+        if let Some(system_locale) = &self.system_locale {
+            writer.comment("Non Unicode programs");
+            writer.empty("gs:SystemLocale", &[("Name", system_locale.clone())]);
+        }
+
+        if let Some(geo_id) = self.location_preferences {
+            writer.comment("Home location");
+            writer.open("gs:LocationPreferences", &[]);
+            writer.empty("gs:GeoID", &[("Value", geo_id.to_string())]);
+            writer.close("gs:LocationPreferences");
+        }
+
+        if !self.input_preferences.is_empty() {
+            writer.open("gs:InputPreferences", &[]);
+            for input in &self.input_preferences {
+                let mut attributes: Vec<(&str, String)> = vec![
+                    ("Action", input.action.to_string()),
+                    ("ID", input.id.clone()),
+                ];
+                if input.default {
+                    attributes.push(("Default", "true".to_string()));
+                }
+                writer.empty("gs:InputLanguageID", &attributes);
+            }
+            writer.close("gs:InputPreferences");
+        }
+
+        writer.close("gs:GlobalizationServices");
+        writer.output
+    }
+}
+
+/// Escape a value for a double-quoted XML attribute
+///
+/// # Arguments
+///
+/// * `value` - The raw attribute value
+///
+/// # Returns
+///
+/// `String` - The value with markup characters replaced by entities
+pub fn escape_attribute(value: &str) -> String {
+    let mut escaped: String = String::with_capacity(value.len());
+    for character in value.chars() {
+        match character {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            // Parsers normalize raw whitespace in attributes to spaces
+            '\t' => escaped.push_str("&#9;"),
+            '\n' => escaped.push_str("&#10;"),
+            '\r' => escaped.push_str("&#13;"),
+            // Other control characters are not allowed in XML 1.0 at all
+            character if character.is_control() => {}
+            character => escaped.push(character),
+        }
+    }
+    escaped
+}
+
+// +-----------------------+
+// |  private functions    |
+// +-----------------------+
+
+/// Writes indented elements into a string
+#[derive(Default)]
+struct XmlWriter {
+    output: String,
+    depth: usize,
+}
+
+impl XmlWriter {
+    fn declaration(&mut self) {
+        self.output.push_str("<?xml version=\"1.0\"?>\n");
+    }
+
+    fn open(&mut self, tag: &str, attributes: &[(&str, String)]) {
+        self.line(&format!("<{}{}>", tag, render_attributes(attributes)));
+        self.depth += 1;
+    }
+
+    fn empty(&mut self, tag: &str, attributes: &[(&str, String)]) {
+        self.line(&format!("<{}{} />", tag, render_attributes(attributes)));
+    }
+
+    fn close(&mut self, tag: &str) {
+        self.depth = self.depth.saturating_sub(1);
+        self.line(&format!("</{}>", tag));
+    }
+
+    /// `text` is a fixed label, it must not contain `--`
+    fn comment(&mut self, text: &str) {
+        self.line(&format!("<!-- {} -->", text));
+    }
+
+    fn line(&mut self, text: &str) {
+        for _ in 0..self.depth {
+            self.output.push_str("    ");
+        }
+        self.output.push_str(text);
+        self.output.push('\n');
+    }
+}
+
+fn render_attributes(attributes: &[(&str, String)]) -> String {
+    attributes
+        .iter()
+        .map(|(name, value)| format!(" {}=\"{}\"", name, escape_attribute(value)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn attribute_values_are_escaped() {
+        assert_eq!(
+            escape_attribute("a&b <c> \"d\" 'e'"),
+            "a&amp;b &lt;c&gt; &quot;d&quot; &apos;e&apos;"
+        );
+        assert_eq!(
+            escape_attribute("tab\tline\nreturn\r"),
+            "tab&#9;line&#10;return&#13;"
+        );
+        assert_eq!(escape_attribute("bell\u{7}null\0"), "bellnull");
+        assert_eq!(escape_attribute("fa-IR"), "fa-IR");
+    }
+
+    #[test]
+    fn documents_escape_every_attribute() {
+        let document = GlobalizationServices {
+            users: vec![GsUser {
+                user_id: "\"Current\"".to_string(),
+                copy_settings_to_system_account: true,
+                copy_settings_to_default_user_account: false,
+            }],
+            system_locale: Some("fa-IR\" injected=\"1".to_string()),
+            input_preferences: vec![InputLanguage {
+                action: InputAction::Remove,
+                id: "<0409:00000409>".to_string(),
+                default: true,
+            }],
+            ..GlobalizationServices::default()
+        };
+        let xml: String = document.to_xml();
+        assert!(xml.contains("UserID=\"&quot;Current&quot;\""));
+        assert!(xml.contains("<gs:SystemLocale Name=\"fa-IR&quot; injected=&quot;1\" />"));
+        assert!(xml.contains(
+            "<gs:InputLanguageID Action=\"remove\" ID=\"&lt;0409:00000409&gt;\" Default=\"true\" />"
+        ));
+        // Sections without settings are left out
+        assert!(!xml.contains("gs:MUILanguagePreferences"));
+        assert!(!xml.contains("gs:UserLocale"));
+        assert!(!xml.contains("gs:LocationPreferences"));
+    }
+}
@@ -0,0 +1,36 @@
+//! Logic shared by setup_assistant and registry_restore
+//!
+//! The binaries only parse their arguments and call into this crate: logging, registry access,
+//! backups and their restore, path resolution and the steps of a setup run live here.
+
+pub mod autostart;
+pub mod backup_manifest;
+pub mod config;
+pub mod config_schema;
+pub mod constants;
+pub mod executor;
+pub mod exit_code;
+pub mod globalization;
+pub mod locale;
+pub mod locale_catalog;
+pub mod locale_verify;
+pub mod logging;
+pub mod merge;
+pub mod paths;
+pub mod plan;
+pub mod reg_file;
+pub mod regional;
+pub mod registry_backend;
+pub mod registry_handler;
+pub mod registry_journal;
+pub mod restore;
+pub mod run_report;
+pub mod run_state;
+pub mod startup_folder;
+pub mod startup_policy;
+pub mod step;
+pub mod steps;
+#[cfg(test)]
+mod test_support;
+pub mod utilities;
+pub mod xml_handler;
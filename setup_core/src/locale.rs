@@ -0,0 +1,216 @@
+// +------------------+
+// |    dependencies  |
+// +------------------+
+
+// serde
+use serde::{Deserialize, Serialize};
+
+// internal: globalization
+use crate::globalization::{
+    GlobalizationServices, GsUser, InputAction, InputLanguage, MuiLanguagePreferences, UserLocale,
+};
+
+// internal: locale_catalog
+use crate::locale_catalog::resolve_input_language;
+
+// +------------------+
+// |      types       |
+// +------------------+
+
+/// What to do when the registry does not show the applied locale settings
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LocaleVerification {
+    /// Do not check the registry
+    Off,
+    /// Log every setting that differs
+    #[default]
+    Report,
+    /// Log the differences and apply the settings once more
+    Reapply,
+}
+
+/// Language, locale and keyboard settings applied by the locale step
+///
+/// The defaults are a Persian UI with English fallback, Persian user and system locale and the
+/// Persian keyboard.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LocaleSettings {
+    /// Display language, e.g. `fa-IR`
+    pub mui_language: String,
+    /// Display language used where the display language has no translation
+    pub mui_fallback: Option<String>,
+    /// Locale for formats of dates, numbers and currency
+    pub user_locale: String,
+    /// Locale for programs that do not support Unicode
+    pub system_locale: String,
+    /// Keyboards to add, as `LCID:KLID` ids such as `0429:00000429` or as a locale such as
+    /// `fa-IR` for its default layout
+    pub input_languages: Vec<String>,
+    /// Home location as a Windows GeoID, e.g. `116` for Iran; left unchanged if not set
+    pub home_location: Option<u32>,
+    /// Copy the settings to the system account, used by the welcome screen
+    pub copy_settings_to_system_account: bool,
+    /// Copy the settings to the default account new users are created from
+    pub copy_settings_to_default_user_account: bool,
+    /// Checked after applying and again after the reboot
+    pub verification: LocaleVerification,
+}
+
+impl Default for LocaleSettings {
+    fn default() -> Self {
+        Self {
+            mui_language: "fa-IR".to_string(),
+            mui_fallback: Some("en-US".to_string()),
+            user_locale: "fa-IR".to_string(),
+            system_locale: "fa-IR".to_string(),
+            input_languages: vec!["0429:00000429".to_string()],
+            home_location: None,
+            copy_settings_to_system_account: true,
+            copy_settings_to_default_user_account: true,
+            verification: LocaleVerification::default(),
+        }
+    }
+}
+
+impl LocaleSettings {
+    /// The GlobalizationServices document applying these settings to the current user
+    ///
+    /// # Returns
+    ///
+    /// `GlobalizationServices` - The document model
+    pub fn to_globalization_services(&self) -> GlobalizationServices {
+        GlobalizationServices {
+            users: vec![GsUser::current(
+                self.copy_settings_to_system_account,
+                self.copy_settings_to_default_user_account,
+            )],
+            mui_language_preferences: Some(MuiLanguagePreferences {
+                language: self.mui_language.clone(),
+                fallback: self.mui_fallback.clone(),
+            }),
+            user_locale: Some(UserLocale {
+                name: self.user_locale.clone(),
+                set_as_current: true,
+            }),
+            system_locale: Some(self.system_locale.clone()),
+            input_preferences: self
+                .input_languages
+                .iter()
+                .map(|id| InputLanguage {
+                    action: InputAction::Add,
+                    // The config is validated before it is used, keep the value as is otherwise
+                    id: resolve_input_language(id).unwrap_or_else(|_| id.clone()),
+                    default: false,
+                })
+                .collect(),
+            location_preferences: self.home_location,
+        }
+    }
+}
+
+// +------------------+
+// | public functions |
+// +------------------+
+
+/// Render the settings as the GlobalizationServices XML read by `control.exe intl.cpl`
+///
+/// # Arguments
+///
+/// * `settings` - The locale settings to apply
+///
+/// # Returns
+///
+/// `String` - The XML document
+pub fn render_locale_xml(settings: &LocaleSettings) -> String {
+    settings.to_globalization_services().to_xml()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The locale XML applied before the settings were configurable
+    const XML_CONTENT: &str = r#"<?xml version="1.0"?>
+    <gs:GlobalizationServices xmlns:gs="urn:longhornGlobalizationUnattend">
+        <gs:UserList>
+            <gs:User CopySettingsToSystemAcct="true" CopySettingsToDefaultUserAcct="true"
+                UserID="Current" />
+        </gs:UserList>
+
+        <!-- MUI language preferences -->
+        <gs:MUILanguagePreferences>
+            <gs:MUILanguage Value="fa-IR" />
+            <gs:MUIFallback Value="en-US" />
+        </gs:MUILanguagePreferences>
+
+        <!--User
+        Locale-->
+        <gs:UserLocale>
+            <gs:Locale SetAsCurrent="true" Name="fa-IR" />
+        </gs:UserLocale>
+
+        <!-- Non Unicode programs -->
+        <gs:SystemLocale Name="fa-IR" />
+
+        <gs:InputPreferences>
+            <gs:InputLanguageID Action="add" ID="0429:00000429" />
+        </gs:InputPreferences>
+    </gs:GlobalizationServices>"#;
+
+    /// The tags of a document with their attributes sorted, comments and layout dropped
+    fn tags(xml: &str) -> Vec<(String, Vec<(String, String)>)> {
+        let mut rest: &str = xml;
+        let mut tags: Vec<(String, Vec<(String, String)>)> = Vec::new();
+        while let Some(start) = rest.find('<') {
+            rest = &rest[start..];
+            if let Some(comment) = rest.strip_prefix("<!--") {
+                rest = &comment[comment.find("-->").unwrap() + 3..];
+                continue;
+            }
+            let end: usize = rest.find('>').unwrap();
+            let tag: &str = rest[1..end].trim_end_matches(['/', '?']);
+            rest = &rest[end + 1..];
+
+            let (name, mut attributes) = tag.split_once(char::is_whitespace).unwrap_or((tag, ""));
+            let mut parsed: Vec<(String, String)> = Vec::new();
+            while let Some((attribute, value)) = attributes.split_once("=\"") {
+                let (value, remaining) = value.split_once('"').unwrap();
+                parsed.push((attribute.trim().to_string(), value.to_string()));
+                attributes = remaining;
+            }
+            parsed.sort();
+            tags.push((name.to_string(), parsed));
+        }
+        tags
+    }
+
+    #[test]
+    fn default_settings_render_the_original_xml() {
+        assert_eq!(
+            tags(&render_locale_xml(&LocaleSettings::default())),
+            tags(XML_CONTENT)
+        );
+    }
+
+    #[test]
+    fn configured_settings_are_rendered() {
+        let settings = LocaleSettings {
+            mui_language: "en-US".to_string(),
+            mui_fallback: None,
+            user_locale: "de-DE".to_string(),
+            input_languages: vec!["0407:00000407".to_string()],
+            home_location: Some(94),
+            copy_settings_to_system_account: false,
+            ..LocaleSettings::default()
+        };
+        let xml: String = render_locale_xml(&settings);
+        assert!(xml.contains("<gs:MUILanguage Value=\"en-US\" />"));
+        assert!(!xml.contains("gs:MUIFallback"));
+        assert!(xml.contains("<gs:Locale Name=\"de-DE\" SetAsCurrent=\"true\" />"));
+        assert!(xml.contains("<gs:GeoID Value=\"94\" />"));
+        assert!(xml.contains("<gs:InputLanguageID Action=\"add\" ID=\"0407:00000407\" />"));
+        assert!(xml.contains("CopySettingsToSystemAcct=\"false\""));
+    }
+}
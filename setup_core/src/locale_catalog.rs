@@ -0,0 +1,475 @@
+// +------------------+
+// |      types       |
+// +------------------+
+
+/// A keyboard layout, `klid` is the 8 hex digit layout id such as `00000429`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyboardLayout {
+    pub klid: &'static str,
+    pub name: &'static str,
+}
+
+/// A locale known to the catalog
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LocaleInfo {
+    /// BCP-47 tag, e.g. `fa-IR`
+    pub tag: &'static str,
+    pub name: &'static str,
+    /// Windows language id, the first part of an input language id
+    pub lcid: u16,
+    /// Three letter name Windows stores as `sLanguage`, e.g. `FAR`
+    pub abbreviation: &'static str,
+    /// Layout Windows adds with the language
+    pub keyboard: KeyboardLayout,
+    pub alternative_keyboards: &'static [KeyboardLayout],
+    /// Windows GeoID of the country or region
+    pub geo_id: u32,
+}
+
+impl LocaleInfo {
+    /// The default layout followed by the alternatives
+    pub fn keyboards(&self) -> impl Iterator<Item = &KeyboardLayout> {
+        std::iter::once(&self.keyboard).chain(self.alternative_keyboards)
+    }
+
+    /// Input language id of the default layout, e.g. `0429:00000429`
+    pub fn input_language(&self) -> String {
+        format!("{:04X}:{}", self.lcid, self.keyboard.klid)
+    }
+}
+
+const fn layout(klid: &'static str, name: &'static str) -> KeyboardLayout {
+    KeyboardLayout { klid, name }
+}
+
+/// Locales the assistant can apply, keyed by tag
+pub const LOCALES: &[LocaleInfo] = &[
+    LocaleInfo {
+        tag: "ar-AE",
+        name: "Arabic (United Arab Emirates)",
+        lcid: 0x3801,
+        abbreviation: "ARU",
+        keyboard: layout("00000401", "Arabic (101)"),
+        alternative_keyboards: &[layout("00010401", "Arabic (102)")],
+        geo_id: 224,
+    },
+    LocaleInfo {
+        tag: "ar-IQ",
+        name: "Arabic (Iraq)",
+        lcid: 0x0801,
+        abbreviation: "ARI",
+        keyboard: layout("00000401", "Arabic (101)"),
+        alternative_keyboards: &[layout("00010401", "Arabic (102)")],
+        geo_id: 121,
+    },
+    LocaleInfo {
+        tag: "ar-SA",
+        name: "Arabic (Saudi Arabia)",
+        lcid: 0x0401,
+        abbreviation: "ARA",
+        keyboard: layout("00000401", "Arabic (101)"),
+        alternative_keyboards: &[
+            layout("00010401", "Arabic (102)"),
+            layout("00020401", "Arabic (102) AZERTY"),
+        ],
+        geo_id: 205,
+    },
+    LocaleInfo {
+        tag: "az-Latn-AZ",
+        name: "Azerbaijani (Latin, Azerbaijan)",
+        lcid: 0x042C,
+        abbreviation: "AZE",
+        keyboard: layout("0000042C", "Azerbaijani Latin"),
+        alternative_keyboards: &[],
+        geo_id: 5,
+    },
+    LocaleInfo {
+        tag: "de-DE",
+        name: "German (Germany)",
+        lcid: 0x0407,
+        abbreviation: "DEU",
+        keyboard: layout("00000407", "German"),
+        alternative_keyboards: &[layout("00010407", "German (IBM)")],
+        geo_id: 94,
+    },
+    LocaleInfo {
+        tag: "en-GB",
+        name: "English (United Kingdom)",
+        lcid: 0x0809,
+        abbreviation: "ENG",
+        keyboard: layout("00000809", "United Kingdom"),
+        alternative_keyboards: &[layout("00000452", "United Kingdom Extended")],
+        geo_id: 242,
+    },
+    LocaleInfo {
+        tag: "en-US",
+        name: "English (United States)",
+        lcid: 0x0409,
+        abbreviation: "ENU",
+        keyboard: layout("00000409", "US"),
+        alternative_keyboards: &[
+            layout("00020409", "United States-International"),
+            layout("00010409", "United States-Dvorak"),
+        ],
+        geo_id: 244,
+    },
+    LocaleInfo {
+        tag: "es-ES",
+        name: "Spanish (Spain)",
+        lcid: 0x0C0A,
+        abbreviation: "ESN",
+        keyboard: layout("0000040A", "Spanish"),
+        alternative_keyboards: &[layout("0001040A", "Spanish Variation")],
+        geo_id: 217,
+    },
+    LocaleInfo {
+        tag: "fa-IR",
+        name: "Persian (Iran)",
+        lcid: 0x0429,
+        abbreviation: "FAR",
+        keyboard: layout("00000429", "Persian"),
+        alternative_keyboards: &[layout("00050429", "Persian (Standard)")],
+        geo_id: 116,
+    },
+    LocaleInfo {
+        tag: "fr-FR",
+        name: "French (France)",
+        lcid: 0x040C,
+        abbreviation: "FRA",
+        keyboard: layout("0000040C", "French"),
+        alternative_keyboards: &[],
+        geo_id: 84,
+    },
+    LocaleInfo {
+        tag: "he-IL",
+        name: "Hebrew (Israel)",
+        lcid: 0x040D,
+        abbreviation: "HEB",
+        keyboard: layout("0000040D", "Hebrew"),
+        alternative_keyboards: &[],
+        geo_id: 117,
+    },
+    LocaleInfo {
+        tag: "it-IT",
+        name: "Italian (Italy)",
+        lcid: 0x0410,
+        abbreviation: "ITA",
+        keyboard: layout("00000410", "Italian"),
+        alternative_keyboards: &[],
+        geo_id: 118,
+    },
+    LocaleInfo {
+        tag: "ku-Arab-IQ",
+        name: "Central Kurdish (Iraq)",
+        lcid: 0x0492,
+        abbreviation: "KUR",
+        keyboard: layout("00000492", "Central Kurdish"),
+        alternative_keyboards: &[],
+        geo_id: 121,
+    },
+    LocaleInfo {
+        tag: "ps-AF",
+        name: "Pashto (Afghanistan)",
+        lcid: 0x0463,
+        abbreviation: "PAS",
+        keyboard: layout("00000463", "Pashto (Afghanistan)"),
+        alternative_keyboards: &[],
+        geo_id: 3,
+    },
+    LocaleInfo {
+        tag: "ru-RU",
+        name: "Russian (Russia)",
+        lcid: 0x0419,
+        abbreviation: "RUS",
+        keyboard: layout("00000419", "Russian"),
+        alternative_keyboards: &[layout("00010419", "Russian (Typewriter)")],
+        geo_id: 203,
+    },
+    LocaleInfo {
+        tag: "tr-TR",
+        name: "Turkish (Turkey)",
+        lcid: 0x041F,
+        abbreviation: "TRK",
+        keyboard: layout("0000041F", "Turkish Q"),
+        alternative_keyboards: &[layout("0001041F", "Turkish F")],
+        geo_id: 235,
+    },
+    LocaleInfo {
+        tag: "ur-PK",
+        name: "Urdu (Pakistan)",
+        lcid: 0x0420,
+        abbreviation: "URD",
+        keyboard: layout("00000420", "Urdu"),
+        alternative_keyboards: &[],
+        geo_id: 190,
+    },
+];
+
+/// Most suggestions listed for an unknown value
+const MAX_SUGGESTIONS: usize = 3;
+
+// +------------------+
+// | public functions |
+// +------------------+
+
+/// Find a locale by tag, tags are case insensitive
+///
+/// # Arguments
+///
+/// * `tag` - BCP-47 tag, e.g. `fa-IR`
+///
+/// # Returns
+///
+/// `Option<&LocaleInfo>` - The locale, if the catalog knows it
+pub fn find_locale(tag: &str) -> Option<&'static LocaleInfo> {
+    LOCALES
+        .iter()
+        .find(|locale| locale.tag.eq_ignore_ascii_case(tag))
+}
+
+/// Find a keyboard layout by its 8 hex digit id
+///
+/// # Arguments
+///
+/// * `klid` - Layout id, e.g. `00050429`
+///
+/// # Returns
+///
+/// `Option<&KeyboardLayout>` - The layout, if the catalog knows it
+pub fn find_keyboard(klid: &str) -> Option<&'static KeyboardLayout> {
+    LOCALES
+        .iter()
+        .flat_map(|locale| locale.keyboards())
+        .find(|keyboard| keyboard.klid.eq_ignore_ascii_case(klid))
+}
+
+/// Check a locale tag against the catalog
+///
+/// # Arguments
+///
+/// * `tag` - BCP-47 tag, e.g. `fa-IR`
+///
+/// # Returns
+///
+/// `Result<&LocaleInfo, String>` - The locale, or why it is not accepted with suggestions
+pub fn check_locale(tag: &str) -> Result<&'static LocaleInfo, String> {
+    find_locale(tag).ok_or_else(|| {
+        format!(
+            "unknown locale `{}`{}",
+            tag,
+            did_you_mean(&suggest_locales(tag))
+        )
+    })
+}
+
+/// Check a home location against the GeoIDs of the catalog
+///
+/// # Arguments
+///
+/// * `geo_id` - Windows GeoID
+/// * `user_locale` - The configured user locale, its region is suggested
+///
+/// # Returns
+///
+/// `Result<(), String>` - Why the GeoID is not accepted, with suggestions
+pub fn check_geo_id(geo_id: u32, user_locale: &str) -> Result<(), String> {
+    if LOCALES.iter().any(|locale| locale.geo_id == geo_id) {
+        return Ok(());
+    }
+    let suggestion: String = match find_locale(user_locale) {
+        Some(locale) => format!(", the region of {} is `{}`", locale.tag, locale.geo_id),
+        None => String::new(),
+    };
+    Err(format!("unknown GeoID `{}`{}", geo_id, suggestion))
+}
+
+/// Turn an input language into the `LCID:KLID` form Windows expects
+///
+/// Besides `LCID:KLID`, a locale tag such as `fa-IR` selects its default layout. Text services
+/// with a `{CLSID}{profile}` id are passed through, the catalog does not list them.
+///
+/// # Arguments
+///
+/// * `value` - The configured input language
+///
+/// # Returns
+///
+/// `Result<String, String>` - The input language id, or why it is not accepted with suggestions
+pub fn resolve_input_language(value: &str) -> Result<String, String> {
+    let Some((lcid, klid)) = value.split_once(':') else {
+        return match find_locale(value) {
+            Some(locale) => Ok(locale.input_language()),
+            None => Err(format!(
+                "expected `LCID:KLID` such as `0429:00000429` or a locale such as `fa-IR`{}",
+                did_you_mean(&suggest_locales(value))
+            )),
+        };
+    };
+
+    if is_hex(klid, 4) && is_hex(lcid, 8) {
+        return Err(format!(
+            "the language id comes first, did you mean `{}:{}`?",
+            klid.to_uppercase(),
+            lcid.to_uppercase()
+        ));
+    }
+    if !is_hex(lcid, 4) {
+        return Err(format!(
+            "language id `{}` must be 4 hex digits such as `0429`",
+            lcid
+        ));
+    }
+    let language: u16 = u16::from_str_radix(lcid, 16).unwrap_or_default();
+    let Some(locale) = LOCALES.iter().find(|locale| locale.lcid == language) else {
+        // The low word of a layout id is the language it was made for
+        let suggestions: Vec<String> = find_keyboard(klid)
+            .and_then(|keyboard| u16::from_str_radix(&keyboard.klid[4..], 16).ok())
+            .and_then(|language| LOCALES.iter().find(|locale| locale.lcid == language))
+            .map(|locale| vec![format!("{:04X}:{}", locale.lcid, klid.to_uppercase())])
+            .unwrap_or_default();
+        return Err(format!(
+            "unknown language id `{}`{}",
+            lcid,
+            did_you_mean(&suggestions)
+        ));
+    };
+
+    if klid.starts_with('{') && klid.ends_with('}') {
+        return Ok(format!("{:04X}:{}", locale.lcid, klid));
+    }
+    if !is_hex(klid, 8) {
+        return Err(format!(
+            "keyboard layout `{}` must be 8 hex digits such as `{}`",
+            klid, locale.keyboard.klid
+        ));
+    }
+    match find_keyboard(klid) {
+        Some(keyboard) => Ok(format!("{:04X}:{}", locale.lcid, keyboard.klid)),
+        None => {
+            let known: Vec<String> = locale
+                .keyboards()
+                .map(|keyboard| format!("`{}` ({})", keyboard.klid, keyboard.name))
+                .collect();
+            Err(format!(
+                "unknown keyboard layout `{}`, layouts of {} are {}",
+                klid,
+                locale.tag,
+                known.join(", ")
+            ))
+        }
+    }
+}
+
+// +-----------------------+
+// |  private functions    |
+// +-----------------------+
+
+/// Tags of the same language first, then locales named like the value, then tags within a few
+/// typos
+fn suggest_locales(tag: &str) -> Vec<String> {
+    let language: String = tag
+        .split(['-', '_'])
+        .next()
+        .unwrap_or_default()
+        .to_lowercase();
+    let mut suggestions: Vec<String> = LOCALES
+        .iter()
+        .filter(|locale| locale.tag.split('-').next() == Some(language.as_str()))
+        .map(|locale| locale.tag.to_string())
+        .collect();
+    if suggestions.is_empty() && tag.len() >= 3 {
+        suggestions = LOCALES
+            .iter()
+            .filter(|locale| locale.name.to_lowercase().contains(&tag.to_lowercase()))
+            .map(|locale| locale.tag.to_string())
+            .collect();
+    }
+    if suggestions.is_empty() {
+        let mut close: Vec<(usize, &str)> = LOCALES
+            .iter()
+            .map(|locale| {
+                let distance: usize =
+                    edit_distance(&tag.to_lowercase(), &locale.tag.to_lowercase());
+                (distance, locale.tag)
+            })
+            .filter(|(distance, _)| *distance <= 2)
+            .collect();
+        close.sort();
+        suggestions = close.into_iter().map(|(_, tag)| tag.to_string()).collect();
+    }
+    suggestions.truncate(MAX_SUGGESTIONS);
+    suggestions
+}
+
+fn did_you_mean(suggestions: &[String]) -> String {
+    if suggestions.is_empty() {
+        return String::new();
+    }
+    let quoted: Vec<String> = suggestions
+        .iter()
+        .map(|suggestion| format!("`{}`", suggestion))
+        .collect();
+    format!(", did you mean {}?", quoted.join(" or "))
+}
+
+fn is_hex(value: &str, length: usize) -> bool {
+    value.len() == length && value.chars().all(|character| character.is_ascii_hexdigit())
+}
+
+/// Levenshtein distance between two short strings
+fn edit_distance(left: &str, right: &str) -> usize {
+    let right: Vec<char> = right.chars().collect();
+    let mut previous: Vec<usize> = (0..=right.len()).collect();
+    for (i, left_char) in left.chars().enumerate() {
+        let mut current: Vec<usize> = vec![i + 1];
+        for (j, right_char) in right.iter().enumerate() {
+            let substitution: usize = previous[j] + usize::from(left_char != *right_char);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[right.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn input_languages_resolve_to_lcid_and_klid() {
+        assert_eq!(
+            resolve_input_language("0429:00050429"),
+            Ok("0429:00050429".to_string())
+        );
+        assert_eq!(
+            resolve_input_language("0429:00000429"),
+            Ok("0429:00000429".to_string())
+        );
+        assert_eq!(
+            resolve_input_language("fa-ir"),
+            Ok("0429:00000429".to_string())
+        );
+    }
+
+    #[test]
+    fn swapped_ids_are_detected() {
+        let error: String = resolve_input_language("00000429:0429").unwrap_err();
+        assert!(error.contains("did you mean `0429:00000429`?"), "{}", error);
+    }
+
+    #[test]
+    fn unknown_locales_get_a_suggestion() {
+        let error: String = resolve_input_language("fa-XX").unwrap_err();
+        assert!(error.contains("did you mean `fa-IR`?"), "{}", error);
+    }
+
+    #[test]
+    fn text_services_are_passed_through() {
+        let text_service: &str =
+            "0429:{03B5835F-F03C-411B-9CE2-AA23E1171E36}{A76C93D9-5523-4E90-AAFA-4DB112F9AC76}";
+        assert_eq!(
+            resolve_input_language(text_service),
+            Ok(text_service.to_string())
+        );
+    }
+}
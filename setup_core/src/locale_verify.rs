@@ -0,0 +1,356 @@
+// +------------------+
+// |    dependencies  |
+// +------------------+
+
+// std
+use std::collections::HashMap;
+use std::fmt;
+
+// internal: constants
+use crate::constants::{
+    REGISTRY_GEO_PATH, REGISTRY_INTERNATIONAL_PATH, REGISTRY_KEYBOARD_PRELOAD_PATH,
+    REGISTRY_KEYBOARD_SUBSTITUTES_PATH, REGISTRY_NLS_LOCALE_PATH,
+};
+
+// internal: locale
+use crate::locale::LocaleSettings;
+
+// internal: locale_catalog
+use crate::locale_catalog::{find_locale, resolve_input_language};
+
+// internal: registry_backend
+use crate::registry_backend::{Hive, RegValue, RegistryBackend, RegistryView};
+
+// +------------------+
+// |      types       |
+// +------------------+
+
+/// A registry value that does not show a requested locale setting
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LocaleMismatch {
+    /// The value that was checked, e.g. `HKEY_CURRENT_USER\Control Panel\International LocaleName`
+    pub value: String,
+    pub expected: String,
+    /// What the registry holds, `None` if the value is missing
+    pub found: Option<String>,
+}
+
+impl fmt::Display for LocaleMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.found {
+            Some(found) => write!(
+                f,
+                "{}: expected {}, found {}",
+                self.value, self.expected, found
+            ),
+            None => write!(f, "{}: expected {}, not set", self.value, self.expected),
+        }
+    }
+}
+
+// +------------------+
+// | public functions |
+// +------------------+
+
+/// Compare the locale settings in the registry with the requested ones
+///
+/// Checks the user locale (`LocaleName`, `sLanguage`), the home location, the preloaded
+/// keyboards and the system locale, the default of `Nls\Locale`. Text services, which are not
+/// preloaded keyboards, are not checked.
+///
+/// # Arguments
+///
+/// * `backend` - The registry to read
+/// * `settings` - The requested locale settings
+///
+/// # Returns
+///
+/// `Vec<LocaleMismatch>` - Every setting the registry does not show, empty if all match
+pub fn verify_locale(
+    backend: &dyn RegistryBackend,
+    settings: &LocaleSettings,
+) -> Vec<LocaleMismatch> {
+    let mut mismatches: Vec<LocaleMismatch> = Vec::new();
+    let mut compare = |hive: Hive, path: &str, name: &str, expected: String| {
+        let found: Option<String> = read_string(backend, hive, path, name);
+        if !found
+            .as_ref()
+            .is_some_and(|found| found.eq_ignore_ascii_case(&expected))
+        {
+            mismatches.push(LocaleMismatch {
+                value: format!(
+                    "{}\\{} {}",
+                    hive.name(),
+                    path,
+                    if name.is_empty() { "(Default)" } else { name }
+                ),
+                expected,
+                found,
+            });
+        }
+    };
+
+    compare(
+        Hive::CurrentUser,
+        REGISTRY_INTERNATIONAL_PATH,
+        "LocaleName",
+        settings.user_locale.clone(),
+    );
+    if let Some(locale) = find_locale(&settings.user_locale) {
+        compare(
+            Hive::CurrentUser,
+            REGISTRY_INTERNATIONAL_PATH,
+            "sLanguage",
+            locale.abbreviation.to_string(),
+        );
+    }
+    if let Some(geo_id) = settings.home_location {
+        compare(
+            Hive::CurrentUser,
+            REGISTRY_GEO_PATH,
+            "Nation",
+            geo_id.to_string(),
+        );
+    }
+    if let Some(locale) = find_locale(&settings.system_locale) {
+        // `Nls\Language Default` is the installed UI language, not the system locale
+        compare(
+            Hive::LocalMachine,
+            REGISTRY_NLS_LOCALE_PATH,
+            "",
+            format!("{:08X}", locale.lcid),
+        );
+    }
+
+    let preloaded: Vec<String> = preloaded_keyboards(backend);
+    for input_language in &settings.input_languages {
+        let Ok(id) = resolve_input_language(input_language) else {
+            continue;
+        };
+        let Some((lcid, klid)) = id.split_once(':') else {
+            continue;
+        };
+        if klid.starts_with('{') {
+            continue;
+        }
+        if !preloaded
+            .iter()
+            .any(|preloaded| preloaded.eq_ignore_ascii_case(&id))
+        {
+            mismatches.push(LocaleMismatch {
+                value: format!(
+                    "{}\\{}",
+                    Hive::CurrentUser.name(),
+                    REGISTRY_KEYBOARD_PRELOAD_PATH
+                ),
+                expected: format!("keyboard {} for language {}", klid, lcid),
+                found: Some(preloaded.join(", ")).filter(|found| !found.is_empty()),
+            });
+        }
+    }
+    mismatches
+}
+
+// +-----------------------+
+// |  private functions    |
+// +-----------------------+
+
+/// Read a string value of the shared view, `None` if it is missing or not a string
+fn read_string(
+    backend: &dyn RegistryBackend,
+    hive: Hive,
+    path: &str,
+    name: &str,
+) -> Option<String> {
+    match backend.get_value(hive, path, RegistryView::Native, name) {
+        Ok(RegValue::String(value)) | Ok(RegValue::ExpandString(value)) => Some(value),
+        _ => None,
+    }
+}
+
+/// The preloaded keyboards as `LCID:KLID`, in the order of `Preload`
+///
+/// `Preload` holds the layout id for a language's own layout, other layouts are listed by a
+/// substitute id whose low word is the language and which `Substitutes` maps to the layout.
+fn preloaded_keyboards(backend: &dyn RegistryBackend) -> Vec<String> {
+    let substitutes: HashMap<String, String> = backend
+        .enum_values(
+            Hive::CurrentUser,
+            REGISTRY_KEYBOARD_SUBSTITUTES_PATH,
+            RegistryView::Native,
+        )
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|(name, value)| match value {
+            RegValue::String(layout) => Some((name.to_uppercase(), layout.to_uppercase())),
+            _ => None,
+        })
+        .collect();
+    let mut preload: Vec<(u32, String)> = backend
+        .enum_values(
+            Hive::CurrentUser,
+            REGISTRY_KEYBOARD_PRELOAD_PATH,
+            RegistryView::Native,
+        )
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|(name, value)| match value {
+            RegValue::String(id)
+                if id.len() == 8 && id.chars().all(|digit| digit.is_ascii_hexdigit()) =>
+            {
+                Some((name.parse().unwrap_or(u32::MAX), id.to_uppercase()))
+            }
+            _ => None,
+        })
+        .collect();
+    preload.sort();
+    preload
+        .into_iter()
+        .map(|(_, id)| {
+            let layout: String = substitutes.get(&id).cloned().unwrap_or_else(|| id.clone());
+            format!("{}:{}", &id[4..], layout)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::registry_backend::MemoryBackend;
+
+    fn set(backend: &MemoryBackend, hive: Hive, path: &str, name: &str, data: &str) {
+        backend
+            .create_key(hive, path, RegistryView::Native)
+            .unwrap();
+        backend
+            .set_value(
+                hive,
+                path,
+                RegistryView::Native,
+                name,
+                &RegValue::String(data.to_string()),
+            )
+            .unwrap();
+    }
+
+    /// A registry showing the Persian user and system locale on an English installation
+    fn persian_registry() -> MemoryBackend {
+        let backend = MemoryBackend::default();
+        let international: &str = REGISTRY_INTERNATIONAL_PATH;
+        set(
+            &backend,
+            Hive::CurrentUser,
+            international,
+            "LocaleName",
+            "fa-IR",
+        );
+        set(
+            &backend,
+            Hive::CurrentUser,
+            international,
+            "sLanguage",
+            "FAR",
+        );
+        set(
+            &backend,
+            Hive::LocalMachine,
+            REGISTRY_NLS_LOCALE_PATH,
+            "",
+            "00000429",
+        );
+        // The installed UI language, not the system locale
+        set(
+            &backend,
+            Hive::LocalMachine,
+            "SYSTEM\\CurrentControlSet\\Control\\Nls\\Language",
+            "Default",
+            "0409",
+        );
+        set(
+            &backend,
+            Hive::CurrentUser,
+            REGISTRY_KEYBOARD_PRELOAD_PATH,
+            "1",
+            "00000429",
+        );
+        backend
+    }
+
+    #[test]
+    fn applied_settings_match() {
+        let backend = persian_registry();
+        // A value that is not a layout id is skipped
+        set(
+            &backend,
+            Hive::CurrentUser,
+            REGISTRY_KEYBOARD_PRELOAD_PATH,
+            "2",
+            "000é0429",
+        );
+
+        assert_eq!(verify_locale(&backend, &LocaleSettings::default()), vec![]);
+    }
+
+    #[test]
+    fn settings_the_registry_does_not_show_are_reported() {
+        let backend = persian_registry();
+        set(
+            &backend,
+            Hive::LocalMachine,
+            REGISTRY_NLS_LOCALE_PATH,
+            "",
+            "00000409",
+        );
+        let settings = LocaleSettings {
+            home_location: Some(116),
+            ..LocaleSettings::default()
+        };
+
+        let mismatches: Vec<LocaleMismatch> = verify_locale(&backend, &settings);
+
+        assert_eq!(
+            mismatches,
+            vec![
+                LocaleMismatch {
+                    value: format!("HKEY_CURRENT_USER\\{} Nation", REGISTRY_GEO_PATH),
+                    expected: "116".to_string(),
+                    found: None,
+                },
+                LocaleMismatch {
+                    value: format!("HKEY_LOCAL_MACHINE\\{} (Default)", REGISTRY_NLS_LOCALE_PATH),
+                    expected: "00000429".to_string(),
+                    found: Some("00000409".to_string()),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn preloaded_substitutes_resolve_to_their_layout() {
+        let backend = persian_registry();
+        set(
+            &backend,
+            Hive::CurrentUser,
+            REGISTRY_KEYBOARD_PRELOAD_PATH,
+            "2",
+            "d0010429",
+        );
+        set(
+            &backend,
+            Hive::CurrentUser,
+            REGISTRY_KEYBOARD_SUBSTITUTES_PATH,
+            "d0010429",
+            "00050429",
+        );
+        let settings = LocaleSettings {
+            input_languages: vec!["0429:00000429".to_string(), "0429:00050429".to_string()],
+            ..LocaleSettings::default()
+        };
+
+        assert_eq!(verify_locale(&backend, &settings), vec![]);
+        assert_eq!(
+            preloaded_keyboards(&backend),
+            vec!["0429:00000429", "0429:00050429"]
+        );
+    }
+}
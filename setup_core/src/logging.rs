@@ -0,0 +1,38 @@
+// +------------------+
+// |    dependencies  |
+// +------------------+
+
+// std
+use std::error::Error;
+use std::path::Path;
+
+// log
+use log::LevelFilter;
+use log4rs::append::file::FileAppender;
+use log4rs::config::{Appender, Config, Root};
+
+// +------------------+
+// | public functions |
+// +------------------+
+
+/// Set up file logger for the application using log and log4rs as the backend
+///
+/// Both binaries log to the same file, so a run can be followed across the reboot.
+///
+/// # Arguments
+///
+/// * `log_path` - Path of the log file, appended to if it exists
+///
+/// # Returns
+///
+/// Result<(), Box<dyn Error>> - Result indicating success or an error
+pub fn setup_logging(log_path: &Path) -> Result<(), Box<dyn Error>> {
+    let log_file = FileAppender::builder().append(true).build(log_path)?;
+
+    let config = Config::builder()
+        .appender(Appender::builder().build("logfile", Box::new(log_file)))
+        .build(Root::builder().appender("logfile").build(LevelFilter::Info))?;
+
+    log4rs::init_config(config)?;
+    Ok(())
+}
@@ -0,0 +1,370 @@
+// +------------------+
+// |    dependencies  |
+// +------------------+
+
+// std
+use std::fmt;
+use std::io;
+
+// internal: backup_manifest
+use crate::backup_manifest::{BackupEntry, BackupKind, ConflictPolicy};
+
+// internal: reg_file
+use crate::reg_file::RegOperation;
+
+// internal: registry_backend
+use crate::registry_backend::{Hive, RegValue, RegistryBackend};
+
+// +------------------+
+// |      types       |
+// +------------------+
+
+/// What the restore did with a single backed up value
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MergeDecision {
+    /// The value was missing and is re-created
+    Restored,
+    /// The value exists with the backed up data already
+    AlreadyPresent,
+    /// The value exists with different data and is left alone
+    KeptCurrent(RegValue),
+    /// The value exists with different data and is overwritten
+    ReplacedCurrent(RegValue),
+    /// The value exists with different data, the backup is restored under another name
+    Renamed(String),
+    /// The value is in the backup file but was not removed by setup_assistant
+    NotInManifest,
+    /// Writing the value failed
+    Failed(String),
+}
+
+/// A merge decision together with the value it was made for
+#[derive(Debug, Clone)]
+pub struct ValueDecision {
+    pub hive: Hive,
+    pub path: String,
+    pub name: String,
+    pub decision: MergeDecision,
+}
+
+impl fmt::Display for ValueDecision {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}\\{} \"{}\": ", self.hive, self.path, self.name)?;
+        match &self.decision {
+            MergeDecision::Restored => write!(f, "restored"),
+            MergeDecision::AlreadyPresent => write!(f, "already present"),
+            MergeDecision::KeptCurrent(current) => {
+                write!(f, "conflict, keeping current data {}", current)
+            }
+            MergeDecision::ReplacedCurrent(current) => {
+                write!(f, "conflict, replaced current data {}", current)
+            }
+            MergeDecision::Renamed(name) => write!(f, "conflict, restored as \"{}\"", name),
+            MergeDecision::NotInManifest => write!(f, "skipped, not removed by setup"),
+            MergeDecision::Failed(err) => write!(f, "failed: {}", err),
+        }
+    }
+}
+
+impl ValueDecision {
+    /// Whether the decision leaves the backup to be retried
+    pub fn is_failure(&self) -> bool {
+        matches!(self.decision, MergeDecision::Failed(_))
+    }
+}
+
+// +------------------+
+// | public functions |
+// +------------------+
+
+/// Merge the values of a registry backup into the current registry
+///
+/// Only values recorded in the manifest entry are re-created. A value that exists again with
+/// different data is a conflict and handled by `policy`; StartupApproved flags were set by
+/// setup_assistant itself, so they are always put back. Keys are created as needed, deletions
+/// in the backup file are ignored.
+///
+/// # Arguments
+///
+/// * `backend` - The registry backend to write to
+/// * `entry` - The manifest entry of the backup
+/// * `operations` - The operations parsed from the backup file
+/// * `policy` - What to do with conflicting values
+///
+/// # Returns
+///
+/// `Vec<ValueDecision>` - The decision made for every value in the backup
+pub fn merge_backup(
+    backend: &dyn RegistryBackend,
+    entry: &BackupEntry,
+    operations: &[RegOperation],
+    policy: ConflictPolicy,
+) -> Vec<ValueDecision> {
+    let policy: ConflictPolicy = match entry.kind {
+        BackupKind::RemovedValues => policy,
+        BackupKind::StartupApprovedFlags => ConflictPolicy::PreferBackup,
+    };
+    let mut decisions: Vec<ValueDecision> = Vec::new();
+    for operation in operations {
+        match operation {
+            RegOperation::CreateKey { hive, path } => {
+                if let Err(err) = backend.create_key(*hive, path, entry.view) {
+                    log::error!("Failed to create key {}\\{}: {}", hive, path, err);
+                }
+            }
+            RegOperation::SetValue {
+                hive,
+                path,
+                name,
+                value,
+            } => {
+                let decision: MergeDecision = if entry
+                    .values
+                    .iter()
+                    .any(|v| v.name.eq_ignore_ascii_case(name))
+                {
+                    merge_value(backend, entry, *hive, path, name, value, policy)
+                } else {
+                    MergeDecision::NotInManifest
+                };
+                decisions.push(ValueDecision {
+                    hive: *hive,
+                    path: path.clone(),
+                    name: name.clone(),
+                    decision,
+                });
+            }
+            RegOperation::DeleteKey { .. } | RegOperation::DeleteValue { .. } => {
+                log::info!("Ignoring \"{}\", backups only re-create values", operation);
+            }
+        }
+    }
+    decisions
+}
+
+// +-----------------------+
+// |  private functions    |
+// +-----------------------+
+
+/// Decide on and apply a single backed up value
+fn merge_value(
+    backend: &dyn RegistryBackend,
+    entry: &BackupEntry,
+    hive: Hive,
+    path: &str,
+    name: &str,
+    value: &RegValue,
+    policy: ConflictPolicy,
+) -> MergeDecision {
+    let current: RegValue = match backend.get_value(hive, path, entry.view, name) {
+        Ok(current) => current,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => {
+            return match backend.set_value(hive, path, entry.view, name, value) {
+                Ok(_) => MergeDecision::Restored,
+                Err(err) => MergeDecision::Failed(err.to_string()),
+            };
+        }
+        Err(err) => return MergeDecision::Failed(err.to_string()),
+    };
+    if current == *value {
+        return MergeDecision::AlreadyPresent;
+    }
+    match policy {
+        ConflictPolicy::KeepCurrent => MergeDecision::KeptCurrent(current),
+        ConflictPolicy::PreferBackup => {
+            match backend.set_value(hive, path, entry.view, name, value) {
+                Ok(_) => MergeDecision::ReplacedCurrent(current),
+                Err(err) => MergeDecision::Failed(err.to_string()),
+            }
+        }
+        ConflictPolicy::Rename => {
+            // "Name (restored)", then "Name (restored 2)" and so on until a free name is found
+            let mut new_name: String = format!("{} (restored)", name);
+            let mut counter: usize = 2;
+            loop {
+                match backend.get_value(hive, path, entry.view, &new_name) {
+                    Ok(existing) if existing == *value => return MergeDecision::Renamed(new_name),
+                    Ok(_) => {
+                        new_name = format!("{} (restored {})", name, counter);
+                        counter += 1;
+                    }
+                    Err(err) if err.kind() == io::ErrorKind::NotFound => break,
+                    Err(err) => return MergeDecision::Failed(err.to_string()),
+                }
+            }
+            match backend.set_value(hive, path, entry.view, &new_name, value) {
+                Ok(_) => MergeDecision::Renamed(new_name),
+                Err(err) => MergeDecision::Failed(err.to_string()),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backup_manifest::ManifestValue;
+    use crate::registry_backend::{MemoryBackend, RegistryView};
+    use crate::test_support::LockedValue;
+
+    const KEY: &str = "SOFTWARE\\Microsoft\\Windows\\CurrentVersion\\Run";
+
+    fn command(data: &str) -> RegValue {
+        RegValue::String(data.to_string())
+    }
+
+    /// A manifest entry for the backup of the `App` value
+    fn app_entry() -> BackupEntry {
+        BackupEntry {
+            run_id: "run".to_string(),
+            created_at: "2024-01-01T00:00:00Z".to_string(),
+            kind: BackupKind::RemovedValues,
+            hive: Hive::CurrentUser,
+            view: RegistryView::Native,
+            key_path: KEY.to_string(),
+            file: "backup.reg".to_string(),
+            sha256: String::new(),
+            values: vec![ManifestValue {
+                name: "App".to_string(),
+                value: command("C:\\backup.exe"),
+            }],
+        }
+    }
+
+    /// The backup file: the `App` value and one setup_assistant did not remove
+    fn operations() -> Vec<RegOperation> {
+        let set = |name: &str| RegOperation::SetValue {
+            hive: Hive::CurrentUser,
+            path: KEY.to_string(),
+            name: name.to_string(),
+            value: command("C:\\backup.exe"),
+        };
+        vec![
+            RegOperation::CreateKey {
+                hive: Hive::CurrentUser,
+                path: KEY.to_string(),
+            },
+            set("App"),
+            set("Other"),
+        ]
+    }
+
+    /// A registry with the given values in the Run key
+    fn run_key(values: &[(&str, &str)]) -> MemoryBackend {
+        let backend = MemoryBackend::default();
+        backend
+            .create_key(Hive::CurrentUser, KEY, RegistryView::Native)
+            .unwrap();
+        for (name, data) in values {
+            backend
+                .set_value(
+                    Hive::CurrentUser,
+                    KEY,
+                    RegistryView::Native,
+                    name,
+                    &command(data),
+                )
+                .unwrap();
+        }
+        backend
+    }
+
+    fn merge(backend: &dyn RegistryBackend, policy: ConflictPolicy) -> Vec<MergeDecision> {
+        merge_backup(backend, &app_entry(), &operations(), policy)
+            .into_iter()
+            .map(|decision| decision.decision)
+            .collect()
+    }
+
+    fn value(backend: &dyn RegistryBackend, name: &str) -> Option<RegValue> {
+        backend
+            .get_value(Hive::CurrentUser, KEY, RegistryView::Native, name)
+            .ok()
+    }
+
+    #[test]
+    fn missing_values_are_restored_and_others_skipped() {
+        let backend = MemoryBackend::default();
+
+        let decisions = merge(&backend, ConflictPolicy::KeepCurrent);
+
+        assert_eq!(
+            decisions,
+            vec![MergeDecision::Restored, MergeDecision::NotInManifest]
+        );
+        assert_eq!(value(&backend, "App"), Some(command("C:\\backup.exe")));
+        assert_eq!(value(&backend, "Other"), None);
+    }
+
+    #[test]
+    fn values_with_the_backed_up_data_are_already_present() {
+        let backend = run_key(&[("App", "C:\\backup.exe")]);
+
+        let decisions = merge(&backend, ConflictPolicy::Rename);
+
+        assert_eq!(decisions[0], MergeDecision::AlreadyPresent);
+        assert_eq!(value(&backend, "App (restored)"), None);
+    }
+
+    #[test]
+    fn keep_current_leaves_a_conflict_alone() {
+        let backend = run_key(&[("App", "C:\\current.exe")]);
+
+        let decisions = merge(&backend, ConflictPolicy::KeepCurrent);
+
+        assert_eq!(
+            decisions[0],
+            MergeDecision::KeptCurrent(command("C:\\current.exe"))
+        );
+        assert_eq!(value(&backend, "App"), Some(command("C:\\current.exe")));
+    }
+
+    #[test]
+    fn prefer_backup_overwrites_a_conflict() {
+        let backend = run_key(&[("App", "C:\\current.exe")]);
+
+        let decisions = merge(&backend, ConflictPolicy::PreferBackup);
+
+        assert_eq!(
+            decisions[0],
+            MergeDecision::ReplacedCurrent(command("C:\\current.exe"))
+        );
+        assert_eq!(value(&backend, "App"), Some(command("C:\\backup.exe")));
+    }
+
+    #[test]
+    fn rename_restores_next_to_a_conflict_under_a_free_name() {
+        let backend = run_key(&[
+            ("App", "C:\\current.exe"),
+            ("App (restored)", "C:\\earlier.exe"),
+        ]);
+
+        let decisions = merge(&backend, ConflictPolicy::Rename);
+
+        assert_eq!(
+            decisions[0],
+            MergeDecision::Renamed("App (restored 2)".to_string())
+        );
+        assert_eq!(value(&backend, "App"), Some(command("C:\\current.exe")));
+        assert_eq!(
+            value(&backend, "App (restored)"),
+            Some(command("C:\\earlier.exe"))
+        );
+        assert_eq!(
+            value(&backend, "App (restored 2)"),
+            Some(command("C:\\backup.exe"))
+        );
+    }
+
+    #[test]
+    fn rename_fails_when_a_name_can_not_be_read() {
+        let backend = LockedValue {
+            inner: run_key(&[("App", "C:\\current.exe")]),
+            name: "App (restored)",
+        };
+
+        let decisions = merge(&backend, ConflictPolicy::Rename);
+
+        assert!(matches!(decisions[0], MergeDecision::Failed(_)));
+    }
+}
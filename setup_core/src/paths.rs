@@ -0,0 +1,126 @@
+// +------------------+
+// |    dependencies  |
+// +------------------+
+
+// std
+use std::env;
+use std::io;
+use std::path::{Path, PathBuf};
+
+// internal: constants
+use crate::constants::*;
+
+// +------------------+
+// |      types       |
+// +------------------+
+
+/// Where the files of the assistant live
+///
+/// setup_assistant sits next to the data folder and registry_restore inside it, so both
+/// resolve the same files from their own location.
+#[derive(Debug, Clone)]
+pub struct AppPaths {
+    /// The data folder holding the config, backups, state and logs
+    pub data: PathBuf,
+}
+
+impl AppPaths {
+    /// Paths inside the given data folder
+    pub fn new(data: &Path) -> AppPaths {
+        AppPaths {
+            data: data.to_path_buf(),
+        }
+    }
+
+    /// Paths for setup_assistant, the data folder is next to the executable
+    pub fn for_setup_assistant() -> io::Result<AppPaths> {
+        Ok(AppPaths::new(&executable_folder()?.join(DATA_FOLDER_NAME)))
+    }
+
+    /// Paths for registry_restore, the executable is inside the data folder
+    pub fn for_registry_restore() -> io::Result<AppPaths> {
+        Ok(AppPaths::new(&executable_folder()?))
+    }
+
+    pub fn config_file(&self) -> PathBuf {
+        self.data.join(CONFIG_FILE_NAME)
+    }
+
+    pub fn machine_config_file(&self) -> PathBuf {
+        self.data.join(MACHINE_CONFIG_FILE_NAME)
+    }
+
+    pub fn log_file(&self) -> PathBuf {
+        self.data.join(LOG_FOLDER_NAME).join(LOG_FILE_NAME)
+    }
+
+    /// The report of a run, named after its id
+    pub fn report_file(&self, run_id: &str) -> PathBuf {
+        self.data
+            .join(REPORT_FOLDER_NAME)
+            .join(format!("{}.json", run_id))
+    }
+
+    pub fn state_file(&self) -> PathBuf {
+        self.data.join(STATE_FILE_NAME)
+    }
+
+    pub fn journal_file(&self) -> PathBuf {
+        self.data.join(JOURNAL_FILE_NAME)
+    }
+
+    pub fn regional_snapshot(&self) -> PathBuf {
+        self.data.join(REGIONAL_SNAPSHOT_FILE_NAME)
+    }
+
+    pub fn backup_manifest(&self) -> PathBuf {
+        self.data.join(BACKUP_MANIFEST_FILE_NAME)
+    }
+
+    pub fn restored_manifest(&self) -> PathBuf {
+        self.data.join(RESTORED_MANIFEST_FILE_NAME)
+    }
+
+    pub fn startup_backup(&self) -> PathBuf {
+        self.data.join(STARTUP_BACKUP_FOLDER_NAME)
+    }
+
+    pub fn registry_restore(&self) -> PathBuf {
+        self.data.join(REGISTRY_RESTORE_EXECUTABLE)
+    }
+
+    pub fn setup_executable(&self) -> PathBuf {
+        self.data.join(SETUP_EXE_NAME)
+    }
+}
+
+// +------------------+
+// | public functions |
+// +------------------+
+
+/// Folder of the running executable
+///
+/// # Returns
+///
+/// `std::io::Result<PathBuf>` - The folder, or an error if the executable path is unknown
+pub fn executable_folder() -> io::Result<PathBuf> {
+    let executable: PathBuf = env::current_exe()?;
+    executable
+        .parent()
+        .map(Path::to_path_buf)
+        .ok_or_else(|| io::Error::other("the executable has no parent folder"))
+}
+
+/// Make the folder of the running executable the current directory
+///
+/// RunOnce and setup_assistant start the binaries from other folders; the relative paths used
+/// for the locale files only work from the executable folder.
+///
+/// # Returns
+///
+/// `std::io::Result<PathBuf>` - The folder that is now the current directory
+pub fn enter_executable_folder() -> io::Result<PathBuf> {
+    let folder: PathBuf = executable_folder()?;
+    env::set_current_dir(&folder)?;
+    Ok(folder)
+}
@@ -0,0 +1,325 @@
+// +------------------+
+// |    dependencies  |
+// +------------------+
+
+// std
+use std::cell::RefCell;
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+// serde
+use serde::Serialize;
+
+// internal: executor
+use crate::executor::Executor;
+
+// internal: registry_backend
+use crate::registry_backend::{Hive, RegValue, RegistryBackend, RegistryView};
+
+// internal: run_state
+use crate::run_state::SetupStep;
+
+// internal: step
+use crate::step::StepPolicy;
+
+// +------------------+
+// |      types       |
+// +------------------+
+
+/// A change the setup flow intends to make
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum PlannedAction {
+    CreateKey {
+        hive: Hive,
+        view: RegistryView,
+        path: String,
+    },
+    SetValue {
+        hive: Hive,
+        view: RegistryView,
+        path: String,
+        name: String,
+        value: RegValue,
+    },
+    DeleteValue {
+        hive: Hive,
+        view: RegistryView,
+        path: String,
+        name: String,
+    },
+    DeleteKey {
+        hive: Hive,
+        view: RegistryView,
+        path: String,
+    },
+    WriteFile {
+        path: PathBuf,
+        bytes: usize,
+    },
+    MoveFile {
+        from: PathBuf,
+        to: PathBuf,
+    },
+    RemoveFile {
+        path: PathBuf,
+    },
+    RemoveFolder {
+        path: PathBuf,
+    },
+    RunCommand {
+        program: String,
+        args: Vec<String>,
+    },
+}
+
+impl fmt::Display for PlannedAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PlannedAction::CreateKey { hive, view, path } => {
+                write!(f, "create key {}\\{} ({} view)", hive, path, view)
+            }
+            PlannedAction::SetValue {
+                hive,
+                view,
+                path,
+                name,
+                value,
+            } => write!(
+                f,
+                "set {}\\{} \"{}\" ({} view) = {}",
+                hive, path, name, view, value
+            ),
+            PlannedAction::DeleteValue {
+                hive,
+                view,
+                path,
+                name,
+            } => write!(f, "delete {}\\{} \"{}\" ({} view)", hive, path, name, view),
+            PlannedAction::DeleteKey { hive, view, path } => {
+                write!(f, "delete key {}\\{} ({} view)", hive, path, view)
+            }
+            PlannedAction::WriteFile { path, bytes } => {
+                write!(f, "write {} ({} bytes)", path.display(), bytes)
+            }
+            PlannedAction::MoveFile { from, to } => {
+                write!(f, "move {} to {}", from.display(), to.display())
+            }
+            PlannedAction::RemoveFile { path } => write!(f, "remove {}", path.display()),
+            PlannedAction::RemoveFolder { path } => {
+                write!(f, "remove folder {}", path.display())
+            }
+            PlannedAction::RunCommand { program, args } => {
+                write!(f, "run {}", program)?;
+                for arg in args {
+                    write!(f, " {}", arg)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// A step the run would carry out
+#[derive(Debug, Clone, Serialize)]
+pub struct PlannedStep {
+    pub step: SetupStep,
+    pub on_failure: StepPolicy,
+    pub description: String,
+}
+
+/// The steps a run would carry out and the actions it would take, in order
+#[derive(Debug, Clone, Serialize)]
+pub struct Plan {
+    pub run_id: String,
+    pub steps: Vec<PlannedStep>,
+    pub actions: Vec<PlannedAction>,
+}
+
+impl fmt::Display for Plan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Plan for run {}:", self.run_id)?;
+        writeln!(f, "Steps ({}):", self.steps.len())?;
+        for planned in &self.steps {
+            writeln!(
+                f,
+                "  {} (on failure: {}): {}",
+                planned.step, planned.on_failure, planned.description
+            )?;
+        }
+        writeln!(f, "Actions ({}):", self.actions.len())?;
+        for (index, action) in self.actions.iter().enumerate() {
+            writeln!(f, "{:>4}. {}", index + 1, action)?;
+        }
+        Ok(())
+    }
+}
+
+/// Executor recording the actions instead of carrying them out
+///
+/// Registry reads go to the live registry so the plan is made from the real machine state;
+/// every write, file operation and command is only recorded. Commands are assumed to succeed.
+pub struct PlanExecutor<'a> {
+    inner: &'a dyn RegistryBackend,
+    actions: RefCell<Vec<PlannedAction>>,
+}
+
+impl<'a> PlanExecutor<'a> {
+    /// Create an executor planning against the registry in `inner`
+    pub fn new(inner: &'a dyn RegistryBackend) -> PlanExecutor<'a> {
+        PlanExecutor {
+            inner,
+            actions: RefCell::new(Vec::new()),
+        }
+    }
+
+    /// The plan made of `steps` and the recorded actions
+    pub fn into_plan(self, run_id: &str, steps: Vec<PlannedStep>) -> Plan {
+        Plan {
+            run_id: run_id.to_string(),
+            steps,
+            actions: self.actions.into_inner(),
+        }
+    }
+
+    fn record(&self, action: PlannedAction) {
+        log::info!("planned: {}", action);
+        self.actions.borrow_mut().push(action);
+    }
+}
+
+impl Executor for PlanExecutor<'_> {
+    fn registry(&self) -> &dyn RegistryBackend {
+        self
+    }
+
+    fn write_file(&self, path: &Path, data: &[u8]) -> io::Result<()> {
+        self.record(PlannedAction::WriteFile {
+            path: path.to_path_buf(),
+            bytes: data.len(),
+        });
+        Ok(())
+    }
+
+    fn move_file(&self, from: &Path, to: &Path) -> io::Result<()> {
+        self.record(PlannedAction::MoveFile {
+            from: from.to_path_buf(),
+            to: to.to_path_buf(),
+        });
+        Ok(())
+    }
+
+    fn remove_file(&self, path: &Path) -> io::Result<()> {
+        self.record(PlannedAction::RemoveFile {
+            path: path.to_path_buf(),
+        });
+        Ok(())
+    }
+
+    fn remove_folder(&self, path: &Path) -> io::Result<()> {
+        self.record(PlannedAction::RemoveFolder {
+            path: path.to_path_buf(),
+        });
+        Ok(())
+    }
+
+    fn run_command(&self, program: &str, args: &[String]) -> io::Result<i32> {
+        self.record(PlannedAction::RunCommand {
+            program: program.to_string(),
+            args: args.to_vec(),
+        });
+        Ok(0)
+    }
+
+    fn run_raw_command(&self, program: &str, command_line: &str) -> io::Result<i32> {
+        self.record(PlannedAction::RunCommand {
+            program: program.to_string(),
+            args: vec![command_line.to_string()],
+        });
+        Ok(0)
+    }
+}
+
+impl RegistryBackend for PlanExecutor<'_> {
+    fn open_key(&self, hive: Hive, path: &str, view: RegistryView) -> io::Result<()> {
+        self.inner.open_key(hive, path, view)
+    }
+
+    fn create_key(&self, hive: Hive, path: &str, view: RegistryView) -> io::Result<()> {
+        if !self.inner.key_exists(hive, path, view) {
+            self.record(PlannedAction::CreateKey {
+                hive,
+                view,
+                path: path.to_string(),
+            });
+        }
+        Ok(())
+    }
+
+    fn enum_keys(&self, hive: Hive, path: &str, view: RegistryView) -> io::Result<Vec<String>> {
+        self.inner.enum_keys(hive, path, view)
+    }
+
+    fn enum_values(
+        &self,
+        hive: Hive,
+        path: &str,
+        view: RegistryView,
+    ) -> io::Result<Vec<(String, RegValue)>> {
+        self.inner.enum_values(hive, path, view)
+    }
+
+    fn get_value(
+        &self,
+        hive: Hive,
+        path: &str,
+        view: RegistryView,
+        name: &str,
+    ) -> io::Result<RegValue> {
+        self.inner.get_value(hive, path, view, name)
+    }
+
+    fn set_value(
+        &self,
+        hive: Hive,
+        path: &str,
+        view: RegistryView,
+        name: &str,
+        value: &RegValue,
+    ) -> io::Result<()> {
+        self.record(PlannedAction::SetValue {
+            hive,
+            view,
+            path: path.to_string(),
+            name: name.to_string(),
+            value: value.clone(),
+        });
+        Ok(())
+    }
+
+    fn delete_value(
+        &self,
+        hive: Hive,
+        path: &str,
+        view: RegistryView,
+        name: &str,
+    ) -> io::Result<()> {
+        self.record(PlannedAction::DeleteValue {
+            hive,
+            view,
+            path: path.to_string(),
+            name: name.to_string(),
+        });
+        Ok(())
+    }
+
+    fn delete_key(&self, hive: Hive, path: &str, view: RegistryView) -> io::Result<()> {
+        self.record(PlannedAction::DeleteKey {
+            hive,
+            view,
+            path: path.to_string(),
+        });
+        Ok(())
+    }
+}
@@ -0,0 +1,639 @@
+// +------------------+
+// |    dependencies  |
+// +------------------+
+
+// std
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+// internal: registry_backend
+use crate::registry_backend::{
+    Hive, RegValue, RegistryBackend, RegistryView, REG_BINARY, REG_EXPAND_SZ, REG_MULTI_SZ,
+};
+
+// +------------------+
+// |    constants     |
+// +------------------+
+
+/// First line of a REGEDIT5 (UTF-16LE) registry file
+pub const REGEDIT5_HEADER: &str = "Windows Registry Editor Version 5.00";
+
+/// First line of a REGEDIT4 (ANSI) registry file
+pub const REGEDIT4_HEADER: &str = "REGEDIT4";
+
+/// Byte order mark written at the start of REGEDIT5 files
+const UTF16LE_BOM: [u8; 2] = [0xff, 0xfe];
+
+/// Column at which regedit wraps hex data onto a continuation line
+const MAX_HEX_CHARS: usize = 77;
+
+// +------------------+
+// |      types       |
+// +------------------+
+
+/// A registry key together with its values as written to a .reg file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RegFileKey {
+    /// Full key path including the hive name, e.g. `HKEY_CURRENT_USER\Software\...`
+    pub path: String,
+    /// Value names and data in registry order; the default value has an empty name
+    pub values: Vec<(String, RegValue)>,
+}
+
+/// A single change described by a .reg file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RegOperation {
+    /// `[key]` - create the key if missing
+    CreateKey { hive: Hive, path: String },
+    /// `[-key]` - delete the key and its sub keys
+    DeleteKey { hive: Hive, path: String },
+    /// `"name"=data` - write a value
+    SetValue {
+        hive: Hive,
+        path: String,
+        name: String,
+        value: RegValue,
+    },
+    /// `"name"=-` - delete a value
+    DeleteValue {
+        hive: Hive,
+        path: String,
+        name: String,
+    },
+}
+
+impl fmt::Display for RegOperation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RegOperation::CreateKey { hive, path } => write!(f, "create key {}\\{}", hive, path),
+            RegOperation::DeleteKey { hive, path } => write!(f, "delete key {}\\{}", hive, path),
+            RegOperation::SetValue {
+                hive,
+                path,
+                name,
+                value,
+            } => write!(
+                f,
+                "set value {}\\{} \"{}\" = {} (type {})",
+                hive,
+                path,
+                name,
+                value,
+                value.kind()
+            ),
+            RegOperation::DeleteValue { hive, path, name } => {
+                write!(f, "delete value {}\\{} \"{}\"", hive, path, name)
+            }
+        }
+    }
+}
+
+/// Format version of a parsed .reg file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RegFileVersion {
+    Regedit4,
+    Regedit5,
+}
+
+// +------------------+
+// | public functions |
+// +------------------+
+
+/// Read a key and all of its sub keys from the registry
+///
+/// # Arguments
+///
+/// * `backend` - The registry backend to read from
+/// * `hive` - The hive holding the key
+/// * `path` - Path of the key inside the hive
+/// * `view` - The registry view to read the key in
+/// * `file_path` - The key path to be written to the file, including the hive name
+///
+/// # Returns
+///
+/// `std::io::Result<Vec<RegFileKey>>` - The key followed by its sub keys, depth first
+pub fn read_key_tree(
+    backend: &dyn RegistryBackend,
+    hive: Hive,
+    path: &str,
+    view: RegistryView,
+    file_path: &str,
+) -> io::Result<Vec<RegFileKey>> {
+    let mut keys: Vec<RegFileKey> = vec![RegFileKey {
+        path: file_path.to_string(),
+        values: backend.enum_values(hive, path, view)?,
+    }];
+    for sub_key in backend.enum_keys(hive, path, view)? {
+        keys.extend(read_key_tree(
+            backend,
+            hive,
+            &format!("{}\\{}", path, sub_key),
+            view,
+            &format!("{}\\{}", file_path, sub_key),
+        )?);
+    }
+    Ok(keys)
+}
+
+/// Render keys in the REGEDIT5 text format, the way regedit exports them
+///
+/// # Arguments
+///
+/// * `keys` - The keys to be rendered
+///
+/// # Returns
+///
+/// `String` - The file content with `\r\n` line endings
+pub fn render_reg_file(keys: &[RegFileKey]) -> String {
+    let mut content: String = format!("{}\r\n", REGEDIT5_HEADER);
+    for key in keys {
+        content.push_str(&format!("\r\n[{}]\r\n", key.path));
+        for (name, value) in &key.values {
+            content.push_str(&render_value(name, value));
+            content.push_str("\r\n");
+        }
+    }
+    content.push_str("\r\n");
+    content
+}
+
+/// Encode rendered file content as UTF-16LE with a byte order mark
+///
+/// # Arguments
+///
+/// * `content` - The rendered file content
+///
+/// # Returns
+///
+/// `Vec<u8>` - The bytes to be written to disk
+pub fn encode_regedit5(content: &str) -> Vec<u8> {
+    let mut bytes: Vec<u8> = UTF16LE_BOM.to_vec();
+    bytes.extend(content.encode_utf16().flat_map(|unit| unit.to_le_bytes()));
+    bytes
+}
+
+/// Write keys to a REGEDIT5 registry file
+///
+/// # Arguments
+///
+/// * `file_path` - Path of the file to be written
+/// * `keys` - The keys to be written
+///
+/// # Returns
+///
+/// `std::io::Result<()>` - Whether the operation was successful or not
+pub fn write_reg_file(file_path: &Path, keys: &[RegFileKey]) -> io::Result<()> {
+    fs::write(file_path, encode_regedit5(&render_reg_file(keys)))
+}
+
+/// Parse the content of a REGEDIT4 or REGEDIT5 file
+///
+/// UTF-16LE files are recognised by their byte order mark, everything else is read as
+/// UTF-8 with a Latin-1 fallback. Comments, blank lines and `\` line continuations of
+/// hex data are handled.
+///
+/// # Arguments
+///
+/// * `bytes` - The raw file content
+///
+/// # Returns
+///
+/// `std::io::Result<Vec<RegOperation>>` - The operations in file order, or an
+/// `InvalidData` error naming the offending line
+pub fn parse_reg_file(bytes: &[u8]) -> io::Result<Vec<RegOperation>> {
+    let content: String = decode_reg_file(bytes);
+    let mut lines = content.lines().enumerate();
+    let version: RegFileVersion = loop {
+        match lines.next() {
+            Some((_, line)) if line.trim().is_empty() => continue,
+            Some((_, line)) if line.trim() == REGEDIT5_HEADER => break RegFileVersion::Regedit5,
+            Some((_, line)) if line.trim() == REGEDIT4_HEADER => break RegFileVersion::Regedit4,
+            Some((index, _)) => return Err(parse_error(index + 1, "unknown file header")),
+            None => return Err(parse_error(0, "empty file")),
+        }
+    };
+
+    let mut operations: Vec<RegOperation> = Vec::new();
+    let mut current_key: Option<(Hive, String)> = None;
+    while let Some((index, line)) = lines.next() {
+        let line_number: usize = index + 1;
+        let mut line: String = line.trim().to_string();
+        if line.is_empty() || line.starts_with(';') {
+            continue;
+        }
+
+        if line.starts_with('[') {
+            if !line.ends_with(']') {
+                return Err(parse_error(line_number, "unterminated key name"));
+            }
+            let key: &str = &line[1..line.len() - 1];
+            let (deleted, key) = match key.strip_prefix('-') {
+                Some(key) => (true, key),
+                None => (false, key),
+            };
+            let (hive, path) = split_key_path(key)
+                .ok_or_else(|| parse_error(line_number, &format!("unsupported key {}", key)))?;
+            if deleted {
+                operations.push(RegOperation::DeleteKey { hive, path });
+                current_key = None;
+            } else {
+                operations.push(RegOperation::CreateKey {
+                    hive,
+                    path: path.clone(),
+                });
+                current_key = Some((hive, path));
+            }
+            continue;
+        }
+
+        let (hive, path) = current_key
+            .clone()
+            .ok_or_else(|| parse_error(line_number, "value outside of a key"))?;
+        let (name, data_start) =
+            parse_value_name(&line).map_err(|message| parse_error(line_number, &message))?;
+        if line[data_start..].trim_start().starts_with("hex") {
+            while line.ends_with('\\') {
+                line.pop();
+                match lines.next() {
+                    Some((_, next)) => line.push_str(next.trim()),
+                    None => return Err(parse_error(line_number, "unexpected end of file")),
+                }
+            }
+        }
+        let data: &str = line[data_start..].trim();
+        if data == "-" {
+            operations.push(RegOperation::DeleteValue { hive, path, name });
+            continue;
+        }
+        let value: RegValue = parse_value_data(data, version)
+            .map_err(|message| parse_error(line_number, &message))?;
+        operations.push(RegOperation::SetValue {
+            hive,
+            path,
+            name,
+            value,
+        });
+    }
+    Ok(operations)
+}
+
+/// Apply a parsed operation to the registry
+///
+/// # Arguments
+///
+/// * `backend` - The registry backend to write to
+/// * `operation` - The operation to be applied
+/// * `view` - The registry view to apply the operation in
+///
+/// # Returns
+///
+/// `std::io::Result<()>` - Whether the operation was successful or not
+pub fn apply_operation(
+    backend: &dyn RegistryBackend,
+    operation: &RegOperation,
+    view: RegistryView,
+) -> io::Result<()> {
+    match operation {
+        RegOperation::CreateKey { hive, path } => backend.create_key(*hive, path, view),
+        RegOperation::DeleteKey { hive, path } => match backend.delete_key(*hive, path, view) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err),
+            _ => Ok(()),
+        },
+        RegOperation::SetValue {
+            hive,
+            path,
+            name,
+            value,
+        } => backend.set_value(*hive, path, view, name, value),
+        RegOperation::DeleteValue { hive, path, name } => {
+            match backend.delete_value(*hive, path, view, name) {
+                Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err),
+                _ => Ok(()),
+            }
+        }
+    }
+}
+
+// +-----------------------+
+// |  private functions    |
+// +-----------------------+
+
+/// Build the error returned for malformed files
+fn parse_error(line_number: usize, message: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("line {}: {}", line_number, message),
+    )
+}
+
+/// Decode UTF-16LE files with a byte order mark, otherwise UTF-8 falling back to Latin-1
+fn decode_reg_file(bytes: &[u8]) -> String {
+    if let Some(bytes) = bytes.strip_prefix(&UTF16LE_BOM) {
+        let units: Vec<u16> = bytes
+            .chunks_exact(2)
+            .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+            .collect();
+        return String::from_utf16_lossy(&units);
+    }
+    let bytes: &[u8] = bytes.strip_prefix(&[0xef, 0xbb, 0xbf]).unwrap_or(bytes);
+    match std::str::from_utf8(bytes) {
+        Ok(content) => content.to_string(),
+        Err(_) => bytes.iter().map(|&byte| byte as char).collect(),
+    }
+}
+
+/// Split `HKEY_...\path` into the hive and the path inside it
+fn split_key_path(key: &str) -> Option<(Hive, String)> {
+    let (hive, path) = match key.split_once('\\') {
+        Some((hive, path)) => (hive, path),
+        None => (key, ""),
+    };
+    Some((Hive::from_name(hive)?, path.trim_matches('\\').to_string()))
+}
+
+/// Read a quoted string starting at `start`, returning it and the index after the closing quote
+fn parse_quoted(line: &str, start: usize) -> Result<(String, usize), String> {
+    let mut value: String = String::new();
+    let mut chars = line[start..].char_indices();
+    if !matches!(chars.next(), Some((_, '"'))) {
+        return Err(String::from("expected a quoted string"));
+    }
+    while let Some((offset, character)) = chars.next() {
+        match character {
+            '"' => return Ok((value, start + offset + 1)),
+            '\\' => match chars.next() {
+                Some((_, escaped)) => value.push(escaped),
+                None => break,
+            },
+            _ => value.push(character),
+        }
+    }
+    Err(String::from("unterminated string"))
+}
+
+/// Parse `@=` or `"name"=`, returning the name and the index where the data starts
+fn parse_value_name(line: &str) -> Result<(String, usize), String> {
+    let (name, end) = if line.starts_with('@') {
+        (String::new(), 1)
+    } else {
+        parse_quoted(line, 0)?
+    };
+    let rest: &str = &line[end..];
+    let trimmed: &str = rest.trim_start();
+    match trimmed.strip_prefix('=') {
+        Some(_) => Ok((name, end + (rest.len() - trimmed.len()) + 1)),
+        None => Err(String::from("expected '=' after the value name")),
+    }
+}
+
+/// Parse the data part of a value line
+fn parse_value_data(data: &str, version: RegFileVersion) -> Result<RegValue, String> {
+    if data.starts_with('"') {
+        let (value, end) = parse_quoted(data, 0)?;
+        if !data[end..].trim().is_empty() {
+            return Err(String::from("unexpected data after string"));
+        }
+        return Ok(RegValue::String(value));
+    }
+    if let Some(digits) = data.strip_prefix("dword:") {
+        if digits.is_empty() || digits.len() > 8 {
+            return Err(format!("invalid dword {}", digits));
+        }
+        return u32::from_str_radix(digits, 16)
+            .map(RegValue::Dword)
+            .map_err(|_| format!("invalid dword {}", digits));
+    }
+    let (kind, bytes) = if let Some(bytes) = data.strip_prefix("hex:") {
+        (REG_BINARY, bytes)
+    } else if let Some(rest) = data.strip_prefix("hex(") {
+        let (kind, bytes) = rest
+            .split_once("):")
+            .ok_or_else(|| String::from("invalid hex type"))?;
+        let kind: u32 =
+            u32::from_str_radix(kind, 16).map_err(|_| format!("invalid hex type {}", kind))?;
+        (kind, bytes)
+    } else {
+        return Err(format!("unsupported value data {}", data));
+    };
+
+    let mut parsed: Vec<u8> = Vec::new();
+    for byte in bytes.split(',').map(str::trim) {
+        if byte.is_empty() {
+            continue;
+        }
+        parsed
+            .push(u8::from_str_radix(byte, 16).map_err(|_| format!("invalid hex byte {}", byte))?);
+    }
+
+    // REGEDIT4 stores expandable and multi strings as ANSI bytes
+    if version == RegFileVersion::Regedit4 && (kind == REG_EXPAND_SZ || kind == REG_MULTI_SZ) {
+        let text: String = parsed.iter().map(|&byte| byte as char).collect();
+        let mut values: Vec<String> = text.split('\0').map(String::from).collect();
+        while values.last().is_some_and(|value| value.is_empty()) {
+            values.pop();
+        }
+        return Ok(if kind == REG_EXPAND_SZ {
+            RegValue::ExpandString(values.into_iter().next().unwrap_or_default())
+        } else {
+            RegValue::MultiString(values)
+        });
+    }
+    Ok(RegValue::from_bytes(kind, &parsed))
+}
+
+/// Escape backslashes and quotes the way regedit does
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// Render a single `"name"=data` line, wrapping hex data like regedit
+fn render_value(name: &str, value: &RegValue) -> String {
+    let mut line: String = if name.is_empty() {
+        String::from("@=")
+    } else {
+        format!("\"{}\"=", escape(name))
+    };
+    match value {
+        RegValue::String(data) => line.push_str(&format!("\"{}\"", escape(data))),
+        RegValue::Dword(data) => line.push_str(&format!("dword:{:08x}", data)),
+        _ => {
+            let kind: u32 = value.kind();
+            if kind == REG_BINARY {
+                line.push_str("hex:");
+            } else {
+                line.push_str(&format!("hex({:x}):", kind));
+            }
+            push_hex_data(&mut line, &value.to_bytes());
+        }
+    }
+    line
+}
+
+/// Append comma separated hex bytes, breaking lines with `\` once they reach regedit's limit
+fn push_hex_data(line: &mut String, data: &[u8]) {
+    let mut line_len: usize = line.encode_utf16().count();
+    for (index, byte) in data.iter().enumerate() {
+        line.push_str(&format!("{:02x}", byte));
+        if index + 1 == data.len() {
+            break;
+        }
+        line.push(',');
+        line_len += 3;
+        if line_len >= MAX_HEX_CHARS {
+            line.push_str("\\\r\n  ");
+            line_len = 2;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: &str = "HKEY_CURRENT_USER\\Software\\Moein";
+
+    fn parse(content: &str) -> io::Result<Vec<RegOperation>> {
+        parse_reg_file(content.as_bytes())
+    }
+
+    #[test]
+    fn written_files_parse_back_to_the_same_values() {
+        let values: Vec<(String, RegValue)> = vec![
+            (String::new(), RegValue::String("default".to_string())),
+            (
+                "Path \"quoted\"".to_string(),
+                RegValue::String("C:\\Moein\\Moein.exe".to_string()),
+            ),
+            ("Count".to_string(), RegValue::Dword(42)),
+            ("Big".to_string(), RegValue::Qword(0x0102_0304_0506_0708)),
+            (
+                "Expand".to_string(),
+                RegValue::ExpandString("%ProgramFiles%\\Moein".to_string()),
+            ),
+            (
+                "Multi".to_string(),
+                RegValue::MultiString(vec!["first".to_string(), "second".to_string()]),
+            ),
+            ("Blob".to_string(), RegValue::Binary((0..=255).collect())),
+        ];
+        let keys: Vec<RegFileKey> = vec![
+            RegFileKey {
+                path: KEY.to_string(),
+                values: values.clone(),
+            },
+            RegFileKey {
+                path: format!("{}\\Empty", KEY),
+                values: Vec::new(),
+            },
+        ];
+
+        let rendered: String = render_reg_file(&keys);
+        assert!(rendered.contains("\\\r\n  "), "long hex data is wrapped");
+
+        let mut expected: Vec<RegOperation> = vec![RegOperation::CreateKey {
+            hive: Hive::CurrentUser,
+            path: "Software\\Moein".to_string(),
+        }];
+        expected.extend(
+            values
+                .into_iter()
+                .map(|(name, value)| RegOperation::SetValue {
+                    hive: Hive::CurrentUser,
+                    path: "Software\\Moein".to_string(),
+                    name,
+                    value,
+                }),
+        );
+        expected.push(RegOperation::CreateKey {
+            hive: Hive::CurrentUser,
+            path: "Software\\Moein\\Empty".to_string(),
+        });
+        assert_eq!(
+            parse_reg_file(&encode_regedit5(&rendered)).unwrap(),
+            expected
+        );
+    }
+
+    #[test]
+    fn hex_line_continuations_are_joined() {
+        let operations = parse(
+            "REGEDIT4\r\n\r\n[HKEY_LOCAL_MACHINE\\SOFTWARE\\Moein]\r\n\
+             \"Blob\"=hex:01,02,\\\r\n  03,04,\\\r\n  05\r\n\
+             \"Multi\"=hex(7):61,00,\\\r\n  62,00,00\r\n",
+        )
+        .unwrap();
+        assert_eq!(
+            operations[1..],
+            [
+                RegOperation::SetValue {
+                    hive: Hive::LocalMachine,
+                    path: "SOFTWARE\\Moein".to_string(),
+                    name: "Blob".to_string(),
+                    value: RegValue::Binary(vec![1, 2, 3, 4, 5]),
+                },
+                RegOperation::SetValue {
+                    hive: Hive::LocalMachine,
+                    path: "SOFTWARE\\Moein".to_string(),
+                    name: "Multi".to_string(),
+                    value: RegValue::MultiString(vec!["a".to_string(), "b".to_string()]),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn deleted_keys_and_values_are_parsed() {
+        let operations = parse(&format!(
+            "{}\r\n\r\n; comment\r\n[-{}\\Old]\r\n\r\n[{}]\r\n\"Gone\"=-\r\n@=-\r\n",
+            REGEDIT5_HEADER, KEY, KEY
+        ))
+        .unwrap();
+        assert_eq!(
+            operations,
+            vec![
+                RegOperation::DeleteKey {
+                    hive: Hive::CurrentUser,
+                    path: "Software\\Moein\\Old".to_string(),
+                },
+                RegOperation::CreateKey {
+                    hive: Hive::CurrentUser,
+                    path: "Software\\Moein".to_string(),
+                },
+                RegOperation::DeleteValue {
+                    hive: Hive::CurrentUser,
+                    path: "Software\\Moein".to_string(),
+                    name: "Gone".to_string(),
+                },
+                RegOperation::DeleteValue {
+                    hive: Hive::CurrentUser,
+                    path: "Software\\Moein".to_string(),
+                    name: String::new(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn malformed_files_are_rejected() {
+        let header: String = format!("{}\r\n\r\n", REGEDIT5_HEADER);
+        let cases: Vec<String> = vec![
+            String::new(),
+            String::from("not a registry file\r\n"),
+            format!("{}[{}\r\n", header, KEY),
+            format!("{}[HKEY_UNKNOWN\\Moein]\r\n", header),
+            format!("{}\"Orphan\"=\"value\"\r\n", header),
+            format!("{}[{}]\r\n\"Name\"\"value\"\r\n", header, KEY),
+            format!("{}[{}]\r\n\"Name=\"value\"\r\n", header, KEY),
+            format!("{}[{}]\r\n\"Count\"=dword:123456789\r\n", header, KEY),
+            format!("{}[{}]\r\n\"Blob\"=hex:01,zz\r\n", header, KEY),
+            format!("{}[{}]\r\n\"Blob\"=hex:01,\\\r\n", header, KEY),
+            format!("{}[{}]\r\n\"Other\"=text\r\n", header, KEY),
+        ];
+        for content in cases {
+            let error = parse(&content).unwrap_err();
+            assert_eq!(error.kind(), io::ErrorKind::InvalidData, "{:?}", content);
+        }
+    }
+}
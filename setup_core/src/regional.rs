@@ -0,0 +1,679 @@
+// +------------------+
+// |    dependencies  |
+// +------------------+
+
+// std
+use std::fs;
+use std::io;
+use std::path::Path;
+
+// serde
+use serde::{Deserialize, Serialize};
+
+// internal: constants
+use crate::constants::{
+    REGISTRY_GEO_PATH, REGISTRY_INTERNATIONAL_PATH, REGISTRY_TIME_ZONE_PATH, TIME_ZONE_PROGRAM,
+};
+
+// internal: executor
+use crate::executor::Executor;
+
+// internal: registry_backend
+use crate::registry_backend::{Hive, RegValue, RegistryBackend, RegistryView};
+
+// internal: utilities
+use crate::utilities::timestamp_utc;
+
+// +------------------+
+// |      types       |
+// +------------------+
+
+/// Calendar used for dates, stored as `iCalendarType`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CalendarType {
+    Gregorian,
+    Hijri,
+    /// Solar Hijri
+    Persian,
+    UmAlQura,
+}
+
+impl CalendarType {
+    /// The Windows calendar id, e.g. `22` for Persian
+    pub fn id(&self) -> u32 {
+        match self {
+            CalendarType::Gregorian => 1,
+            CalendarType::Hijri => 6,
+            CalendarType::Persian => 22,
+            CalendarType::UmAlQura => 23,
+        }
+    }
+}
+
+/// First day of the week, stored as `iFirstDayOfWeek`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Weekday {
+    Monday,
+    Tuesday,
+    Wednesday,
+    Thursday,
+    Friday,
+    Saturday,
+    Sunday,
+}
+
+impl Weekday {
+    /// The Windows day number, Monday is `0`
+    pub fn id(&self) -> u32 {
+        *self as u32
+    }
+}
+
+/// How digits are shown, stored as `iDigitSubstitution`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DigitSubstitution {
+    /// Native digits next to native text, Latin digits otherwise
+    Context,
+    /// Always Latin digits
+    None,
+    /// Always native digits
+    Native,
+}
+
+impl DigitSubstitution {
+    /// The Windows substitution mode
+    pub fn id(&self) -> u32 {
+        match self {
+            DigitSubstitution::Context => 0,
+            DigitSubstitution::None => 1,
+            DigitSubstitution::Native => 2,
+        }
+    }
+}
+
+/// Regional settings applied next to the locale, settings left unset are not changed
+///
+/// The defaults are what an Iranian installation needs: Iran Standard Time, Iran as the home
+/// location and the Persian calendar with Persian date formats, weeks starting on Saturday.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RegionalSettings {
+    /// Windows time zone id as listed by `tzutil /l`, e.g. `Iran Standard Time`
+    pub time_zone: Option<String>,
+    /// Home location as a Windows GeoID, e.g. `116` for Iran
+    pub home_location: Option<u32>,
+    pub calendar: Option<CalendarType>,
+    /// Short date format, e.g. `yyyy/MM/dd`
+    pub short_date: Option<String>,
+    /// Long date format, e.g. `dddd, d MMMM yyyy`
+    pub long_date: Option<String>,
+    pub first_day_of_week: Option<Weekday>,
+    pub digit_substitution: Option<DigitSubstitution>,
+}
+
+impl Default for RegionalSettings {
+    fn default() -> Self {
+        Self {
+            time_zone: Some("Iran Standard Time".to_string()),
+            home_location: Some(116),
+            calendar: Some(CalendarType::Persian),
+            short_date: Some("yyyy/MM/dd".to_string()),
+            long_date: Some("dddd, d MMMM yyyy".to_string()),
+            first_day_of_week: Some(Weekday::Saturday),
+            digit_substitution: Some(DigitSubstitution::Context),
+        }
+    }
+}
+
+impl RegionalSettings {
+    /// The current user values the settings are written to, as `(key path, name, data)`
+    ///
+    /// The time zone is not a user value, it is set with `tzutil`.
+    pub fn registry_values(&self) -> Vec<(&'static str, &'static str, String)> {
+        let international: [(&str, Option<String>); 5] = [
+            (
+                "iCalendarType",
+                self.calendar.map(|calendar| calendar.id().to_string()),
+            ),
+            ("sShortDate", self.short_date.clone()),
+            ("sLongDate", self.long_date.clone()),
+            (
+                "iFirstDayOfWeek",
+                self.first_day_of_week.map(|day| day.id().to_string()),
+            ),
+            (
+                "iDigitSubstitution",
+                self.digit_substitution.map(|mode| mode.id().to_string()),
+            ),
+        ];
+        let mut values: Vec<(&str, &str, String)> = Vec::new();
+        if let Some(geo_id) = self.home_location {
+            values.push((REGISTRY_GEO_PATH, "Nation", geo_id.to_string()));
+        }
+        for (name, data) in international {
+            if let Some(data) = data {
+                values.push((REGISTRY_INTERNATIONAL_PATH, name, data));
+            }
+        }
+        values
+    }
+}
+
+/// A current user value as it was before the regional settings were applied
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SnapshotValue {
+    pub path: String,
+    pub name: String,
+    /// `None` if the value did not exist
+    pub previous: Option<RegValue>,
+}
+
+/// What the regional settings step replaced, written before anything is changed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RegionalSnapshot {
+    /// Id of the run that took the snapshot
+    pub run_id: String,
+    /// UTC time the snapshot was taken at, RFC 3339
+    pub taken_at: String,
+    /// The previous time zone, `None` if the time zone was not changed
+    pub time_zone: Option<String>,
+    pub values: Vec<SnapshotValue>,
+}
+
+impl RegionalSnapshot {
+    /// Read a snapshot from disk
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path of the snapshot file
+    ///
+    /// # Returns
+    ///
+    /// `std::io::Result<Option<RegionalSnapshot>>` - The snapshot, or `None` if there is no file
+    pub fn load(path: &Path) -> io::Result<Option<RegionalSnapshot>> {
+        let content: String = match fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err),
+        };
+        serde_json::from_str(&content)
+            .map(Some)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+
+    /// Record the values the settings are about to replace
+    ///
+    /// # Arguments
+    ///
+    /// * `backend` - The registry to read
+    /// * `settings` - The regional settings about to be applied
+    /// * `run_id` - Id of the current run
+    ///
+    /// # Returns
+    ///
+    /// `std::io::Result<RegionalSnapshot>` - The current values; only a missing value counts as
+    /// not set, any other read error fails the snapshot
+    pub fn take(
+        backend: &dyn RegistryBackend,
+        settings: &RegionalSettings,
+        run_id: &str,
+    ) -> io::Result<RegionalSnapshot> {
+        let time_zone: Option<String> = match &settings.time_zone {
+            Some(_) => match existing_value(
+                backend,
+                Hive::LocalMachine,
+                REGISTRY_TIME_ZONE_PATH,
+                "TimeZoneKeyName",
+            )? {
+                Some(RegValue::String(time_zone)) => Some(time_zone),
+                Some(other) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("unexpected time zone value {}", other),
+                    ))
+                }
+                None => None,
+            },
+            None => None,
+        };
+        let mut values: Vec<SnapshotValue> = Vec::new();
+        for (path, name, _) in settings.registry_values() {
+            values.push(SnapshotValue {
+                path: path.to_string(),
+                name: name.to_string(),
+                previous: existing_value(backend, Hive::CurrentUser, path, name)?,
+            });
+        }
+        Ok(RegionalSnapshot {
+            run_id: run_id.to_string(),
+            taken_at: timestamp_utc(),
+            time_zone,
+            values,
+        })
+    }
+
+    /// Keep the previous values an earlier snapshot recorded, they predate this one
+    ///
+    /// # Arguments
+    ///
+    /// * `earlier` - The snapshot of an earlier run that was not reverted
+    pub fn take_over(&mut self, earlier: RegionalSnapshot) {
+        self.taken_at = earlier.taken_at;
+        if earlier.time_zone.is_some() {
+            self.time_zone = earlier.time_zone;
+        }
+        for value in earlier.values {
+            match self
+                .values
+                .iter_mut()
+                .find(|current| current.path == value.path && current.name == value.name)
+            {
+                Some(current) => current.previous = value.previous,
+                None => self.values.push(value),
+            }
+        }
+    }
+
+    /// Serialize the snapshot as pretty-printed JSON
+    pub fn to_json(&self) -> io::Result<String> {
+        serde_json::to_string_pretty(self).map_err(io::Error::other)
+    }
+}
+
+// +------------------+
+// | public functions |
+// +------------------+
+
+/// Snapshot the current regional settings and apply the configured ones
+///
+/// A snapshot already taken by the same run is kept, so a retried step does not record its own
+/// changes as the previous values. A snapshot left by an earlier run that was not reverted is
+/// taken over: the values it recorded stay the previous ones, so a revert still goes back to the
+/// settings from before any run. The first value that fails to apply stops the step, the
+/// snapshot is written by then so a rollback puts back what was changed.
+///
+/// # Arguments
+///
+/// * `executor` - The executor carrying out the changes
+/// * `settings` - The regional settings to apply
+/// * `run_id` - Id of the current run
+/// * `snapshot_path` - Where the snapshot is written
+///
+/// # Returns
+///
+/// `std::io::Result<()>` - An error if the snapshot could not be taken, nothing is changed then,
+/// or if a setting could not be applied
+pub fn apply_regional_settings(
+    executor: &dyn Executor,
+    settings: &RegionalSettings,
+    run_id: &str,
+    snapshot_path: &Path,
+) -> io::Result<()> {
+    let backend: &dyn RegistryBackend = executor.registry();
+    // Taking a new snapshot over an unreadable one would lose the values it records
+    let existing: Option<RegionalSnapshot> =
+        RegionalSnapshot::load(snapshot_path).map_err(|err| {
+            io::Error::other(format!(
+                "failed to read regional settings snapshot {}: {}",
+                snapshot_path.display(),
+                err
+            ))
+        })?;
+    if existing
+        .as_ref()
+        .is_none_or(|snapshot| snapshot.run_id != run_id)
+    {
+        let mut snapshot: RegionalSnapshot = RegionalSnapshot::take(backend, settings, run_id)
+            .map_err(|err| {
+                io::Error::other(format!(
+                    "failed to take regional settings snapshot: {}",
+                    err
+                ))
+            })?;
+        if let Some(earlier) = existing {
+            log::info!(
+                "taking over the regional settings snapshot of run {}",
+                earlier.run_id
+            );
+            snapshot.take_over(earlier);
+        }
+        executor
+            .write_file(snapshot_path, snapshot.to_json()?.as_bytes())
+            .map_err(|err| {
+                io::Error::other(format!(
+                    "failed to write regional settings snapshot: {}",
+                    err
+                ))
+            })?;
+        log::info!(
+            "regional settings snapshot written to {}",
+            snapshot_path.display()
+        );
+    }
+
+    for (path, name, data) in settings.registry_values() {
+        let written: io::Result<()> = backend
+            .create_key(Hive::CurrentUser, path, RegistryView::Native)
+            .and_then(|_| {
+                backend.set_value(
+                    Hive::CurrentUser,
+                    path,
+                    RegistryView::Native,
+                    name,
+                    &RegValue::String(data.clone()),
+                )
+            });
+        match written {
+            Ok(_) => log::info!("{} set to {}", name, data),
+            Err(err) => return Err(io::Error::other(format!("failed to set {}: {}", name, err))),
+        }
+    }
+
+    if let Some(time_zone) = &settings.time_zone {
+        set_time_zone(executor, time_zone)?;
+    }
+    Ok(())
+}
+
+/// Put back the regional settings recorded in the snapshot and remove it
+///
+/// # Arguments
+///
+/// * `executor` - The executor carrying out the changes
+/// * `snapshot_path` - Where the snapshot was written
+/// * `run_id` - Only revert the snapshot of this run, `None` for any run
+///
+/// # Returns
+///
+/// `std::io::Result<bool>` - Whether a snapshot was reverted
+pub fn revert_regional_settings(
+    executor: &dyn Executor,
+    snapshot_path: &Path,
+    run_id: Option<&str>,
+) -> io::Result<bool> {
+    let Some(snapshot) = RegionalSnapshot::load(snapshot_path)? else {
+        return Ok(false);
+    };
+    if run_id.is_some_and(|run_id| run_id != snapshot.run_id) {
+        return Ok(false);
+    }
+
+    let backend: &dyn RegistryBackend = executor.registry();
+    for value in &snapshot.values {
+        let reverted: io::Result<()> = match &value.previous {
+            Some(previous) => backend
+                .create_key(Hive::CurrentUser, &value.path, RegistryView::Native)
+                .and_then(|_| {
+                    backend.set_value(
+                        Hive::CurrentUser,
+                        &value.path,
+                        RegistryView::Native,
+                        &value.name,
+                        previous,
+                    )
+                }),
+            None => match backend.delete_value(
+                Hive::CurrentUser,
+                &value.path,
+                RegistryView::Native,
+                &value.name,
+            ) {
+                Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(()),
+                result => result,
+            },
+        };
+        match reverted {
+            Ok(_) => log::info!("{} reverted", value.name),
+            Err(err) => log::error!("failed to revert {}: {}", value.name, err),
+        }
+    }
+    if let Some(time_zone) = &snapshot.time_zone {
+        if let Err(err) = set_time_zone(executor, time_zone) {
+            log::error!("failed to revert the time zone: {}", err);
+        }
+    }
+
+    executor.remove_file(snapshot_path)?;
+    log::info!("regional settings of run {} reverted", snapshot.run_id);
+    Ok(true)
+}
+
+// +-----------------------+
+// |  private functions    |
+// +-----------------------+
+
+/// Switch the time zone with `tzutil`
+fn set_time_zone(executor: &dyn Executor, time_zone: &str) -> io::Result<()> {
+    let args: Vec<String> = vec!["/s".to_string(), time_zone.to_string()];
+    match executor.run_command(TIME_ZONE_PROGRAM, &args) {
+        Ok(0) => {
+            log::info!("time zone set to {}", time_zone);
+            Ok(())
+        }
+        Ok(code) => Err(io::Error::other(format!(
+            "{} exited with code {}",
+            TIME_ZONE_PROGRAM, code
+        ))),
+        Err(err) => Err(io::Error::other(format!(
+            "failed to run {}: {}",
+            TIME_ZONE_PROGRAM, err
+        ))),
+    }
+}
+
+/// Read a value, `None` if it or its key does not exist
+fn existing_value(
+    backend: &dyn RegistryBackend,
+    hive: Hive,
+    path: &str,
+    name: &str,
+) -> io::Result<Option<RegValue>> {
+    match backend.get_value(hive, path, RegistryView::Native, name) {
+        Ok(value) => Ok(Some(value)),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(err),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::executor::SystemExecutor;
+    use crate::plan::{PlanExecutor, PlannedAction};
+    use crate::registry_backend::MemoryBackend;
+    use crate::test_support::TempFolder;
+
+    /// A registry every read of which is refused
+    struct DeniedBackend;
+
+    fn denied<T>() -> io::Result<T> {
+        Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            "access denied",
+        ))
+    }
+
+    impl RegistryBackend for DeniedBackend {
+        fn open_key(&self, _: Hive, _: &str, _: RegistryView) -> io::Result<()> {
+            denied()
+        }
+
+        fn create_key(&self, _: Hive, _: &str, _: RegistryView) -> io::Result<()> {
+            denied()
+        }
+
+        fn enum_keys(&self, _: Hive, _: &str, _: RegistryView) -> io::Result<Vec<String>> {
+            denied()
+        }
+
+        fn enum_values(
+            &self,
+            _: Hive,
+            _: &str,
+            _: RegistryView,
+        ) -> io::Result<Vec<(String, RegValue)>> {
+            denied()
+        }
+
+        fn get_value(&self, _: Hive, _: &str, _: RegistryView, _: &str) -> io::Result<RegValue> {
+            denied()
+        }
+
+        fn set_value(
+            &self,
+            _: Hive,
+            _: &str,
+            _: RegistryView,
+            _: &str,
+            _: &RegValue,
+        ) -> io::Result<()> {
+            denied()
+        }
+
+        fn delete_value(&self, _: Hive, _: &str, _: RegistryView, _: &str) -> io::Result<()> {
+            denied()
+        }
+
+        fn delete_key(&self, _: Hive, _: &str, _: RegistryView) -> io::Result<()> {
+            denied()
+        }
+    }
+
+    #[test]
+    fn snapshot_records_missing_values_as_not_set() {
+        let backend = MemoryBackend::default();
+        backend
+            .create_key(
+                Hive::CurrentUser,
+                REGISTRY_INTERNATIONAL_PATH,
+                RegistryView::Native,
+            )
+            .unwrap();
+        backend
+            .set_value(
+                Hive::CurrentUser,
+                REGISTRY_INTERNATIONAL_PATH,
+                RegistryView::Native,
+                "sShortDate",
+                &RegValue::String("M/d/yyyy".to_string()),
+            )
+            .unwrap();
+
+        let snapshot =
+            RegionalSnapshot::take(&backend, &RegionalSettings::default(), "run").unwrap();
+
+        assert_eq!(snapshot.run_id, "run");
+        assert_eq!(snapshot.time_zone, None);
+        for value in &snapshot.values {
+            let expected: Option<RegValue> =
+                (value.name == "sShortDate").then(|| RegValue::String("M/d/yyyy".to_string()));
+            assert_eq!(value.previous, expected, "{}", value.name);
+        }
+    }
+
+    #[test]
+    fn snapshot_fails_on_other_read_errors() {
+        let error = RegionalSnapshot::take(&DeniedBackend, &RegionalSettings::default(), "run")
+            .unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::PermissionDenied);
+    }
+
+    #[test]
+    fn nothing_is_changed_without_a_snapshot() {
+        let executor = PlanExecutor::new(&DeniedBackend);
+        let snapshot_path = Path::new("regional_snapshot.json");
+
+        let result = apply_regional_settings(
+            &executor,
+            &RegionalSettings::default(),
+            "run",
+            snapshot_path,
+        );
+
+        assert!(result.is_err());
+        assert!(executor.into_plan("run", Vec::new()).actions.is_empty());
+    }
+
+    #[test]
+    fn settings_are_written_after_the_snapshot() {
+        let backend = MemoryBackend::default();
+        let executor = PlanExecutor::new(&backend);
+        let snapshot_path = Path::new("regional_snapshot.json");
+
+        apply_regional_settings(
+            &executor,
+            &RegionalSettings::default(),
+            "run",
+            snapshot_path,
+        )
+        .unwrap();
+
+        let actions: Vec<PlannedAction> = executor.into_plan("run", Vec::new()).actions;
+        assert!(matches!(
+            actions.first(),
+            Some(PlannedAction::WriteFile { path, .. }) if path == snapshot_path
+        ));
+        assert!(actions.iter().any(|action| matches!(
+            action,
+            PlannedAction::SetValue { name, .. } if name == "iCalendarType"
+        )));
+        assert!(matches!(
+            actions.last(),
+            Some(PlannedAction::RunCommand { program, .. }) if program == TIME_ZONE_PROGRAM
+        ));
+    }
+
+    #[test]
+    fn snapshot_of_an_earlier_run_is_taken_over() {
+        let temp = TempFolder::new("regional_take_over");
+        let snapshot_path = temp.0.join("regional_snapshot.json");
+        let backend = MemoryBackend::default();
+        backend
+            .create_key(
+                Hive::CurrentUser,
+                REGISTRY_INTERNATIONAL_PATH,
+                RegistryView::Native,
+            )
+            .unwrap();
+        backend
+            .set_value(
+                Hive::CurrentUser,
+                REGISTRY_INTERNATIONAL_PATH,
+                RegistryView::Native,
+                "sShortDate",
+                &RegValue::String("M/d/yyyy".to_string()),
+            )
+            .unwrap();
+        let executor = SystemExecutor::new(&backend);
+        // tzutil is not run, the time zone is left alone
+        let earlier = RegionalSettings {
+            time_zone: None,
+            short_date: Some("yyyy/MM/dd".to_string()),
+            ..RegionalSettings::default()
+        };
+        apply_regional_settings(&executor, &earlier, "earlier", &snapshot_path).unwrap();
+
+        let settings = RegionalSettings {
+            time_zone: None,
+            short_date: Some("yy/M/d".to_string()),
+            ..RegionalSettings::default()
+        };
+        apply_regional_settings(&executor, &settings, "run", &snapshot_path).unwrap();
+
+        let snapshot = RegionalSnapshot::load(&snapshot_path).unwrap().unwrap();
+        assert_eq!(snapshot.run_id, "run");
+        let short_date = snapshot
+            .values
+            .iter()
+            .find(|value| value.name == "sShortDate")
+            .unwrap();
+        // The value from before the earlier run, not the one it wrote
+        assert_eq!(
+            short_date.previous,
+            Some(RegValue::String("M/d/yyyy".to_string()))
+        );
+    }
+}
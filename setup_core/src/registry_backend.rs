@@ -0,0 +1,833 @@
+// +------------------+
+// |    dependencies  |
+// +------------------+
+
+// std
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io;
+
+// serde
+use serde::{Deserialize, Serialize};
+
+// winreg
+#[cfg(windows)]
+use winreg::enums::{
+    RegType, HKEY_CURRENT_USER, HKEY_LOCAL_MACHINE, KEY_ALL_ACCESS, KEY_READ, KEY_WOW64_32KEY,
+    KEY_WOW64_64KEY,
+};
+#[cfg(windows)]
+use winreg::RegKey;
+
+// +------------------+
+// |    value types   |
+// +------------------+
+
+pub const REG_NONE: u32 = 0;
+pub const REG_SZ: u32 = 1;
+pub const REG_EXPAND_SZ: u32 = 2;
+pub const REG_BINARY: u32 = 3;
+pub const REG_DWORD: u32 = 4;
+pub const REG_MULTI_SZ: u32 = 7;
+pub const REG_QWORD: u32 = 11;
+
+/// Registry root keys the application works with
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum Hive {
+    #[serde(rename = "HKLM", alias = "HKEY_LOCAL_MACHINE")]
+    LocalMachine,
+    #[serde(rename = "HKCU", alias = "HKEY_CURRENT_USER")]
+    CurrentUser,
+}
+
+impl Hive {
+    /// Full name of the hive as used by regedit, e.g. `HKEY_LOCAL_MACHINE`
+    pub fn name(&self) -> &'static str {
+        match self {
+            Hive::LocalMachine => "HKEY_LOCAL_MACHINE",
+            Hive::CurrentUser => "HKEY_CURRENT_USER",
+        }
+    }
+
+    /// Parse a hive from its full or abbreviated name (case-insensitive)
+    pub fn from_name(name: &str) -> Option<Hive> {
+        match name.to_uppercase().as_str() {
+            "HKEY_LOCAL_MACHINE" | "HKLM" => Some(Hive::LocalMachine),
+            "HKEY_CURRENT_USER" | "HKCU" => Some(Hive::CurrentUser),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Hive {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Registry view a key is opened in
+///
+/// On 64-bit Windows the 32-bit view of `HKLM\SOFTWARE` is redirected to
+/// `HKLM\SOFTWARE\Wow6432Node`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RegistryView {
+    Native,
+    X64,
+    X32,
+}
+
+impl fmt::Display for RegistryView {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            RegistryView::Native => "native",
+            RegistryView::X64 => "64-bit",
+            RegistryView::X32 => "32-bit",
+        })
+    }
+}
+
+/// The path a key in a view is stored at, as shown by regedit
+///
+/// On 64-bit Windows the 32-bit view of `HKEY_LOCAL_MACHINE\SOFTWARE` is stored under
+/// `SOFTWARE\Wow6432Node`; every other path is returned unchanged.
+///
+/// # Arguments
+///
+/// * `hive` - The hive holding the key
+/// * `path` - Path of the key inside the hive
+/// * `view` - The registry view the key is opened in
+///
+/// # Returns
+///
+/// `String` - The physical path of the key inside the hive
+pub fn physical_path(hive: Hive, path: &str, view: RegistryView) -> String {
+    let path: &str = path.trim_matches('\\');
+    let lower: String = fold_case(path);
+    if hive == Hive::LocalMachine
+        && view == RegistryView::X32
+        && (lower == "software" || lower.starts_with("software\\"))
+        && !lower.starts_with("software\\wow6432node")
+    {
+        let (software, rest) = path.split_at("software".len());
+        return format!("{}\\Wow6432Node{}", software, rest);
+    }
+    path.to_string()
+}
+
+/// A typed registry value
+///
+/// Serialized as `{"type": "REG_SZ", "data": ...}`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", content = "data")]
+pub enum RegValue {
+    /// `REG_SZ`
+    #[serde(rename = "REG_SZ")]
+    String(String),
+    /// `REG_EXPAND_SZ`
+    #[serde(rename = "REG_EXPAND_SZ")]
+    ExpandString(String),
+    /// `REG_MULTI_SZ`
+    #[serde(rename = "REG_MULTI_SZ")]
+    MultiString(Vec<String>),
+    /// `REG_DWORD`
+    #[serde(rename = "REG_DWORD")]
+    Dword(u32),
+    /// `REG_QWORD`
+    #[serde(rename = "REG_QWORD")]
+    Qword(u64),
+    /// `REG_BINARY`
+    #[serde(rename = "REG_BINARY")]
+    Binary(Vec<u8>),
+    /// Any other value type, kept as raw bytes
+    #[serde(rename = "raw")]
+    Raw { kind: u32, data: Vec<u8> },
+}
+
+impl RegValue {
+    /// The numeric registry type of the value, e.g. `REG_SZ`
+    pub fn kind(&self) -> u32 {
+        match self {
+            RegValue::String(_) => REG_SZ,
+            RegValue::ExpandString(_) => REG_EXPAND_SZ,
+            RegValue::MultiString(_) => REG_MULTI_SZ,
+            RegValue::Dword(_) => REG_DWORD,
+            RegValue::Qword(_) => REG_QWORD,
+            RegValue::Binary(_) => REG_BINARY,
+            RegValue::Raw { kind, .. } => *kind,
+        }
+    }
+
+    /// Encode the value the way it is stored in the registry
+    ///
+    /// Strings are UTF-16LE with a terminating null, numbers are little endian.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        match self {
+            RegValue::String(value) | RegValue::ExpandString(value) => encode_utf16z(value),
+            RegValue::MultiString(values) => {
+                let mut bytes: Vec<u8> = Vec::new();
+                for value in values {
+                    bytes.extend(encode_utf16z(value));
+                }
+                bytes.extend([0, 0]);
+                bytes
+            }
+            RegValue::Dword(value) => value.to_le_bytes().to_vec(),
+            RegValue::Qword(value) => value.to_le_bytes().to_vec(),
+            RegValue::Binary(data) | RegValue::Raw { data, .. } => data.clone(),
+        }
+    }
+
+    /// Decode a value from its registry type and stored bytes
+    ///
+    /// Data that does not fit its declared type is kept as `RegValue::Raw`.
+    pub fn from_bytes(kind: u32, bytes: &[u8]) -> RegValue {
+        match kind {
+            REG_SZ => RegValue::String(decode_utf16z(bytes)),
+            REG_EXPAND_SZ => RegValue::ExpandString(decode_utf16z(bytes)),
+            REG_MULTI_SZ => {
+                let text: String = decode_utf16(bytes);
+                let mut values: Vec<String> = text.split('\0').map(String::from).collect();
+                while values.last().is_some_and(|value| value.is_empty()) {
+                    values.pop();
+                }
+                RegValue::MultiString(values)
+            }
+            REG_DWORD if bytes.len() == 4 => {
+                RegValue::Dword(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+            }
+            REG_QWORD if bytes.len() == 8 => {
+                let mut buffer = [0u8; 8];
+                buffer.copy_from_slice(bytes);
+                RegValue::Qword(u64::from_le_bytes(buffer))
+            }
+            REG_BINARY => RegValue::Binary(bytes.to_vec()),
+            _ => RegValue::Raw {
+                kind,
+                data: bytes.to_vec(),
+            },
+        }
+    }
+}
+
+impl fmt::Display for RegValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RegValue::String(value) | RegValue::ExpandString(value) => write!(f, "{}", value),
+            RegValue::MultiString(values) => write!(f, "{}", values.join("|")),
+            RegValue::Dword(value) => write!(f, "0x{:08x}", value),
+            RegValue::Qword(value) => write!(f, "0x{:016x}", value),
+            RegValue::Binary(data) | RegValue::Raw { data, .. } => {
+                let hex: Vec<String> = data.iter().map(|byte| format!("{:02x}", byte)).collect();
+                write!(f, "{}", hex.join(","))
+            }
+        }
+    }
+}
+
+// +------------------+
+// |  backend trait   |
+// +------------------+
+
+/// Access to the Windows registry
+///
+/// All paths are relative to the given hive and every call names the view it
+/// operates in. Missing keys and values are reported as `io::ErrorKind::NotFound`.
+pub trait RegistryBackend {
+    /// Open an existing key
+    fn open_key(&self, hive: Hive, path: &str, view: RegistryView) -> io::Result<()>;
+
+    /// Create a key and all of its missing parents, succeeding if it already exists
+    fn create_key(&self, hive: Hive, path: &str, view: RegistryView) -> io::Result<()>;
+
+    /// Names of the direct sub keys of a key
+    fn enum_keys(&self, hive: Hive, path: &str, view: RegistryView) -> io::Result<Vec<String>>;
+
+    /// Names and data of all values of a key
+    fn enum_values(
+        &self,
+        hive: Hive,
+        path: &str,
+        view: RegistryView,
+    ) -> io::Result<Vec<(String, RegValue)>>;
+
+    /// Read a single value; the default value has an empty name
+    fn get_value(
+        &self,
+        hive: Hive,
+        path: &str,
+        view: RegistryView,
+        name: &str,
+    ) -> io::Result<RegValue>;
+
+    /// Write a single value of an existing key
+    fn set_value(
+        &self,
+        hive: Hive,
+        path: &str,
+        view: RegistryView,
+        name: &str,
+        value: &RegValue,
+    ) -> io::Result<()>;
+
+    /// Delete a single value
+    fn delete_value(
+        &self,
+        hive: Hive,
+        path: &str,
+        view: RegistryView,
+        name: &str,
+    ) -> io::Result<()>;
+
+    /// Delete a key together with all of its sub keys and values
+    fn delete_key(&self, hive: Hive, path: &str, view: RegistryView) -> io::Result<()>;
+
+    /// Whether a key exists
+    fn key_exists(&self, hive: Hive, path: &str, view: RegistryView) -> bool {
+        self.open_key(hive, path, view).is_ok()
+    }
+}
+
+/// The backend used when running on the target machine
+#[cfg(windows)]
+pub type SystemBackend = WinRegBackend;
+
+/// The backend used when running on the target machine
+#[cfg(not(windows))]
+pub type SystemBackend = MemoryBackend;
+
+// +------------------+
+// |  winreg backend  |
+// +------------------+
+
+/// Registry backend talking to the live registry through `winreg`
+#[cfg(windows)]
+#[derive(Debug, Default)]
+pub struct WinRegBackend;
+
+#[cfg(windows)]
+impl WinRegBackend {
+    fn root(hive: Hive) -> RegKey {
+        RegKey::predef(match hive {
+            Hive::LocalMachine => HKEY_LOCAL_MACHINE,
+            Hive::CurrentUser => HKEY_CURRENT_USER,
+        })
+    }
+
+    fn view_flags(view: RegistryView) -> u32 {
+        match view {
+            RegistryView::Native => 0,
+            RegistryView::X64 => KEY_WOW64_64KEY,
+            RegistryView::X32 => KEY_WOW64_32KEY,
+        }
+    }
+
+    fn open(&self, hive: Hive, path: &str, view: RegistryView, access: u32) -> io::Result<RegKey> {
+        Self::root(hive).open_subkey_with_flags(path, access | Self::view_flags(view))
+    }
+
+    fn reg_type(kind: u32) -> RegType {
+        match kind {
+            1 => RegType::REG_SZ,
+            2 => RegType::REG_EXPAND_SZ,
+            3 => RegType::REG_BINARY,
+            4 => RegType::REG_DWORD,
+            5 => RegType::REG_DWORD_BIG_ENDIAN,
+            6 => RegType::REG_LINK,
+            7 => RegType::REG_MULTI_SZ,
+            8 => RegType::REG_RESOURCE_LIST,
+            9 => RegType::REG_FULL_RESOURCE_DESCRIPTOR,
+            10 => RegType::REG_RESOURCE_REQUIREMENTS_LIST,
+            11 => RegType::REG_QWORD,
+            _ => RegType::REG_NONE,
+        }
+    }
+}
+
+#[cfg(windows)]
+impl RegistryBackend for WinRegBackend {
+    fn open_key(&self, hive: Hive, path: &str, view: RegistryView) -> io::Result<()> {
+        self.open(hive, path, view, KEY_READ).map(|_| ())
+    }
+
+    fn create_key(&self, hive: Hive, path: &str, view: RegistryView) -> io::Result<()> {
+        Self::root(hive)
+            .create_subkey_with_flags(path, KEY_ALL_ACCESS | Self::view_flags(view))
+            .map(|_| ())
+    }
+
+    fn enum_keys(&self, hive: Hive, path: &str, view: RegistryView) -> io::Result<Vec<String>> {
+        self.open(hive, path, view, KEY_READ)?.enum_keys().collect()
+    }
+
+    fn enum_values(
+        &self,
+        hive: Hive,
+        path: &str,
+        view: RegistryView,
+    ) -> io::Result<Vec<(String, RegValue)>> {
+        let reg_key: RegKey = self.open(hive, path, view, KEY_READ)?;
+        let mut values: Vec<(String, RegValue)> = Vec::new();
+        for entry in reg_key.enum_values() {
+            let (name, raw) = entry?;
+            values.push((name, RegValue::from_bytes(raw.vtype as u32, &raw.bytes)));
+        }
+        Ok(values)
+    }
+
+    fn get_value(
+        &self,
+        hive: Hive,
+        path: &str,
+        view: RegistryView,
+        name: &str,
+    ) -> io::Result<RegValue> {
+        let raw = self.open(hive, path, view, KEY_READ)?.get_raw_value(name)?;
+        Ok(RegValue::from_bytes(raw.vtype as u32, &raw.bytes))
+    }
+
+    fn set_value(
+        &self,
+        hive: Hive,
+        path: &str,
+        view: RegistryView,
+        name: &str,
+        value: &RegValue,
+    ) -> io::Result<()> {
+        let raw = winreg::RegValue {
+            bytes: value.to_bytes(),
+            vtype: Self::reg_type(value.kind()),
+        };
+        self.open(hive, path, view, KEY_ALL_ACCESS)?
+            .set_raw_value(name, &raw)
+    }
+
+    fn delete_value(
+        &self,
+        hive: Hive,
+        path: &str,
+        view: RegistryView,
+        name: &str,
+    ) -> io::Result<()> {
+        self.open(hive, path, view, KEY_ALL_ACCESS)?
+            .delete_value(name)
+    }
+
+    fn delete_key(&self, hive: Hive, path: &str, view: RegistryView) -> io::Result<()> {
+        let (parent, leaf) = match path.trim_matches('\\').rsplit_once('\\') {
+            Some((parent, leaf)) => (parent, leaf),
+            None => ("", path.trim_matches('\\')),
+        };
+        self.open(hive, parent, view, KEY_ALL_ACCESS)?
+            .delete_subkey_all(leaf)
+    }
+}
+
+// +------------------+
+// | memory backend   |
+// +------------------+
+
+#[derive(Debug, Clone)]
+struct MemoryKey {
+    path: String,
+    values: Vec<(String, RegValue)>,
+}
+
+/// Registry backend keeping everything in memory
+///
+/// Key and value names are case-insensitive like in the real registry, and the
+/// 32-bit view of `HKEY_LOCAL_MACHINE\SOFTWARE` is redirected to `Wow6432Node`.
+#[derive(Debug, Default)]
+pub struct MemoryBackend {
+    keys: RefCell<BTreeMap<(Hive, String), MemoryKey>>,
+}
+
+impl MemoryBackend {
+    fn not_found(hive: Hive, path: &str) -> io::Error {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("registry key {}\\{} not found", hive, path),
+        )
+    }
+}
+
+impl RegistryBackend for MemoryBackend {
+    fn open_key(&self, hive: Hive, path: &str, view: RegistryView) -> io::Result<()> {
+        let path: String = physical_path(hive, path, view);
+        if path.is_empty() || self.keys.borrow().contains_key(&(hive, fold_case(&path))) {
+            Ok(())
+        } else {
+            Err(Self::not_found(hive, &path))
+        }
+    }
+
+    fn create_key(&self, hive: Hive, path: &str, view: RegistryView) -> io::Result<()> {
+        let path: String = physical_path(hive, path, view);
+        let mut keys = self.keys.borrow_mut();
+        let mut current: String = String::new();
+        for part in path.split('\\').filter(|part| !part.is_empty()) {
+            if !current.is_empty() {
+                current.push('\\');
+            }
+            current.push_str(part);
+            keys.entry((hive, fold_case(&current)))
+                .or_insert_with(|| MemoryKey {
+                    path: current.clone(),
+                    values: Vec::new(),
+                });
+        }
+        Ok(())
+    }
+
+    fn enum_keys(&self, hive: Hive, path: &str, view: RegistryView) -> io::Result<Vec<String>> {
+        self.open_key(hive, path, view)?;
+        let path: String = fold_case(&physical_path(hive, path, view));
+        let prefix: String = if path.is_empty() {
+            String::new()
+        } else {
+            format!("{}\\", path)
+        };
+        // The folded path may differ in length from the stored one, the name is taken from the
+        // last part of the stored path instead of slicing it at the prefix length
+        Ok(self
+            .keys
+            .borrow()
+            .iter()
+            .filter(|((key_hive, key_path), _)| {
+                *key_hive == hive
+                    && key_path
+                        .strip_prefix(&prefix)
+                        .is_some_and(|rest| !rest.is_empty() && !rest.contains('\\'))
+            })
+            .filter_map(|(_, key)| key.path.rsplit('\\').next().map(str::to_string))
+            .collect())
+    }
+
+    fn enum_values(
+        &self,
+        hive: Hive,
+        path: &str,
+        view: RegistryView,
+    ) -> io::Result<Vec<(String, RegValue)>> {
+        let path: String = physical_path(hive, path, view);
+        match self.keys.borrow().get(&(hive, fold_case(&path))) {
+            Some(key) => Ok(key.values.clone()),
+            None => Err(Self::not_found(hive, &path)),
+        }
+    }
+
+    fn get_value(
+        &self,
+        hive: Hive,
+        path: &str,
+        view: RegistryView,
+        name: &str,
+    ) -> io::Result<RegValue> {
+        self.enum_values(hive, path, view)?
+            .into_iter()
+            .find(|(value_name, _)| fold_case(value_name) == fold_case(name))
+            .map(|(_, value)| value)
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("registry value {} not found in {}\\{}", name, hive, path),
+                )
+            })
+    }
+
+    fn set_value(
+        &self,
+        hive: Hive,
+        path: &str,
+        view: RegistryView,
+        name: &str,
+        value: &RegValue,
+    ) -> io::Result<()> {
+        let path: String = physical_path(hive, path, view);
+        let mut keys = self.keys.borrow_mut();
+        let key: &mut MemoryKey = keys
+            .get_mut(&(hive, fold_case(&path)))
+            .ok_or_else(|| Self::not_found(hive, &path))?;
+        match key
+            .values
+            .iter_mut()
+            .find(|(value_name, _)| fold_case(value_name) == fold_case(name))
+        {
+            Some((_, existing)) => *existing = value.clone(),
+            None => key.values.push((name.to_string(), value.clone())),
+        }
+        Ok(())
+    }
+
+    fn delete_value(
+        &self,
+        hive: Hive,
+        path: &str,
+        view: RegistryView,
+        name: &str,
+    ) -> io::Result<()> {
+        let path: String = physical_path(hive, path, view);
+        let mut keys = self.keys.borrow_mut();
+        let key: &mut MemoryKey = keys
+            .get_mut(&(hive, fold_case(&path)))
+            .ok_or_else(|| Self::not_found(hive, &path))?;
+        let count: usize = key.values.len();
+        key.values
+            .retain(|(value_name, _)| fold_case(value_name) != fold_case(name));
+        if key.values.len() == count {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("registry value {} not found in {}\\{}", name, hive, path),
+            ));
+        }
+        Ok(())
+    }
+
+    fn delete_key(&self, hive: Hive, path: &str, view: RegistryView) -> io::Result<()> {
+        let path: String = fold_case(&physical_path(hive, path, view));
+        let prefix: String = format!("{}\\", path);
+        let mut keys = self.keys.borrow_mut();
+        if !keys.contains_key(&(hive, path.clone())) {
+            return Err(Self::not_found(hive, &path));
+        }
+        keys.retain(|(key_hive, key_path), _| {
+            *key_hive != hive || (*key_path != path && !key_path.starts_with(&prefix))
+        });
+        Ok(())
+    }
+}
+
+// +-----------------------+
+// |  private functions    |
+// +-----------------------+
+
+/// Fold a key or value name for case-insensitive lookups
+///
+/// Every name the memory backend compares goes through here, so keys and values follow the
+/// same rule.
+fn fold_case(name: &str) -> String {
+    name.to_lowercase()
+}
+
+/// Encode a string as UTF-16LE with a terminating null
+fn encode_utf16z(value: &str) -> Vec<u8> {
+    value
+        .encode_utf16()
+        .chain(Some(0))
+        .flat_map(|unit| unit.to_le_bytes())
+        .collect()
+}
+
+/// Decode UTF-16LE bytes, ignoring a trailing odd byte
+fn decode_utf16(bytes: &[u8]) -> String {
+    let units: Vec<u16> = bytes
+        .chunks_exact(2)
+        .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+        .collect();
+    String::from_utf16_lossy(&units)
+}
+
+/// Decode UTF-16LE bytes up to the first null
+fn decode_utf16z(bytes: &[u8]) -> String {
+    let text: String = decode_utf16(bytes);
+    match text.find('\0') {
+        Some(end) => text[..end].to_string(),
+        None => text,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RUN_KEY: &str = "SOFTWARE\\Microsoft\\Windows\\CurrentVersion\\Run";
+
+    #[test]
+    fn create_key_creates_every_parent() {
+        let backend = MemoryBackend::default();
+        backend
+            .create_key(Hive::CurrentUser, RUN_KEY, RegistryView::X64)
+            .unwrap();
+        assert!(backend.key_exists(Hive::CurrentUser, "SOFTWARE\\Microsoft", RegistryView::X64));
+        assert!(backend.key_exists(Hive::CurrentUser, RUN_KEY, RegistryView::X64));
+        assert!(!backend.key_exists(Hive::LocalMachine, RUN_KEY, RegistryView::X64));
+    }
+
+    #[test]
+    fn missing_keys_and_values_are_not_found() {
+        let backend = MemoryBackend::default();
+        let error = backend
+            .open_key(Hive::CurrentUser, RUN_KEY, RegistryView::X64)
+            .unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::NotFound);
+
+        backend
+            .create_key(Hive::CurrentUser, RUN_KEY, RegistryView::X64)
+            .unwrap();
+        let error = backend
+            .get_value(Hive::CurrentUser, RUN_KEY, RegistryView::X64, "Moein")
+            .unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::NotFound);
+        let error = backend
+            .delete_value(Hive::CurrentUser, RUN_KEY, RegistryView::X64, "Moein")
+            .unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn value_names_are_case_insensitive() {
+        let backend = MemoryBackend::default();
+        backend
+            .create_key(Hive::CurrentUser, RUN_KEY, RegistryView::X64)
+            .unwrap();
+        backend
+            .set_value(
+                Hive::CurrentUser,
+                RUN_KEY,
+                RegistryView::X64,
+                "Ünicode",
+                &RegValue::String("first".to_string()),
+            )
+            .unwrap();
+        backend
+            .set_value(
+                Hive::CurrentUser,
+                &RUN_KEY.to_uppercase(),
+                RegistryView::X64,
+                "üNICODE",
+                &RegValue::String("second".to_string()),
+            )
+            .unwrap();
+
+        let values = backend
+            .enum_values(Hive::CurrentUser, RUN_KEY, RegistryView::X64)
+            .unwrap();
+        assert_eq!(
+            values,
+            vec![(
+                "Ünicode".to_string(),
+                RegValue::String("second".to_string())
+            )]
+        );
+
+        backend
+            .delete_value(Hive::CurrentUser, RUN_KEY, RegistryView::X64, "ÜNICODE")
+            .unwrap();
+        assert!(backend
+            .enum_values(Hive::CurrentUser, RUN_KEY, RegistryView::X64)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn enum_keys_lists_direct_children_with_their_original_names() {
+        let backend = MemoryBackend::default();
+        // "İ" lowercases to two characters, the folded path is longer than the stored one
+        for path in [
+            "İstanbul\\Ärzte",
+            "İstanbul\\Ärzte\\Nested",
+            "İstanbul\\Ünal",
+        ] {
+            backend
+                .create_key(Hive::CurrentUser, path, RegistryView::X64)
+                .unwrap();
+        }
+
+        let mut children = backend
+            .enum_keys(Hive::CurrentUser, "İstanbul", RegistryView::X64)
+            .unwrap();
+        children.sort();
+        assert_eq!(children, vec!["Ärzte".to_string(), "Ünal".to_string()]);
+
+        let roots = backend
+            .enum_keys(Hive::CurrentUser, "", RegistryView::X64)
+            .unwrap();
+        assert_eq!(roots, vec!["İstanbul".to_string()]);
+    }
+
+    #[test]
+    fn delete_key_removes_the_subtree_only() {
+        let backend = MemoryBackend::default();
+        for path in ["Moein\\Settings\\Nested", "Moein\\Settings", "MoeinOther"] {
+            backend
+                .create_key(Hive::CurrentUser, path, RegistryView::X64)
+                .unwrap();
+        }
+
+        backend
+            .delete_key(Hive::CurrentUser, "moein\\SETTINGS", RegistryView::X64)
+            .unwrap();
+        assert!(backend.key_exists(Hive::CurrentUser, "Moein", RegistryView::X64));
+        assert!(!backend.key_exists(
+            Hive::CurrentUser,
+            "Moein\\Settings\\Nested",
+            RegistryView::X64
+        ));
+        assert!(backend.key_exists(Hive::CurrentUser, "MoeinOther", RegistryView::X64));
+
+        let error = backend
+            .delete_key(Hive::CurrentUser, "Moein\\Settings", RegistryView::X64)
+            .unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn x32_view_of_local_machine_software_is_redirected() {
+        assert_eq!(
+            physical_path(Hive::LocalMachine, "SOFTWARE\\Moein", RegistryView::X32),
+            "SOFTWARE\\Wow6432Node\\Moein"
+        );
+        assert_eq!(
+            physical_path(
+                Hive::LocalMachine,
+                "Software\\Wow6432Node\\Moein",
+                RegistryView::X32
+            ),
+            "Software\\Wow6432Node\\Moein"
+        );
+        assert_eq!(
+            physical_path(Hive::CurrentUser, "SOFTWARE\\Moein", RegistryView::X32),
+            "SOFTWARE\\Moein"
+        );
+
+        let backend = MemoryBackend::default();
+        backend
+            .create_key(Hive::LocalMachine, "SOFTWARE\\Moein", RegistryView::X32)
+            .unwrap();
+        assert!(backend.key_exists(
+            Hive::LocalMachine,
+            "SOFTWARE\\Wow6432Node\\Moein",
+            RegistryView::X64
+        ));
+        assert!(!backend.key_exists(Hive::LocalMachine, "SOFTWARE\\Moein", RegistryView::X64));
+    }
+
+    #[test]
+    fn values_round_trip_through_their_bytes() {
+        let values = [
+            RegValue::String("Moein".to_string()),
+            RegValue::ExpandString("%ProgramFiles%\\Moein".to_string()),
+            RegValue::MultiString(vec!["a".to_string(), "b".to_string()]),
+            RegValue::Dword(0x1234_5678),
+            RegValue::Qword(u64::MAX),
+            RegValue::Binary(vec![0, 1, 2, 255]),
+            RegValue::Raw {
+                kind: REG_NONE,
+                data: vec![1, 2, 3],
+            },
+        ];
+        for value in values {
+            assert_eq!(RegValue::from_bytes(value.kind(), &value.to_bytes()), value);
+        }
+
+        // A DWORD with the wrong length is kept as raw bytes
+        assert_eq!(
+            RegValue::from_bytes(REG_DWORD, &[1, 2]),
+            RegValue::Raw {
+                kind: REG_DWORD,
+                data: vec![1, 2],
+            }
+        );
+    }
+}
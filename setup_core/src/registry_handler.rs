@@ -0,0 +1,891 @@
+// +------------------+
+// |    dependencies  |
+// +------------------+
+
+// std
+use std::io;
+use std::path::{Path, PathBuf};
+
+// internal: autostart
+use crate::autostart::{AutostartLocation, LocationAction};
+
+// internal: backup_manifest
+use crate::backup_manifest::{sha256_hex, BackupEntry, BackupKind, BackupManifest, ManifestValue};
+
+// internal: constants
+use crate::constants::*;
+
+// internal: executor
+use crate::executor::Executor;
+
+// internal: paths
+use crate::paths::AppPaths;
+
+// internal: reg_file
+use crate::reg_file::{encode_regedit5, render_reg_file, RegFileKey};
+
+// internal: registry_backend
+use crate::registry_backend::{physical_path, Hive, RegValue, RegistryBackend, RegistryView};
+
+// internal: startup_policy
+use crate::startup_policy::{StartupAction, StartupCleanMode, StartupPolicy};
+
+// internal: utilities
+use crate::utilities::{filetime_now, is_64bit_os, timestamp_utc};
+
+// +------------------+
+// |      types       |
+// +------------------+
+
+/// A startup key being cleaned and where its backups go
+struct StartupLocation<'a> {
+    hive: Hive,
+    view: RegistryView,
+    path: &'a str,
+    key_full_path: &'a str,
+    data_folder: &'a Path,
+    run_id: &'a str,
+    file_prefix: &'a str,
+    file_extension: &'a str,
+}
+
+// +------------------+
+// | public functions |
+// +------------------+
+
+/// Set a registry key in the local machine registry to show the reboot status.
+///
+/// # Arguments
+///
+/// * `backend` - The registry backend to write to
+/// * `value` - The value to be set on the "rebooted" key to
+///
+/// # Returns
+///
+/// `std::io::Result<()>` - Whether the operation was successful or not
+pub fn set_rebooted_key(backend: &dyn RegistryBackend, value: i8) -> std::io::Result<()> {
+    if let Err(err) =
+        backend.create_key(Hive::LocalMachine, REBOOT_REGISTRY_PATH, RegistryView::X64)
+    {
+        return Err(io::Error::other(format!(
+            "failed to create reboot key: {}",
+            err
+        )));
+    }
+    backend.set_value(
+        Hive::LocalMachine,
+        REBOOT_REGISTRY_PATH,
+        RegistryView::X64,
+        REBOOTED_KEY_NAME,
+        &RegValue::String(value.to_string()),
+    )
+}
+
+/// schedule setup_assistant to be started once after the reboot
+/// The post-reboot steps (registry restoration and setup.exe) are run by setup_assistant itself
+///
+/// # Arguments
+///
+/// * `backend` - The registry backend to write to
+/// * `executable_path` - Path of the setup_assistant executable
+///
+/// # Returns
+///
+/// `std::io::Result<()>` - Whether the operation was successful or not
+pub fn schedule_resume_task(
+    backend: &dyn RegistryBackend,
+    executable_path: &Path,
+) -> std::io::Result<()> {
+    if backend.key_exists(Hive::CurrentUser, REGISTRY_RUNONCE_PATH, RegistryView::X64) {
+        log::info!("Run-once registry key opened successfully");
+    } else {
+        log::error!("Failed to open run once key, trying to create one...");
+        if backend
+            .create_key(Hive::CurrentUser, REGISTRY_RUNONCE_PATH, RegistryView::X64)
+            .is_err()
+        {
+            log::error!("Failed to create run once key");
+            return Err(io::Error::other("failed to create run once key"));
+        }
+        log::info!("Run-once registry key created successfully");
+    }
+
+    let command: RegValue = RegValue::String(format!("\"{}\"", executable_path.display()));
+    match backend.set_value(
+        Hive::CurrentUser,
+        REGISTRY_RUNONCE_PATH,
+        RegistryView::X64,
+        RESUME_TASK_NAME,
+        &command,
+    ) {
+        Ok(_) => {
+            log::info!("resume task scheduled successfully");
+            Ok(())
+        }
+        Err(_) => Err(io::Error::other(
+            "failed setting value for resume task schedule probably due to permissions",
+        )),
+    }
+}
+
+/// Process the configured autostart locations
+///
+/// Locations marked `clean` have the entries disabled by the policy exported to file and then
+/// deleted, `report` locations are only logged and `ignore` locations are skipped. Every backup
+/// file written is recorded in the manifest.
+///
+/// # Arguments
+///
+/// * `executor` - The executor carrying out the changes
+/// * `locations` - The autostart locations to be processed
+/// * `policy` - The policy deciding which entries are disabled
+/// * `mode` - Whether disabled entries are deleted or disabled through StartupApproved
+/// * `run_id` - Id of the current run, recorded with the backups
+/// * `paths` - Where the files of the assistant live, the backups go to the data folder
+/// * `manifest` - The backup manifest the written backups are added to
+///
+/// # Returns
+///
+/// `std::io::Result<()>` - Whether the operation was successful or not
+#[allow(clippy::too_many_arguments)]
+pub fn export_and_delete_startup_registry_keys(
+    executor: &dyn Executor,
+    locations: &[AutostartLocation],
+    policy: &StartupPolicy,
+    mode: StartupCleanMode,
+    run_id: &str,
+    paths: &AppPaths,
+    manifest: &mut BackupManifest,
+) -> std::io::Result<()> {
+    let is_64bit: bool = is_64bit_os();
+    if is_64bit {
+        log::info!("X64 os found.");
+    } else {
+        log::info!("X86 os found, 32-bit view locations are skipped.");
+    }
+    for location in locations {
+        // A 32-bit system only has the native view
+        let view: RegistryView = match (is_64bit, location.view) {
+            (true, view) => view,
+            (false, RegistryView::X32) => continue,
+            (false, _) => RegistryView::Native,
+        };
+        let action: LocationAction =
+            if location.action == LocationAction::Clean && location.is_winlogon() {
+                log::error!(
+                    "Winlogon values can not be cleaned, reporting {} instead",
+                    location.name
+                );
+                LocationAction::Report
+            } else {
+                location.action
+            };
+        match action {
+            LocationAction::Ignore => {
+                log::info!("Ignoring autostart location {}", location.name);
+            }
+            LocationAction::Report => {
+                report_autostart_location(executor.registry(), location, view)
+            }
+            LocationAction::Clean => export_and_delete_startup_values(
+                executor,
+                location,
+                view,
+                &paths.data,
+                policy,
+                mode,
+                run_id,
+                manifest,
+            )?,
+        }
+    }
+    Ok(())
+}
+
+// +-----------------------+
+// |  private functions    |
+// +-----------------------+
+
+/// Log the entries of an autostart location without changing them
+///
+/// # Arguments
+///
+/// * `backend` - The registry backend holding the key.
+/// * `location` - The autostart location to be reported.
+/// * `view` - The registry view to read the key in.
+///
+fn report_autostart_location(
+    backend: &dyn RegistryBackend,
+    location: &AutostartLocation,
+    view: RegistryView,
+) {
+    let key_full_path: String = format!(
+        "{}\\{}",
+        location.hive,
+        physical_path(location.hive, &location.path, view)
+    );
+    match backend.enum_values(location.hive, &location.path, view) {
+        Ok(values) => {
+            for (name, value) in values {
+                if location.includes_value(&name) {
+                    log::info!("[{}] {} {}: {}", location.name, key_full_path, name, value);
+                }
+            }
+        }
+        Err(err) => log::info!(
+            "[{}] {} not readable: {}",
+            location.name,
+            key_full_path,
+            err
+        ),
+    }
+}
+
+/// Export and delete the entries of an autostart location that the policy disables.
+///
+/// Every value of the key is checked against the policy. The disabled values are backed up and
+/// then deleted one by one or flagged in StartupApproved, so the kept values and the key itself
+/// stay untouched.
+///
+/// # Arguments
+///
+/// * `executor` - The executor carrying out the changes.
+/// * `location` - The autostart location to be cleaned.
+/// * `view` - The registry view to open the key in.
+/// * `data_folder` - The folder the backup files are written to.
+/// * `policy` - The policy deciding which entries are disabled.
+/// * `mode` - Whether disabled entries are deleted or disabled through StartupApproved.
+/// * `run_id` - Id of the current run.
+/// * `manifest` - The backup manifest the written backups are added to.
+///
+/// # Returns
+///
+/// A `Result` indicating success (`Ok`) or an `std::io::Error` if an error occurs.
+///
+#[allow(clippy::too_many_arguments)]
+fn export_and_delete_startup_values(
+    executor: &dyn Executor,
+    location: &AutostartLocation,
+    view: RegistryView,
+    data_folder: &Path,
+    policy: &StartupPolicy,
+    mode: StartupCleanMode,
+    run_id: &str,
+    manifest: &mut BackupManifest,
+) -> Result<(), std::io::Error> {
+    let hive: Hive = location.hive;
+    let key_full_path: String = format!("{}\\{}", hive, physical_path(hive, &location.path, view));
+    let values: Vec<(String, RegValue)> =
+        match executor.registry().enum_values(hive, &location.path, view) {
+            Ok(values) => values,
+            Err(err) => {
+                log::error!(
+                    "Failed to open registry key with {} access. Probably not existing {}: {}",
+                    view,
+                    key_full_path,
+                    err
+                );
+                return Ok(());
+            }
+        };
+
+    let mut disabled: Vec<(String, RegValue)> = Vec::new();
+    for (name, value) in values {
+        if !location.includes_value(&name) {
+            continue;
+        }
+        match policy.decide(&name, &value.to_string()) {
+            StartupAction::Keep => log::info!("Keeping startup entry {}: {}", name, value),
+            StartupAction::Disable => {
+                log::info!("Disabling startup entry {}: {}", name, value);
+                disabled.push((name, value));
+            }
+        }
+    }
+    if disabled.is_empty() {
+        log::info!("No startup entries to disable in {}", key_full_path);
+        return Ok(());
+    }
+
+    let file_prefix: String = format!("{}_{}_{}_", hive.name(), location.name, run_id);
+    let startup_location: StartupLocation = StartupLocation {
+        hive,
+        view,
+        path: &location.path,
+        key_full_path: &key_full_path,
+        data_folder,
+        run_id,
+        file_prefix: &file_prefix,
+        file_extension: match view {
+            RegistryView::X64 => "X64.reg",
+            RegistryView::X32 => "X32.reg",
+            RegistryView::Native => ".reg",
+        },
+    };
+    let is_run_key: bool = location.path.eq_ignore_ascii_case(REGISTRY_STARTUP_PATH);
+    match mode {
+        StartupCleanMode::StartupApproved if is_run_key => {
+            disable_startup_approved(executor, &startup_location, disabled, manifest)
+        }
+        StartupCleanMode::StartupApproved => {
+            log::info!(
+                "StartupApproved only covers Run keys, deleting the entries of {} instead",
+                key_full_path
+            );
+            delete_startup_values(executor, &startup_location, disabled, manifest)
+        }
+        StartupCleanMode::Delete => {
+            delete_startup_values(executor, &startup_location, disabled, manifest)
+        }
+    }
+}
+
+/// Back up and delete the disabled values of a startup key
+///
+/// # Arguments
+///
+/// * `executor` - The executor carrying out the changes.
+/// * `location` - The startup key being processed.
+/// * `disabled` - The values to be removed.
+/// * `manifest` - The backup manifest the backup is added to.
+///
+/// # Returns
+///
+/// A `Result` indicating success (`Ok`) or an `std::io::Error` if an error occurs.
+///
+fn delete_startup_values(
+    executor: &dyn Executor,
+    location: &StartupLocation,
+    disabled: Vec<(String, RegValue)>,
+    manifest: &mut BackupManifest,
+) -> std::io::Result<()> {
+    let backup_file_path: PathBuf = backup_file_for(
+        location.data_folder,
+        location.file_prefix,
+        location.file_extension,
+    );
+    let backup: RegFileKey = RegFileKey {
+        path: location.key_full_path.to_string(),
+        values: disabled,
+    };
+    let sha256: String = match export_values_to_file(executor, &backup_file_path, &backup) {
+        Ok(sha256) => sha256,
+        Err(err) => {
+            log::error!("failed to export registry key to file:{}", err);
+            log::error!(
+                "Skipping deletion of {} as it has no backup.",
+                location.key_full_path
+            );
+            return Ok(());
+        }
+    };
+    let mut entry: BackupEntry = backup_entry(
+        location,
+        BackupKind::RemovedValues,
+        location.view,
+        location.path,
+        &backup_file_path,
+        sha256,
+        &backup,
+    );
+    let mut failed: Vec<String> = Vec::new();
+    for (name, _) in &backup.values {
+        match executor
+            .registry()
+            .delete_value(location.hive, location.path, location.view, name)
+        {
+            Ok(_) => log::info!(
+                "startup entry {} in {} deleted successfully",
+                name,
+                location.key_full_path
+            ),
+            Err(err) => {
+                log::error!("Failed to delete startup entry {}: {}", name, err);
+                failed.push(name.clone());
+            }
+        }
+    }
+    // The manifest only lists the values that are gone
+    entry.values.retain(|value| !failed.contains(&value.name));
+    manifest.entries.push(entry);
+    failed_entries("delete", &failed, location.key_full_path)
+}
+
+/// Disable startup values through `Explorer\StartupApproved` the way Task Manager does
+///
+/// The `Run` values stay in place. The previous StartupApproved flags are backed up first, and
+/// entries without a flag are backed up as enabled, so importing the backup flips them back.
+/// Entries that are already disabled are left alone.
+///
+/// # Arguments
+///
+/// * `executor` - The executor carrying out the changes.
+/// * `location` - The startup key being processed.
+/// * `disabled` - The values to be disabled.
+/// * `manifest` - The backup manifest the backup is added to.
+///
+/// # Returns
+///
+/// A `Result` indicating success (`Ok`) or an `std::io::Error` if an error occurs.
+///
+fn disable_startup_approved(
+    executor: &dyn Executor,
+    location: &StartupLocation,
+    disabled: Vec<(String, RegValue)>,
+    manifest: &mut BackupManifest,
+) -> std::io::Result<()> {
+    let approved_path: &str =
+        if location.hive == Hive::LocalMachine && location.view == RegistryView::X32 {
+            REGISTRY_STARTUP_APPROVED_RUN32_PATH
+        } else {
+            REGISTRY_STARTUP_APPROVED_RUN_PATH
+        };
+    // StartupApproved is not redirected, it is always read from the native 64-bit view
+    let approved_view: RegistryView = if location.view == RegistryView::Native {
+        RegistryView::Native
+    } else {
+        RegistryView::X64
+    };
+
+    let backend: &dyn RegistryBackend = executor.registry();
+    let mut previous: Vec<(String, RegValue)> = Vec::new();
+    for (name, _) in disabled {
+        match backend.get_value(location.hive, approved_path, approved_view, &name) {
+            Ok(value) if !is_startup_approved_enabled(&value) => {
+                log::info!("startup entry {} is already disabled", name);
+            }
+            Ok(value) => previous.push((name, value)),
+            Err(_) => previous.push((name, startup_approved_value(true))),
+        }
+    }
+    if previous.is_empty() {
+        return Ok(());
+    }
+
+    let backup_file_path: PathBuf = backup_file_for(
+        location.data_folder,
+        &format!(
+            "{}_startup_approved_{}_",
+            location.hive.name(),
+            location.run_id
+        ),
+        location.file_extension,
+    );
+    let backup: RegFileKey = RegFileKey {
+        path: format!("{}\\{}", location.hive, approved_path),
+        values: previous,
+    };
+    let sha256: String = match export_values_to_file(executor, &backup_file_path, &backup) {
+        Ok(sha256) => sha256,
+        Err(err) => {
+            log::error!("failed to export registry key to file:{}", err);
+            log::error!("Skipping StartupApproved changes as they have no backup.");
+            return Ok(());
+        }
+    };
+    let mut entry: BackupEntry = backup_entry(
+        location,
+        BackupKind::StartupApprovedFlags,
+        approved_view,
+        approved_path,
+        &backup_file_path,
+        sha256,
+        &backup,
+    );
+
+    backend.create_key(location.hive, approved_path, approved_view)?;
+    let disabled_value: RegValue = startup_approved_value(false);
+    let mut failed: Vec<String> = Vec::new();
+    for (name, _) in &backup.values {
+        match backend.set_value(
+            location.hive,
+            approved_path,
+            approved_view,
+            name,
+            &disabled_value,
+        ) {
+            Ok(_) => log::info!(
+                "startup entry {} in {} disabled through StartupApproved",
+                name,
+                location.key_full_path
+            ),
+            Err(err) => {
+                log::error!("Failed to disable startup entry {}: {}", name, err);
+                failed.push(name.clone());
+            }
+        }
+    }
+    // The manifest only lists the flags that are changed
+    entry.values.retain(|value| !failed.contains(&value.name));
+    manifest.entries.push(entry);
+    failed_entries("disable", &failed, location.key_full_path)
+}
+
+/// Turn the startup entries that could not be changed into an error
+///
+/// # Arguments
+///
+/// * `action` - What was done to the entries, e.g. `delete`.
+/// * `failed` - Names of the entries that could not be changed.
+/// * `key_full_path` - The startup key holding the entries.
+///
+/// # Returns
+///
+/// `Ok` if no entry failed, otherwise an `std::io::Error` naming them.
+///
+fn failed_entries(action: &str, failed: &[String], key_full_path: &str) -> std::io::Result<()> {
+    if failed.is_empty() {
+        return Ok(());
+    }
+    Err(io::Error::other(format!(
+        "failed to {} startup entries {} in {}",
+        action,
+        failed.join(", "),
+        key_full_path
+    )))
+}
+
+/// Build a 12-byte StartupApproved flag
+///
+/// The first byte is `02` for enabled and `03` for disabled entries, followed by three zero
+/// bytes and the FILETIME the entry was disabled at (zero for enabled entries).
+fn startup_approved_value(enabled: bool) -> RegValue {
+    let mut data: Vec<u8> = vec![0; 12];
+    if enabled {
+        data[0] = 0x02;
+    } else {
+        data[0] = 0x03;
+        data[4..].copy_from_slice(&filetime_now().to_le_bytes());
+    }
+    RegValue::Binary(data)
+}
+
+/// Whether a StartupApproved flag marks its entry as enabled; the low bit is set when disabled
+fn is_startup_approved_enabled(value: &RegValue) -> bool {
+    match value {
+        RegValue::Binary(data) if !data.is_empty() => data[0] & 0x01 == 0,
+        _ => true,
+    }
+}
+
+/// Describe a written backup file for the manifest
+///
+/// # Arguments
+///
+/// * `location` - The startup key being processed.
+/// * `kind` - What the backed up values are.
+/// * `view` - The registry view the backed up key is opened in.
+/// * `key_path` - Path of the backed up key inside the hive.
+/// * `file_path` - Path to the written backup file.
+/// * `sha256` - Hash of the written backup file.
+/// * `backup` - The key written to the backup file.
+///
+/// # Returns
+///
+/// The manifest entry for the backup file.
+///
+fn backup_entry(
+    location: &StartupLocation,
+    kind: BackupKind,
+    view: RegistryView,
+    key_path: &str,
+    file_path: &Path,
+    sha256: String,
+    backup: &RegFileKey,
+) -> BackupEntry {
+    BackupEntry {
+        run_id: location.run_id.to_string(),
+        created_at: timestamp_utc(),
+        kind,
+        hive: location.hive,
+        view,
+        key_path: key_path.to_string(),
+        file: file_path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default(),
+        sha256,
+        values: backup
+            .values
+            .iter()
+            .map(|(name, value)| ManifestValue {
+                name: name.clone(),
+                value: value.clone(),
+            })
+            .collect(),
+    }
+}
+
+/// Build the path of the backup file for registry keys
+///
+/// # Arguments
+///
+/// * `data_folder` - The folder the backup files are written to.
+/// * `hive_name` - Name of the registry hive.
+/// * `file_extension` - String to be used as the extension of the file
+///
+/// # Returns
+///
+/// The path of the backup file inside the data folder.
+///
+fn backup_file_for(data_folder: &Path, hive_name: &str, file_extension: &str) -> PathBuf {
+    let backup_file_path =
+        data_folder.join(format!("{}{}", hive_name.to_lowercase(), file_extension));
+    log::info!(
+        "Registry file path for {} in {} is: {}",
+        file_extension,
+        hive_name,
+        backup_file_path.display()
+    );
+    backup_file_path
+}
+
+/// Export the disabled startup values to the backup file
+///
+/// The values are written in the REGEDIT5 format, byte-compatible with regedit.
+///
+/// # Arguments
+///
+/// * `executor` - The executor writing the file
+/// * `file_path` - Path to the backup file
+/// * `key` - The startup key path and the values to be exported
+///
+/// # Returns
+///
+/// A `Result` with the SHA-256 of the written file or an `std::io::Error` if an error occurs.
+///
+fn export_values_to_file(
+    executor: &dyn Executor,
+    file_path: &Path,
+    key: &RegFileKey,
+) -> std::io::Result<String> {
+    let content: Vec<u8> = encode_regedit5(&render_reg_file(std::slice::from_ref(key)));
+    match executor.write_file(file_path, &content) {
+        Ok(_) => {
+            log::info!(
+                "{} values of {} exported successfully in {}",
+                key.values.len(),
+                key.path,
+                file_path.display()
+            );
+            Ok(sha256_hex(&content))
+        }
+        Err(err) => {
+            log::error!(
+                "Exporting {} to {} completely failed.",
+                key.path,
+                file_path.display()
+            );
+            Err(err)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::executor::SystemExecutor;
+    use crate::registry_backend::MemoryBackend;
+    use crate::test_support::{LockedValue, TempFolder};
+
+    /// The current user Run key, cleaned
+    fn run_location() -> AutostartLocation {
+        AutostartLocation {
+            name: "run".to_string(),
+            hive: Hive::CurrentUser,
+            view: RegistryView::X64,
+            path: REGISTRY_STARTUP_PATH.to_string(),
+            values: Vec::new(),
+            action: LocationAction::Clean,
+        }
+    }
+
+    /// A registry with the given current user Run values
+    fn run_values(names: &[&str]) -> MemoryBackend {
+        let backend = MemoryBackend::default();
+        backend
+            .create_key(Hive::CurrentUser, REGISTRY_STARTUP_PATH, RegistryView::X64)
+            .unwrap();
+        for name in names {
+            backend
+                .set_value(
+                    Hive::CurrentUser,
+                    REGISTRY_STARTUP_PATH,
+                    RegistryView::X64,
+                    name,
+                    &RegValue::String(format!("C:\\{}.exe", name)),
+                )
+                .unwrap();
+        }
+        backend
+    }
+
+    #[test]
+    fn failed_deletes_fail_the_export_and_stay_out_of_the_manifest() {
+        let temp = TempFolder::new("registry_handler_locked");
+        let backend = LockedValue {
+            inner: run_values(&["Free", "Locked"]),
+            name: "Locked",
+        };
+        let executor = SystemExecutor::new(&backend);
+        let mut manifest = BackupManifest::default();
+
+        let error = export_and_delete_startup_registry_keys(
+            &executor,
+            &[run_location()],
+            &StartupPolicy::default(),
+            StartupCleanMode::Delete,
+            "run",
+            &AppPaths::new(&temp.0),
+            &mut manifest,
+        )
+        .unwrap_err();
+
+        assert!(error.to_string().contains("Locked"), "{}", error);
+        let names: Vec<&str> = manifest.entries[0]
+            .values
+            .iter()
+            .map(|value| value.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["Free"]);
+        assert!(backend
+            .get_value(
+                Hive::CurrentUser,
+                REGISTRY_STARTUP_PATH,
+                RegistryView::X64,
+                "Free"
+            )
+            .is_err());
+    }
+
+    #[test]
+    fn rebooted_key_is_set_in_the_64_bit_view() {
+        let backend = MemoryBackend::default();
+
+        set_rebooted_key(&backend, 1).unwrap();
+
+        assert_eq!(
+            backend
+                .get_value(
+                    Hive::LocalMachine,
+                    REBOOT_REGISTRY_PATH,
+                    RegistryView::X64,
+                    REBOOTED_KEY_NAME
+                )
+                .unwrap(),
+            RegValue::String("1".to_string())
+        );
+    }
+
+    #[test]
+    fn resume_task_is_scheduled_in_run_once() {
+        let backend = MemoryBackend::default();
+
+        schedule_resume_task(&backend, Path::new("C:\\Moein\\setup_assistant.exe")).unwrap();
+
+        assert_eq!(
+            backend
+                .get_value(
+                    Hive::CurrentUser,
+                    REGISTRY_RUNONCE_PATH,
+                    RegistryView::X64,
+                    RESUME_TASK_NAME
+                )
+                .unwrap(),
+            RegValue::String("\"C:\\Moein\\setup_assistant.exe\"".to_string())
+        );
+    }
+
+    #[test]
+    fn deleted_values_are_backed_up_and_recorded() {
+        let temp = TempFolder::new("registry_handler_delete");
+        let backend = run_values(&["App"]);
+        let executor = SystemExecutor::new(&backend);
+        let mut manifest = BackupManifest::default();
+
+        export_and_delete_startup_registry_keys(
+            &executor,
+            &[run_location()],
+            &StartupPolicy::default(),
+            StartupCleanMode::Delete,
+            "run",
+            &AppPaths::new(&temp.0),
+            &mut manifest,
+        )
+        .unwrap();
+
+        assert!(backend
+            .enum_values(Hive::CurrentUser, REGISTRY_STARTUP_PATH, RegistryView::X64)
+            .unwrap()
+            .is_empty());
+        let entry: &BackupEntry = &manifest.entries[0];
+        assert_eq!(entry.run_id, "run");
+        assert_eq!(entry.kind, BackupKind::RemovedValues);
+        assert_eq!(entry.key_path, REGISTRY_STARTUP_PATH);
+        assert_eq!(
+            entry.values,
+            vec![ManifestValue {
+                name: "App".to_string(),
+                value: RegValue::String("C:\\App.exe".to_string()),
+            }]
+        );
+        let content: Vec<u8> = std::fs::read(temp.0.join(&entry.file)).unwrap();
+        assert_eq!(entry.sha256, sha256_hex(&content));
+        assert_eq!(
+            content,
+            encode_regedit5(&render_reg_file(&[RegFileKey {
+                path: format!(
+                    "HKEY_CURRENT_USER\\{}",
+                    physical_path(Hive::CurrentUser, REGISTRY_STARTUP_PATH, RegistryView::X64)
+                ),
+                values: vec![(
+                    "App".to_string(),
+                    RegValue::String("C:\\App.exe".to_string())
+                )],
+            }]))
+        );
+    }
+
+    #[test]
+    fn startup_approved_mode_leaves_run_values_in_place() {
+        let temp = TempFolder::new("registry_handler_approved");
+        let backend = run_values(&["App"]);
+        let executor = SystemExecutor::new(&backend);
+        let mut manifest = BackupManifest::default();
+
+        export_and_delete_startup_registry_keys(
+            &executor,
+            &[run_location()],
+            &StartupPolicy::default(),
+            StartupCleanMode::StartupApproved,
+            "run",
+            &AppPaths::new(&temp.0),
+            &mut manifest,
+        )
+        .unwrap();
+
+        assert_eq!(
+            backend
+                .get_value(
+                    Hive::CurrentUser,
+                    REGISTRY_STARTUP_PATH,
+                    RegistryView::X64,
+                    "App"
+                )
+                .unwrap(),
+            RegValue::String("C:\\App.exe".to_string())
+        );
+        let flag: RegValue = backend
+            .get_value(
+                Hive::CurrentUser,
+                REGISTRY_STARTUP_APPROVED_RUN_PATH,
+                RegistryView::X64,
+                "App",
+            )
+            .unwrap();
+        assert!(!is_startup_approved_enabled(&flag));
+        let entry: &BackupEntry = &manifest.entries[0];
+        assert_eq!(entry.kind, BackupKind::StartupApprovedFlags);
+        assert_eq!(entry.values[0].value, startup_approved_value(true));
+    }
+}
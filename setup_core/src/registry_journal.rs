@@ -0,0 +1,658 @@
+// +------------------+
+// |    dependencies  |
+// +------------------+
+
+// std
+use std::cell::RefCell;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+// serde
+use serde::{Deserialize, Serialize};
+
+// internal: registry_backend
+use crate::registry_backend::{Hive, RegValue, RegistryBackend, RegistryView};
+
+// +------------------+
+// |      types       |
+// +------------------+
+
+/// A key and its values as they were before the key got deleted
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KeySnapshot {
+    pub path: String,
+    pub values: Vec<(String, RegValue)>,
+}
+
+/// A registry change together with what it replaced
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "change", rename_all = "snake_case")]
+pub enum JournalChange {
+    /// A key that did not exist was created, `path` is the top-most key that was missing
+    CreateKey,
+    /// A value was written, `previous` is `None` if it did not exist
+    SetValue {
+        name: String,
+        previous: Option<RegValue>,
+    },
+    /// A value was deleted
+    DeleteValue {
+        name: String,
+        previous: Option<RegValue>,
+    },
+    /// A key was deleted, the snapshot holds the key and all of its sub keys
+    DeleteKey { previous: Vec<KeySnapshot> },
+}
+
+/// A single journaled registry change
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JournalEntry {
+    pub run_id: String,
+    pub hive: Hive,
+    pub view: RegistryView,
+    pub path: String,
+    #[serde(flatten)]
+    pub change: JournalChange,
+}
+
+impl fmt::Display for JournalEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let key: String = format!("{}\\{} ({} view)", self.hive, self.path, self.view);
+        match &self.change {
+            JournalChange::CreateKey => write!(f, "create key {}", key),
+            JournalChange::SetValue { name, .. } => write!(f, "set \"{}\" in {}", name, key),
+            JournalChange::DeleteValue { name, .. } => {
+                write!(f, "delete \"{}\" in {}", name, key)
+            }
+            JournalChange::DeleteKey { .. } => write!(f, "delete key {}", key),
+        }
+    }
+}
+
+/// The registry changes made by setup_assistant, oldest first
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Journal {
+    pub entries: Vec<JournalEntry>,
+}
+
+impl Journal {
+    /// Read a journal from disk, an empty one if there is no file
+    pub fn load(path: &Path) -> io::Result<Journal> {
+        match fs::read_to_string(path) {
+            Ok(content) => serde_json::from_str(&content)
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err)),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(Journal::default()),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Write the journal to disk
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let content: String = serde_json::to_string_pretty(self)?;
+        fs::write(path, content)
+    }
+}
+
+/// Registry backend recording every change in a journal on disk before making it
+///
+/// Reads go straight to the wrapped backend. A change is only made once its journal entry is
+/// persisted; if persisting fails the change is refused.
+pub struct JournalingBackend<'a> {
+    inner: &'a dyn RegistryBackend,
+    journal_path: PathBuf,
+    run_id: String,
+    journal: RefCell<Journal>,
+}
+
+impl<'a> JournalingBackend<'a> {
+    /// Wrap a backend, continuing the journal already on disk
+    ///
+    /// # Arguments
+    ///
+    /// * `inner` - The backend the changes are made through
+    /// * `journal_path` - Path of the journal file
+    /// * `run_id` - Id of the current run, stored with every entry
+    ///
+    /// # Returns
+    ///
+    /// `std::io::Result<JournalingBackend>` - The backend, or an error if the journal is unreadable
+    pub fn new(
+        inner: &'a dyn RegistryBackend,
+        journal_path: &Path,
+        run_id: &str,
+    ) -> io::Result<JournalingBackend<'a>> {
+        Ok(JournalingBackend {
+            inner,
+            journal_path: journal_path.to_path_buf(),
+            run_id: run_id.to_string(),
+            journal: RefCell::new(Journal::load(journal_path)?),
+        })
+    }
+
+    /// Persist a journal entry before the change it describes is made
+    fn record(
+        &self,
+        hive: Hive,
+        path: &str,
+        view: RegistryView,
+        change: JournalChange,
+    ) -> io::Result<()> {
+        let mut journal = self.journal.borrow_mut();
+        journal.entries.push(JournalEntry {
+            run_id: self.run_id.clone(),
+            hive,
+            view,
+            path: path.to_string(),
+            change,
+        });
+        if let Err(err) = journal.save(&self.journal_path) {
+            journal.entries.pop();
+            return Err(io::Error::other(format!(
+                "failed to write registry journal {}: {}",
+                self.journal_path.display(),
+                err
+            )));
+        }
+        Ok(())
+    }
+
+    /// Read a value, `None` if it does not exist
+    fn previous_value(
+        &self,
+        hive: Hive,
+        path: &str,
+        view: RegistryView,
+        name: &str,
+    ) -> io::Result<Option<RegValue>> {
+        match self.inner.get_value(hive, path, view, name) {
+            Ok(value) => Ok(Some(value)),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Capture a key and all of its sub keys
+    fn snapshot(
+        &self,
+        hive: Hive,
+        path: &str,
+        view: RegistryView,
+        snapshots: &mut Vec<KeySnapshot>,
+    ) -> io::Result<()> {
+        snapshots.push(KeySnapshot {
+            path: path.to_string(),
+            values: self.inner.enum_values(hive, path, view)?,
+        });
+        for sub_key in self.inner.enum_keys(hive, path, view)? {
+            self.snapshot(hive, &format!("{}\\{}", path, sub_key), view, snapshots)?;
+        }
+        Ok(())
+    }
+}
+
+impl RegistryBackend for JournalingBackend<'_> {
+    fn open_key(&self, hive: Hive, path: &str, view: RegistryView) -> io::Result<()> {
+        self.inner.open_key(hive, path, view)
+    }
+
+    fn create_key(&self, hive: Hive, path: &str, view: RegistryView) -> io::Result<()> {
+        // Journal the top-most missing key, deleting it removes every key created below it
+        let mut created: Option<String> = None;
+        let mut current: String = String::new();
+        for component in path.split('\\').filter(|component| !component.is_empty()) {
+            if !current.is_empty() {
+                current.push('\\');
+            }
+            current.push_str(component);
+            if !self.inner.key_exists(hive, &current, view) {
+                created = Some(current.clone());
+                break;
+            }
+        }
+        if let Some(created) = created {
+            self.record(hive, &created, view, JournalChange::CreateKey)?;
+        }
+        self.inner.create_key(hive, path, view)
+    }
+
+    fn enum_keys(&self, hive: Hive, path: &str, view: RegistryView) -> io::Result<Vec<String>> {
+        self.inner.enum_keys(hive, path, view)
+    }
+
+    fn enum_values(
+        &self,
+        hive: Hive,
+        path: &str,
+        view: RegistryView,
+    ) -> io::Result<Vec<(String, RegValue)>> {
+        self.inner.enum_values(hive, path, view)
+    }
+
+    fn get_value(
+        &self,
+        hive: Hive,
+        path: &str,
+        view: RegistryView,
+        name: &str,
+    ) -> io::Result<RegValue> {
+        self.inner.get_value(hive, path, view, name)
+    }
+
+    fn set_value(
+        &self,
+        hive: Hive,
+        path: &str,
+        view: RegistryView,
+        name: &str,
+        value: &RegValue,
+    ) -> io::Result<()> {
+        let previous: Option<RegValue> = self.previous_value(hive, path, view, name)?;
+        self.record(
+            hive,
+            path,
+            view,
+            JournalChange::SetValue {
+                name: name.to_string(),
+                previous,
+            },
+        )?;
+        self.inner.set_value(hive, path, view, name, value)
+    }
+
+    fn delete_value(
+        &self,
+        hive: Hive,
+        path: &str,
+        view: RegistryView,
+        name: &str,
+    ) -> io::Result<()> {
+        let previous: Option<RegValue> = self.previous_value(hive, path, view, name)?;
+        if previous.is_some() {
+            self.record(
+                hive,
+                path,
+                view,
+                JournalChange::DeleteValue {
+                    name: name.to_string(),
+                    previous,
+                },
+            )?;
+        }
+        self.inner.delete_value(hive, path, view, name)
+    }
+
+    fn delete_key(&self, hive: Hive, path: &str, view: RegistryView) -> io::Result<()> {
+        if self.inner.key_exists(hive, path, view) {
+            let mut previous: Vec<KeySnapshot> = Vec::new();
+            self.snapshot(hive, path, view, &mut previous)?;
+            self.record(hive, path, view, JournalChange::DeleteKey { previous })?;
+        }
+        self.inner.delete_key(hive, path, view)
+    }
+}
+
+// +------------------+
+// | public functions |
+// +------------------+
+
+/// Undo journaled registry changes, newest first
+///
+/// Rolled back entries are removed from the journal file; entries that fail to roll back stay
+/// in it, so the rollback can be retried.
+///
+/// # Arguments
+///
+/// * `backend` - The registry backend to undo the changes through, not journaled itself
+/// * `journal_path` - Path of the journal file
+/// * `run_id` - Only roll back the changes of this run, all changes if `None`
+///
+/// # Returns
+///
+/// `std::io::Result<usize>` - The number of changes rolled back
+pub fn rollback_journal(
+    backend: &dyn RegistryBackend,
+    journal_path: &Path,
+    run_id: Option<&str>,
+) -> io::Result<usize> {
+    let journal: Journal = Journal::load(journal_path)?;
+    let mut kept: Vec<JournalEntry> = Vec::new();
+    let mut rolled_back: usize = 0;
+    let mut failures: usize = 0;
+    let mut entries: Vec<JournalEntry> = journal.entries;
+    while let Some(entry) = entries.pop() {
+        if run_id.is_some_and(|run_id| run_id != entry.run_id) {
+            kept.push(entry);
+            continue;
+        }
+        match undo_change(backend, &entry) {
+            Ok(_) => {
+                log::info!("Rolled back: {}", entry);
+                rolled_back += 1;
+            }
+            Err(err) => {
+                log::error!("Failed to roll back {}: {}", entry, err);
+                kept.push(entry);
+                failures += 1;
+            }
+        }
+    }
+    kept.reverse();
+    Journal { entries: kept }.save(journal_path)?;
+    if failures > 0 {
+        return Err(io::Error::other(format!(
+            "{} registry changes could not be rolled back",
+            failures
+        )));
+    }
+    Ok(rolled_back)
+}
+
+// +-----------------------+
+// |  private functions    |
+// +-----------------------+
+
+/// Undo a single journaled change; changes that were never made are undone without error
+fn undo_change(backend: &dyn RegistryBackend, entry: &JournalEntry) -> io::Result<()> {
+    let (hive, path, view) = (entry.hive, entry.path.as_str(), entry.view);
+    let ignore_missing = |result: io::Result<()>| match result {
+        Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err),
+        _ => Ok(()),
+    };
+    match &entry.change {
+        JournalChange::CreateKey => ignore_missing(backend.delete_key(hive, path, view)),
+        JournalChange::SetValue {
+            name,
+            previous: None,
+        } => ignore_missing(backend.delete_value(hive, path, view, name)),
+        JournalChange::SetValue {
+            name,
+            previous: Some(previous),
+        }
+        | JournalChange::DeleteValue {
+            name,
+            previous: Some(previous),
+        } => {
+            backend.create_key(hive, path, view)?;
+            backend.set_value(hive, path, view, name, previous)
+        }
+        JournalChange::DeleteValue { previous: None, .. } => Ok(()),
+        JournalChange::DeleteKey { previous } => {
+            for snapshot in previous {
+                backend.create_key(hive, &snapshot.path, view)?;
+                for (name, value) in &snapshot.values {
+                    backend.set_value(hive, &snapshot.path, view, name, value)?;
+                }
+            }
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::registry_backend::MemoryBackend;
+    use crate::test_support::{LockedValue, TempFolder};
+
+    const KEY: &str = "SOFTWARE\\Vendor\\App";
+
+    fn string(data: &str) -> RegValue {
+        RegValue::String(data.to_string())
+    }
+
+    /// A registry holding `SOFTWARE\Vendor` with the value `Kept`
+    fn vendor_key() -> MemoryBackend {
+        let backend = MemoryBackend::default();
+        backend
+            .create_key(Hive::CurrentUser, "SOFTWARE\\Vendor", RegistryView::Native)
+            .unwrap();
+        backend
+            .set_value(
+                Hive::CurrentUser,
+                "SOFTWARE\\Vendor",
+                RegistryView::Native,
+                "Kept",
+                &string("old"),
+            )
+            .unwrap();
+        backend
+    }
+
+    fn value(backend: &dyn RegistryBackend, path: &str, name: &str) -> Option<RegValue> {
+        backend
+            .get_value(Hive::CurrentUser, path, RegistryView::Native, name)
+            .ok()
+    }
+
+    #[test]
+    fn creating_a_key_records_the_top_most_missing_key() {
+        let temp = TempFolder::new("journal_create");
+        let journal_path: PathBuf = temp.0.join("journal.json");
+        let backend = vendor_key();
+        let journaling = JournalingBackend::new(&backend, &journal_path, "run").unwrap();
+
+        journaling
+            .create_key(
+                Hive::CurrentUser,
+                "SOFTWARE\\Vendor\\App\\Sub",
+                RegistryView::Native,
+            )
+            .unwrap();
+        // An existing key is not journaled
+        journaling
+            .create_key(Hive::CurrentUser, "SOFTWARE\\Vendor", RegistryView::Native)
+            .unwrap();
+
+        let entries: Vec<JournalEntry> = Journal::load(&journal_path).unwrap().entries;
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].path, KEY);
+        assert!(matches!(entries[0].change, JournalChange::CreateKey));
+    }
+
+    #[test]
+    fn values_record_what_they_replaced() {
+        let temp = TempFolder::new("journal_values");
+        let journal_path: PathBuf = temp.0.join("journal.json");
+        let backend = vendor_key();
+        let journaling = JournalingBackend::new(&backend, &journal_path, "run").unwrap();
+        let vendor: &str = "SOFTWARE\\Vendor";
+
+        journaling
+            .set_value(
+                Hive::CurrentUser,
+                vendor,
+                RegistryView::Native,
+                "Kept",
+                &string("new"),
+            )
+            .unwrap();
+        journaling
+            .set_value(
+                Hive::CurrentUser,
+                vendor,
+                RegistryView::Native,
+                "Added",
+                &string("new"),
+            )
+            .unwrap();
+        journaling
+            .delete_value(Hive::CurrentUser, vendor, RegistryView::Native, "Kept")
+            .unwrap();
+        // Deleting a missing value changes nothing and is not journaled
+        assert!(journaling
+            .delete_value(Hive::CurrentUser, vendor, RegistryView::Native, "Missing")
+            .is_err());
+
+        let changes: Vec<JournalChange> = Journal::load(&journal_path)
+            .unwrap()
+            .entries
+            .into_iter()
+            .map(|entry| entry.change)
+            .collect();
+        assert_eq!(changes.len(), 3);
+        assert!(matches!(
+            &changes[0],
+            JournalChange::SetValue { name, previous: Some(previous) }
+                if name == "Kept" && *previous == string("old")
+        ));
+        assert!(matches!(
+            &changes[1],
+            JournalChange::SetValue { name, previous: None } if name == "Added"
+        ));
+        assert!(matches!(
+            &changes[2],
+            JournalChange::DeleteValue { name, previous: Some(previous) }
+                if name == "Kept" && *previous == string("new")
+        ));
+    }
+
+    #[test]
+    fn deleting_a_key_records_its_sub_keys() {
+        let temp = TempFolder::new("journal_delete_key");
+        let journal_path: PathBuf = temp.0.join("journal.json");
+        let backend = vendor_key();
+        backend
+            .create_key(
+                Hive::CurrentUser,
+                "SOFTWARE\\Vendor\\App",
+                RegistryView::Native,
+            )
+            .unwrap();
+        backend
+            .set_value(
+                Hive::CurrentUser,
+                "SOFTWARE\\Vendor\\App",
+                RegistryView::Native,
+                "Nested",
+                &string("data"),
+            )
+            .unwrap();
+        let journaling = JournalingBackend::new(&backend, &journal_path, "run").unwrap();
+
+        journaling
+            .delete_key(Hive::CurrentUser, "SOFTWARE\\Vendor", RegistryView::Native)
+            .unwrap();
+        assert_eq!(rollback_journal(&backend, &journal_path, None).unwrap(), 1);
+
+        assert_eq!(
+            value(&backend, "SOFTWARE\\Vendor", "Kept"),
+            Some(string("old"))
+        );
+        assert_eq!(value(&backend, KEY, "Nested"), Some(string("data")));
+    }
+
+    #[test]
+    fn rollback_undoes_the_changes_newest_first() {
+        let temp = TempFolder::new("journal_rollback");
+        let journal_path: PathBuf = temp.0.join("journal.json");
+        let backend = vendor_key();
+        let journaling = JournalingBackend::new(&backend, &journal_path, "run").unwrap();
+        let vendor: &str = "SOFTWARE\\Vendor";
+
+        // Undone oldest first, "Kept" would end up as "first"
+        for data in ["first", "second"] {
+            journaling
+                .set_value(
+                    Hive::CurrentUser,
+                    vendor,
+                    RegistryView::Native,
+                    "Kept",
+                    &string(data),
+                )
+                .unwrap();
+        }
+        journaling
+            .create_key(Hive::CurrentUser, KEY, RegistryView::Native)
+            .unwrap();
+
+        assert_eq!(rollback_journal(&backend, &journal_path, None).unwrap(), 3);
+
+        assert_eq!(value(&backend, vendor, "Kept"), Some(string("old")));
+        assert!(!backend.key_exists(Hive::CurrentUser, KEY, RegistryView::Native));
+        assert!(Journal::load(&journal_path).unwrap().entries.is_empty());
+    }
+
+    #[test]
+    fn rollback_of_a_run_keeps_the_entries_of_other_runs_in_order() {
+        let temp = TempFolder::new("journal_run_filter");
+        let journal_path: PathBuf = temp.0.join("journal.json");
+        let backend = vendor_key();
+        for (run_id, name) in [("earlier", "A"), ("run", "B"), ("earlier", "C")] {
+            JournalingBackend::new(&backend, &journal_path, run_id)
+                .unwrap()
+                .set_value(
+                    Hive::CurrentUser,
+                    "SOFTWARE\\Vendor",
+                    RegistryView::Native,
+                    name,
+                    &string("data"),
+                )
+                .unwrap();
+        }
+
+        assert_eq!(
+            rollback_journal(&backend, &journal_path, Some("run")).unwrap(),
+            1
+        );
+
+        assert_eq!(value(&backend, "SOFTWARE\\Vendor", "B"), None);
+        assert_eq!(
+            value(&backend, "SOFTWARE\\Vendor", "A"),
+            Some(string("data"))
+        );
+        let kept: Vec<(String, String)> = Journal::load(&journal_path)
+            .unwrap()
+            .entries
+            .into_iter()
+            .map(|entry| match entry.change {
+                JournalChange::SetValue { name, .. } => (entry.run_id, name),
+                other => panic!("unexpected change {:?}", other),
+            })
+            .collect();
+        assert_eq!(
+            kept,
+            vec![
+                ("earlier".to_string(), "A".to_string()),
+                ("earlier".to_string(), "C".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn changes_that_fail_to_roll_back_stay_in_the_journal() {
+        let temp = TempFolder::new("journal_failed_undo");
+        let journal_path: PathBuf = temp.0.join("journal.json");
+        let backend = vendor_key();
+        let journaling = JournalingBackend::new(&backend, &journal_path, "run").unwrap();
+        for name in ["Locked", "Free"] {
+            journaling
+                .set_value(
+                    Hive::CurrentUser,
+                    "SOFTWARE\\Vendor",
+                    RegistryView::Native,
+                    name,
+                    &string("data"),
+                )
+                .unwrap();
+        }
+
+        let locked = LockedValue {
+            inner: backend,
+            name: "Locked",
+        };
+        assert!(rollback_journal(&locked, &journal_path, None).is_err());
+
+        let entries: Vec<JournalEntry> = Journal::load(&journal_path).unwrap().entries;
+        assert_eq!(entries.len(), 1);
+        assert!(matches!(
+            &entries[0].change,
+            JournalChange::SetValue { name, .. } if name == "Locked"
+        ));
+        assert_eq!(value(&locked, "SOFTWARE\\Vendor", "Free"), None);
+    }
+}
@@ -0,0 +1,161 @@
+// +------------------+
+// |    dependencies  |
+// +------------------+
+
+// std
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+// internal: backup_manifest
+use crate::backup_manifest::{sha256_hex, BackupEntry, BackupManifest, ConflictPolicy};
+
+// internal: merge
+use crate::merge::{merge_backup, ValueDecision};
+
+// internal: paths
+use crate::paths::AppPaths;
+
+// internal: reg_file
+use crate::reg_file::{parse_reg_file, RegOperation};
+
+// internal: registry_backend
+use crate::registry_backend::RegistryBackend;
+
+// internal: startup_folder
+use crate::startup_folder::restore_startup_shortcuts;
+
+// +------------------+
+// | public functions |
+// +------------------+
+
+/// Restore everything recorded in the backup manifest of the data folder
+///
+/// Backups that fail stay in the manifest for another attempt; once everything is back the
+/// manifest is archived under the restored name.
+///
+/// # Arguments
+///
+/// * `backend` - The registry backend to restore into
+/// * `paths` - Where the files of the assistant live
+///
+/// # Returns
+///
+/// `std::io::Result<usize>` - The number of backups left to retry, an error if the manifest is
+/// unreadable or could not be updated
+pub fn restore_backups(backend: &dyn RegistryBackend, paths: &AppPaths) -> io::Result<usize> {
+    let manifest_path: PathBuf = paths.backup_manifest();
+    let manifest: BackupManifest = match BackupManifest::load(&manifest_path)? {
+        Some(manifest) => manifest,
+        None => {
+            log::info!(
+                "No backup manifest at {}, nothing to restore",
+                manifest_path.display()
+            );
+            return Ok(0);
+        }
+    };
+    log::info!(
+        "Backup manifest found with {} registry backups and {} startup shortcuts, conflict policy {:?}",
+        manifest.entries.len(),
+        manifest.startup_shortcuts.len(),
+        manifest.conflict_policy
+    );
+
+    let mut remaining: BackupManifest = BackupManifest {
+        conflict_policy: manifest.conflict_policy,
+        startup_shortcuts: restore_startup_shortcuts(&manifest.startup_shortcuts),
+        ..BackupManifest::default()
+    };
+    for entry in manifest.entries {
+        match restore_registry(backend, &paths.data, &entry, manifest.conflict_policy) {
+            Ok(_) => log::info!("registry backup {} restored successfully", entry.file),
+            Err(err) => {
+                log::error!("Error restoring registry backup {}: {}", entry.file, err);
+                remaining.entries.push(entry);
+            }
+        }
+    }
+
+    // Keep what failed for another attempt, archive the manifest once everything is back
+    let left: usize = remaining.entries.len() + remaining.startup_shortcuts.len();
+    if remaining.is_empty() {
+        fs::rename(&manifest_path, paths.restored_manifest())?;
+    } else {
+        remaining.save(&manifest_path)?;
+    }
+    Ok(left)
+}
+
+// +-----------------------+
+// |  private functions    |
+// +-----------------------+
+
+/// Validate a registry backup recorded in the manifest and merge it into the registry
+///
+/// The file has to match the hash in the manifest and is parsed as a whole before anything is
+/// written, so a modified or malformed backup leaves the registry untouched. Values are merged
+/// one by one in the view the backup was taken from and the decision for each is logged.
+fn restore_registry(
+    backend: &dyn RegistryBackend,
+    backup_folder: &Path,
+    entry: &BackupEntry,
+    policy: ConflictPolicy,
+) -> io::Result<()> {
+    let file_path: PathBuf = backup_folder.join(&entry.file);
+    let bytes: Vec<u8> = fs::read(&file_path)?;
+    let hash: String = sha256_hex(&bytes);
+    if !hash.eq_ignore_ascii_case(&entry.sha256) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "{} does not match the manifest: sha256 {} instead of {}",
+                file_path.display(),
+                hash,
+                entry.sha256
+            ),
+        ));
+    }
+    let operations: Vec<RegOperation> = match parse_reg_file(&bytes) {
+        Ok(operations) => operations,
+        Err(err) => {
+            log::error!("Invalid registry file {}: {}", file_path.display(), err);
+            return Err(err);
+        }
+    };
+    log::info!(
+        "{} operations found in {} for {}\\{} ({} view, run {}):",
+        operations.len(),
+        file_path.display(),
+        entry.hive,
+        entry.key_path,
+        entry.view,
+        entry.run_id
+    );
+    for operation in &operations {
+        log::info!("  {}", operation);
+    }
+
+    let decisions: Vec<ValueDecision> = merge_backup(backend, entry, &operations, policy);
+    for decision in &decisions {
+        if decision.is_failure() {
+            log::error!("  {}", decision);
+        } else {
+            log::info!("  {}", decision);
+        }
+    }
+    let failures: usize = decisions
+        .iter()
+        .filter(|decision| decision.is_failure())
+        .count();
+    if failures > 0 {
+        return Err(io::Error::other(format!(
+            "{} of {} values from {} failed",
+            failures,
+            decisions.len(),
+            file_path.display()
+        )));
+    }
+    log::info!("registry at {} imported successfully", file_path.display());
+    Ok(())
+}
@@ -0,0 +1,461 @@
+// +------------------+
+// |    dependencies  |
+// +------------------+
+
+// std
+use std::env;
+use std::error::Error;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+// serde
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+// internal: config
+use crate::config::Config;
+
+// internal: constants
+use crate::constants::REGISTRY_WINDOWS_VERSION_PATH;
+
+// internal: executor
+use crate::executor::Executor;
+
+// internal: registry_backend
+use crate::registry_backend::{Hive, RegValue, RegistryBackend, RegistryView};
+
+// internal: run_state
+use crate::run_state::{Phase, SetupStep, ASSISTANT_VERSION};
+
+// internal: step
+use crate::step::StepPolicy;
+
+// internal: utilities
+use crate::utilities::{boot_time_utc, is_elevated, timestamp_utc};
+
+// +------------------+
+// |      types       |
+// +------------------+
+
+/// Everything that happened in a run, written to `reports/<run-id>.json` in the data folder
+///
+/// Every launch of setup_assistant working on the run adds a session, so the launch before
+/// the reboot and the one after it end up in the same file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RunReport {
+    pub run_id: String,
+    /// Version of the assistant that started the report
+    pub assistant_version: String,
+    /// UTC time the report was started at, RFC 3339
+    pub created_at: String,
+    /// UTC time the report was last written at, RFC 3339
+    pub updated_at: String,
+    #[serde(default)]
+    pub sessions: Vec<SessionReport>,
+}
+
+/// A launch of setup_assistant carrying out steps of the run
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionReport {
+    /// Version of the assistant running the session
+    pub assistant_version: String,
+    pub started_at: String,
+    /// Not set while the session runs, or if it was cut off
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub finished_at: Option<String>,
+    /// Phase of the run when the session started
+    pub phase: Phase,
+    /// The step run by hand with `run --step`, not set for a full run
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub manual_step: Option<SetupStep>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub outcome: Option<SessionOutcome>,
+    pub environment: Environment,
+    /// The effective config, after every layer was applied
+    pub config: Value,
+    #[serde(default)]
+    pub steps: Vec<StepReport>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rollback: Option<RollbackReport>,
+}
+
+/// How a session ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SessionOutcome {
+    /// The run, or the step run by hand, is done
+    Done,
+    /// The run waits for the reboot and resumes on the next launch
+    WaitingForReboot,
+    /// A step failed and the run stopped, it is retried on the next launch
+    Stopped,
+    /// A step failed and the run was rolled back
+    RolledBack,
+}
+
+/// Facts about the machine a session ran on
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Environment {
+    /// e.g. `Windows 10 Pro 22H2`
+    pub os_name: Option<String>,
+    /// Build and update revision, e.g. `19045.3570`
+    pub os_build: Option<String>,
+    /// Architecture of the operating system, e.g. `AMD64`
+    pub os_architecture: String,
+    /// Architecture setup_assistant was built for, e.g. `x86`
+    pub process_architecture: String,
+    pub computer: Option<String>,
+    /// The user running setup_assistant, with its domain if known
+    pub user: Option<String>,
+    /// Whether setup_assistant runs elevated, `None` if it could not be checked
+    pub elevated: Option<bool>,
+    pub boot_time: Option<String>,
+}
+
+/// What a step did in a session
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StepReport {
+    pub step: SetupStep,
+    /// The policy the step failed or would have failed by
+    pub on_failure: StepPolicy,
+    pub status: StepStatus,
+    pub started_at: String,
+    pub finished_at: String,
+    pub duration_ms: u64,
+    /// The error and its causes, outermost first
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub artefacts: Vec<Artefact>,
+}
+
+/// What became of a step
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StepStatus {
+    Done,
+    /// The step waits for the reboot
+    Waiting,
+    Failed,
+}
+
+/// Something a step left on the machine
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Artefact {
+    /// A file written or moved by the step
+    File { path: PathBuf, description: String },
+    /// A registry value written by the step
+    RegistryValue {
+        hive: Hive,
+        view: RegistryView,
+        key_path: String,
+        name: String,
+        description: String,
+    },
+}
+
+/// What a failed run undid
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RollbackReport {
+    /// The steps rolled back, newest first
+    pub steps: Vec<RolledBackStep>,
+    /// Number of journaled registry changes undone
+    pub registry_changes: usize,
+    /// Why the registry changes could not be undone
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<String>,
+}
+
+/// A step whose changes were undone
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RolledBackStep {
+    pub step: SetupStep,
+    /// Why the step could not be rolled back, empty if it was
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<String>,
+}
+
+impl RunReport {
+    /// An empty report of the given run
+    pub fn new(run_id: &str) -> RunReport {
+        let now: String = timestamp_utc();
+        RunReport {
+            run_id: run_id.to_string(),
+            assistant_version: ASSISTANT_VERSION.to_string(),
+            created_at: now.clone(),
+            updated_at: now,
+            sessions: Vec::new(),
+        }
+    }
+
+    /// Read a report from disk
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path of the report file
+    ///
+    /// # Returns
+    ///
+    /// `std::io::Result<Option<RunReport>>` - The report, `None` if there is no file yet
+    pub fn load(path: &Path) -> io::Result<Option<RunReport>> {
+        let content: String = match fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err),
+        };
+        serde_json::from_str(&content)
+            .map(Some)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+
+    /// Read the report of a run, starting a new one if there is none or it is unreadable
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path of the report file
+    /// * `run_id` - Id of the run
+    ///
+    /// # Returns
+    ///
+    /// `RunReport` - The report to add the session to
+    pub fn load_or_new(path: &Path, run_id: &str) -> RunReport {
+        match RunReport::load(path) {
+            Ok(Some(report)) if report.run_id == run_id => report,
+            Ok(Some(report)) => {
+                log::error!(
+                    "report {} belongs to run {}, starting a new one",
+                    path.display(),
+                    report.run_id
+                );
+                RunReport::new(run_id)
+            }
+            Ok(None) => RunReport::new(run_id),
+            Err(err) => {
+                log::error!(
+                    "failed to read report {}, starting a new one: {}",
+                    path.display(),
+                    err
+                );
+                RunReport::new(run_id)
+            }
+        }
+    }
+
+    /// Add a session, the steps recorded from now on belong to it
+    pub fn start_session(&mut self, session: SessionReport) {
+        self.sessions.push(session);
+    }
+
+    /// Add what a step did to the current session
+    pub fn record_step(&mut self, step: StepReport) {
+        if let Some(session) = self.sessions.last_mut() {
+            session.steps.push(step);
+        }
+    }
+
+    /// Close the current session
+    ///
+    /// # Arguments
+    ///
+    /// * `outcome` - How the session ended
+    /// * `rollback` - What was undone, if the run was rolled back
+    pub fn finish_session(&mut self, outcome: SessionOutcome, rollback: Option<RollbackReport>) {
+        if let Some(session) = self.sessions.last_mut() {
+            session.finished_at = Some(timestamp_utc());
+            session.outcome = Some(outcome);
+            session.rollback = rollback;
+        }
+    }
+
+    /// The steps that failed with the `continue` policy, in every session of the run
+    pub fn continued_failures(&self) -> Vec<SetupStep> {
+        let mut steps: Vec<SetupStep> = Vec::new();
+        for report in self.sessions.iter().flat_map(|session| &session.steps) {
+            if report.status == StepStatus::Failed
+                && report.on_failure == StepPolicy::Continue
+                && !steps.contains(&report.step)
+            {
+                steps.push(report.step);
+            }
+        }
+        steps
+    }
+
+    /// Serialize the report the way it is stored on disk
+    pub fn to_json(&self) -> io::Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    /// Write the report through the executor
+    pub fn save(&mut self, executor: &dyn Executor, path: &Path) -> io::Result<()> {
+        self.updated_at = timestamp_utc();
+        executor.write_file(path, self.to_json()?.as_bytes())
+    }
+}
+
+impl SessionReport {
+    /// A session starting now
+    ///
+    /// # Arguments
+    ///
+    /// * `phase` - Phase of the run when the session starts
+    /// * `manual_step` - The step run by hand, if any
+    /// * `config` - The effective config
+    /// * `environment` - Facts about the machine
+    ///
+    /// # Returns
+    ///
+    /// `SessionReport` - The session, without steps yet
+    pub fn new(
+        phase: Phase,
+        manual_step: Option<SetupStep>,
+        config: &Config,
+        environment: Environment,
+    ) -> SessionReport {
+        SessionReport {
+            assistant_version: ASSISTANT_VERSION.to_string(),
+            started_at: timestamp_utc(),
+            finished_at: None,
+            phase,
+            manual_step,
+            outcome: None,
+            environment,
+            config: serde_json::to_value(config).unwrap_or(Value::Null),
+            steps: Vec::new(),
+            rollback: None,
+        }
+    }
+}
+
+impl Environment {
+    /// Gather the facts about the machine setup_assistant runs on
+    ///
+    /// # Arguments
+    ///
+    /// * `backend` - The registry to read the Windows version from
+    ///
+    /// # Returns
+    ///
+    /// `Environment` - The facts, the ones that are unknown are left out
+    pub fn collect(backend: &dyn RegistryBackend) -> Environment {
+        let version = |name: &str| -> Option<RegValue> {
+            backend
+                .get_value(
+                    Hive::LocalMachine,
+                    REGISTRY_WINDOWS_VERSION_PATH,
+                    RegistryView::Native,
+                    name,
+                )
+                .ok()
+        };
+        let text = |name: &str| -> Option<String> {
+            match version(name)? {
+                RegValue::String(text) => Some(text),
+                _ => None,
+            }
+        };
+
+        let os_name: Option<String> = text("ProductName").map(|product| {
+            match text("DisplayVersion").or_else(|| text("ReleaseId")) {
+                Some(release) => format!("{} {}", product, release),
+                None => product,
+            }
+        });
+        let os_build: Option<String> = text("CurrentBuild").map(|build| match version("UBR") {
+            Some(RegValue::Dword(revision)) => format!("{}.{}", build, revision),
+            _ => build,
+        });
+        // A 32-bit process on 64-bit Windows sees the real architecture in PROCESSOR_ARCHITEW6432
+        let os_architecture: String = env::var("PROCESSOR_ARCHITEW6432")
+            .or_else(|_| env::var("PROCESSOR_ARCHITECTURE"))
+            .unwrap_or_else(|_| env::consts::ARCH.to_string());
+        let user: Option<String> =
+            env::var("USERNAME")
+                .or_else(|_| env::var("USER"))
+                .ok()
+                .map(|name| match env::var("USERDOMAIN") {
+                    Ok(domain) => format!("{}\\{}", domain, name),
+                    Err(_) => name,
+                });
+
+        Environment {
+            os_name,
+            os_build,
+            os_architecture,
+            process_architecture: env::consts::ARCH.to_string(),
+            computer: env::var("COMPUTERNAME")
+                .or_else(|_| env::var("HOSTNAME"))
+                .ok(),
+            user,
+            elevated: is_elevated(),
+            boot_time: boot_time_utc(),
+        }
+    }
+}
+
+impl StepReport {
+    /// What a step did, from the result of applying and verifying it
+    ///
+    /// # Arguments
+    ///
+    /// * `step` - The step
+    /// * `on_failure` - The policy of the step
+    /// * `started_at` - UTC time the step started at
+    /// * `duration` - How long the step took
+    /// * `result` - Whether the step is done, or why it failed
+    /// * `artefacts` - What the step left on the machine
+    ///
+    /// # Returns
+    ///
+    /// `StepReport` - The report of the step
+    pub fn new(
+        step: SetupStep,
+        on_failure: StepPolicy,
+        started_at: String,
+        duration: Duration,
+        result: &io::Result<bool>,
+        artefacts: Vec<Artefact>,
+    ) -> StepReport {
+        let (status, errors): (StepStatus, Vec<String>) = match result {
+            Ok(true) => (StepStatus::Done, Vec::new()),
+            Ok(false) => (StepStatus::Waiting, Vec::new()),
+            Err(err) => (StepStatus::Failed, error_chain(err)),
+        };
+        StepReport {
+            step,
+            on_failure,
+            status,
+            started_at,
+            finished_at: timestamp_utc(),
+            duration_ms: duration.as_millis() as u64,
+            errors,
+            artefacts,
+        }
+    }
+}
+
+// +------------------+
+// | public functions |
+// +------------------+
+
+/// An error followed by its causes
+///
+/// # Arguments
+///
+/// * `error` - The outermost error
+///
+/// # Returns
+///
+/// `Vec<String>` - The messages, outermost first
+pub fn error_chain(error: &(dyn Error + 'static)) -> Vec<String> {
+    let mut chain: Vec<String> = vec![error.to_string()];
+    let mut source: Option<&(dyn Error + 'static)> = error.source();
+    while let Some(cause) = source {
+        chain.push(cause.to_string());
+        source = cause.source();
+    }
+    chain
+}
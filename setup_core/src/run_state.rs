@@ -0,0 +1,329 @@
+// +------------------+
+// |    dependencies  |
+// +------------------+
+
+// std
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+// serde
+use serde::{Deserialize, Serialize};
+
+// internal: constants
+use crate::constants::REBOOT_REGISTRY_PATH;
+
+// internal: executor
+use crate::executor::Executor;
+
+// internal: registry_backend
+use crate::registry_backend::{Hive, RegValue, RegistryBackend, RegistryView};
+
+// internal: utilities
+use crate::utilities::timestamp_utc;
+
+/// Version of the assistant, shared by every crate of the workspace and stored with the state
+pub const ASSISTANT_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+// +------------------+
+// |      types       |
+// +------------------+
+
+/// Where a run is in the setup
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Phase {
+    /// Cleaning and preparing the machine, up to the reboot
+    PreReboot,
+    /// Restoring the startup and installing after the reboot
+    PostReboot,
+    /// Every step is done
+    Finished,
+}
+
+impl fmt::Display for Phase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Phase::PreReboot => write!(f, "pre_reboot"),
+            Phase::PostReboot => write!(f, "post_reboot"),
+            Phase::Finished => write!(f, "finished"),
+        }
+    }
+}
+
+impl Phase {
+    fn from_name(name: &str) -> Option<Phase> {
+        [Phase::PreReboot, Phase::PostReboot, Phase::Finished]
+            .into_iter()
+            .find(|phase| phase.to_string() == name)
+    }
+}
+
+/// The steps of a run, in the order they are carried out
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SetupStep {
+    /// Back up and disable the startup entries and shortcuts
+    CleanStartup,
+    /// Apply the locale settings
+    ChangeLocale,
+    /// Apply the time zone, calendar and date formats
+    ChangeRegionalSettings,
+    /// Have setup_assistant started again after the reboot
+    ScheduleResume,
+    /// Request the reboot; done once the machine has rebooted
+    Reboot,
+    /// Check the locale settings again after the reboot
+    VerifyLocale,
+    /// Put the startup entries and shortcuts back with registry_restore
+    RestoreStartup,
+    /// Run the Moein setup
+    RunSetup,
+}
+
+impl SetupStep {
+    /// Every step in order
+    pub const ALL: [SetupStep; 8] = [
+        SetupStep::CleanStartup,
+        SetupStep::ChangeLocale,
+        SetupStep::ChangeRegionalSettings,
+        SetupStep::ScheduleResume,
+        SetupStep::Reboot,
+        SetupStep::VerifyLocale,
+        SetupStep::RestoreStartup,
+        SetupStep::RunSetup,
+    ];
+
+    /// The phase the step belongs to
+    pub fn phase(&self) -> Phase {
+        match self {
+            SetupStep::CleanStartup
+            | SetupStep::ChangeLocale
+            | SetupStep::ChangeRegionalSettings
+            | SetupStep::ScheduleResume
+            | SetupStep::Reboot => Phase::PreReboot,
+            SetupStep::VerifyLocale | SetupStep::RestoreStartup | SetupStep::RunSetup => {
+                Phase::PostReboot
+            }
+        }
+    }
+
+    /// Name of the step as stored in the state
+    pub fn name(&self) -> &'static str {
+        match self {
+            SetupStep::CleanStartup => "clean_startup",
+            SetupStep::ChangeLocale => "change_locale",
+            SetupStep::ChangeRegionalSettings => "change_regional_settings",
+            SetupStep::ScheduleResume => "schedule_resume",
+            SetupStep::Reboot => "reboot",
+            SetupStep::VerifyLocale => "verify_locale",
+            SetupStep::RestoreStartup => "restore_startup",
+            SetupStep::RunSetup => "run_setup",
+        }
+    }
+
+    /// Parse a step from its name, e.g. `clean_startup`
+    pub fn from_name(name: &str) -> Option<SetupStep> {
+        SetupStep::ALL.into_iter().find(|step| step.name() == name)
+    }
+}
+
+impl fmt::Display for SetupStep {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// A step that is done
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CompletedStep {
+    pub step: SetupStep,
+    pub completed_at: String,
+}
+
+/// Progress of a run, kept across launches and the reboot
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RunState {
+    pub run_id: String,
+    pub assistant_version: String,
+    pub phase: Phase,
+    pub started_at: String,
+    pub updated_at: String,
+    /// When the reboot was requested, to tell whether the machine has rebooted since
+    #[serde(default)]
+    pub reboot_requested_at: Option<String>,
+    #[serde(default)]
+    pub completed_steps: Vec<CompletedStep>,
+}
+
+impl RunState {
+    /// Start the state of a new run
+    pub fn new(run_id: &str) -> RunState {
+        let now: String = timestamp_utc();
+        RunState {
+            run_id: run_id.to_string(),
+            assistant_version: ASSISTANT_VERSION.to_string(),
+            phase: Phase::PreReboot,
+            started_at: now.clone(),
+            updated_at: now,
+            reboot_requested_at: None,
+            completed_steps: Vec::new(),
+        }
+    }
+
+    /// Whether a step is done
+    pub fn is_completed(&self, step: SetupStep) -> bool {
+        self.completed_steps
+            .iter()
+            .any(|completed| completed.step == step)
+    }
+
+    /// The first step that is not done, `None` if the run is finished
+    ///
+    /// # Arguments
+    ///
+    /// * `order` - The steps of the run, in order
+    pub fn next_step(&self, order: &[SetupStep]) -> Option<SetupStep> {
+        order.iter().copied().find(|step| !self.is_completed(*step))
+    }
+
+    /// Mark a step as done and move the phase along
+    ///
+    /// # Arguments
+    ///
+    /// * `step` - The step that is done
+    /// * `order` - The steps of the run, in order
+    pub fn complete(&mut self, step: SetupStep, order: &[SetupStep]) {
+        if !self.is_completed(step) {
+            self.completed_steps.push(CompletedStep {
+                step,
+                completed_at: timestamp_utc(),
+            });
+        }
+        self.phase = match self.next_step(order) {
+            Some(next) => next.phase(),
+            None => Phase::Finished,
+        };
+    }
+
+    /// Load the state of the current run
+    ///
+    /// The state file is read first; if it is missing the registry copy is used.
+    ///
+    /// # Arguments
+    ///
+    /// * `backend` - The registry backend holding the registry copy
+    /// * `state_path` - Path of the state file
+    ///
+    /// # Returns
+    ///
+    /// `std::io::Result<Option<RunState>>` - The state, or `None` if no run was started
+    pub fn load(backend: &dyn RegistryBackend, state_path: &Path) -> io::Result<Option<RunState>> {
+        match fs::read_to_string(state_path) {
+            Ok(content) => {
+                return serde_json::from_str(&content)
+                    .map(Some)
+                    .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err));
+            }
+            Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err),
+            Err(_) => {}
+        }
+        Self::load_from_registry(backend)
+    }
+
+    /// Persist the state to the state file and the registry
+    ///
+    /// # Arguments
+    ///
+    /// * `executor` - The executor writing the state
+    /// * `state_path` - Path of the state file
+    ///
+    /// # Returns
+    ///
+    /// `std::io::Result<()>` - Whether both copies were written
+    pub fn save(&mut self, executor: &dyn Executor, state_path: &Path) -> io::Result<()> {
+        self.updated_at = timestamp_utc();
+        let content: String = serde_json::to_string_pretty(self)?;
+        executor.write_file(state_path, content.as_bytes())?;
+
+        let backend: &dyn RegistryBackend = executor.registry();
+        let (hive, view) = (Hive::LocalMachine, RegistryView::X64);
+        backend.create_key(hive, REBOOT_REGISTRY_PATH, view)?;
+        let completed: Vec<String> = self
+            .completed_steps
+            .iter()
+            .map(|completed| format!("{}={}", completed.step, completed.completed_at))
+            .collect();
+        let values: [(&str, RegValue); 7] = [
+            ("run_id", RegValue::String(self.run_id.clone())),
+            (
+                "assistant_version",
+                RegValue::String(self.assistant_version.clone()),
+            ),
+            ("phase", RegValue::String(self.phase.to_string())),
+            ("started_at", RegValue::String(self.started_at.clone())),
+            ("updated_at", RegValue::String(self.updated_at.clone())),
+            (
+                "reboot_requested_at",
+                RegValue::String(self.reboot_requested_at.clone().unwrap_or_default()),
+            ),
+            ("completed_steps", RegValue::MultiString(completed)),
+        ];
+        for (name, value) in values {
+            backend.set_value(hive, REBOOT_REGISTRY_PATH, view, name, &value)?;
+        }
+        Ok(())
+    }
+
+    /// Read the registry copy of the state
+    fn load_from_registry(backend: &dyn RegistryBackend) -> io::Result<Option<RunState>> {
+        let read = |name: &str| -> io::Result<Option<RegValue>> {
+            match backend.get_value(
+                Hive::LocalMachine,
+                REBOOT_REGISTRY_PATH,
+                RegistryView::X64,
+                name,
+            ) {
+                Ok(value) => Ok(Some(value)),
+                Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+                Err(err) => Err(err),
+            }
+        };
+        let read_string = |name: &str| -> io::Result<String> {
+            match read(name)? {
+                Some(RegValue::String(value)) => Ok(value),
+                _ => Ok(String::new()),
+            }
+        };
+
+        let run_id: String = read_string("run_id")?;
+        if run_id.is_empty() {
+            return Ok(None);
+        }
+        let phase: String = read_string("phase")?;
+        let completed_steps: Vec<CompletedStep> = match read("completed_steps")? {
+            Some(RegValue::MultiString(lines)) => lines
+                .iter()
+                .filter_map(|line| {
+                    let (name, completed_at) = line.split_once('=')?;
+                    Some(CompletedStep {
+                        step: SetupStep::from_name(name)?,
+                        completed_at: completed_at.to_string(),
+                    })
+                })
+                .collect(),
+            _ => Vec::new(),
+        };
+        let reboot_requested_at: String = read_string("reboot_requested_at")?;
+        Ok(Some(RunState {
+            run_id,
+            assistant_version: read_string("assistant_version")?,
+            phase: Phase::from_name(&phase).unwrap_or(Phase::PreReboot),
+            started_at: read_string("started_at")?,
+            updated_at: read_string("updated_at")?,
+            reboot_requested_at: Some(reboot_requested_at).filter(|value| !value.is_empty()),
+            completed_steps,
+        }))
+    }
+}
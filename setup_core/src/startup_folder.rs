@@ -0,0 +1,352 @@
+// +------------------+
+// |    dependencies  |
+// +------------------+
+
+// std
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+// serde
+use serde::{Deserialize, Serialize};
+
+// +------------------+
+// |      types       |
+// +------------------+
+
+/// Whose startup folder a shortcut belongs to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StartupFolderScope {
+    /// `%APPDATA%\Microsoft\Windows\Start Menu\Programs\Startup`
+    User,
+    /// `%PROGRAMDATA%\Microsoft\Windows\Start Menu\Programs\StartUp`
+    Common,
+}
+
+impl StartupFolderScope {
+    fn folder_name(&self) -> &'static str {
+        match self {
+            StartupFolderScope::User => "user",
+            StartupFolderScope::Common => "common",
+        }
+    }
+}
+
+/// A startup folder on disk
+#[derive(Debug, Clone)]
+pub struct StartupFolder {
+    pub scope: StartupFolderScope,
+    pub path: PathBuf,
+}
+
+/// A shortcut moved out of a startup folder
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MovedShortcut {
+    /// Id of the run that moved the shortcut, empty in manifests written before it was recorded
+    #[serde(default)]
+    pub run_id: String,
+    pub scope: StartupFolderScope,
+    pub original_path: PathBuf,
+    pub backup_path: PathBuf,
+}
+
+// +------------------+
+// | public functions |
+// +------------------+
+
+/// Locate the per-user and all-users startup folders from the environment
+///
+/// # Returns
+///
+/// `Vec<StartupFolder>` - The startup folders whose base directory is known
+pub fn startup_folders() -> Vec<StartupFolder> {
+    let mut folders: Vec<StartupFolder> = Vec::new();
+    let locations = [
+        (StartupFolderScope::User, "APPDATA", "Startup"),
+        (StartupFolderScope::Common, "PROGRAMDATA", "StartUp"),
+    ];
+    for (scope, variable, folder_name) in locations {
+        match env::var_os(variable) {
+            Some(base) => folders.push(StartupFolder {
+                scope,
+                path: PathBuf::from(base)
+                    .join("Microsoft")
+                    .join("Windows")
+                    .join("Start Menu")
+                    .join("Programs")
+                    .join(folder_name),
+            }),
+            None => log::error!(
+                "{} is not set, skipping {:?} startup folder",
+                variable,
+                scope
+            ),
+        }
+    }
+    folders
+}
+
+/// Find the shortcuts to be moved out of the startup folders and where they are moved to
+///
+/// Only `.lnk` files for which `should_move` returns `true` are picked. Their backup path is
+/// under `<backup_root>/<run_id>/<scope>/`, so backups of earlier runs that were not restored
+/// are not overwritten; the caller moves them and records the moved ones in the backup manifest.
+///
+/// # Arguments
+///
+/// * `folders` - The startup folders to be processed
+/// * `backup_root` - The folder the shortcuts are moved to
+/// * `run_id` - Id of the current run, recorded with the shortcuts
+/// * `should_move` - Decides for each shortcut path whether it is moved
+///
+/// # Returns
+///
+/// `Vec<MovedShortcut>` - The shortcuts to be moved
+pub fn find_startup_shortcuts(
+    folders: &[StartupFolder],
+    backup_root: &Path,
+    run_id: &str,
+    should_move: &dyn Fn(&Path) -> bool,
+) -> Vec<MovedShortcut> {
+    let mut shortcuts: Vec<MovedShortcut> = Vec::new();
+    for folder in folders {
+        let entries = match fs::read_dir(&folder.path) {
+            Ok(entries) => entries,
+            Err(err) => {
+                log::info!(
+                    "Startup folder {} can not be read, skipping: {}",
+                    folder.path.display(),
+                    err
+                );
+                continue;
+            }
+        };
+        let backup_folder: PathBuf = backup_root.join(run_id).join(folder.scope.folder_name());
+        for entry in entries.flatten() {
+            let path: PathBuf = entry.path();
+            let is_shortcut: bool = path
+                .extension()
+                .is_some_and(|extension| extension.eq_ignore_ascii_case("lnk"));
+            if !is_shortcut || !path.is_file() {
+                continue;
+            }
+            if !should_move(&path) {
+                log::info!("Keeping startup shortcut {}", path.display());
+                continue;
+            }
+            shortcuts.push(MovedShortcut {
+                run_id: run_id.to_string(),
+                scope: folder.scope,
+                backup_path: backup_folder.join(entry.file_name()),
+                original_path: path,
+            });
+        }
+    }
+    shortcuts
+}
+
+/// Move shortcuts recorded in the backup manifest back to their startup folders
+///
+/// Shortcuts whose original path is taken again are left in the backup folder, as are the ones
+/// that fail to move; both are returned so they stay in the manifest.
+///
+/// # Arguments
+///
+/// * `shortcuts` - The moved shortcuts to be restored
+///
+/// # Returns
+///
+/// `Vec<MovedShortcut>` - The shortcuts that were not restored
+pub fn restore_startup_shortcuts(shortcuts: &[MovedShortcut]) -> Vec<MovedShortcut> {
+    let mut remaining: Vec<MovedShortcut> = Vec::new();
+    for shortcut in shortcuts {
+        if shortcut.original_path.exists() {
+            log::error!(
+                "Startup shortcut {} exists again, keeping the backup at {}",
+                shortcut.original_path.display(),
+                shortcut.backup_path.display()
+            );
+            remaining.push(shortcut.clone());
+            continue;
+        }
+        let result: io::Result<()> = match shortcut.original_path.parent() {
+            Some(parent) => fs::create_dir_all(parent)
+                .and_then(|_| move_file(&shortcut.backup_path, &shortcut.original_path)),
+            None => move_file(&shortcut.backup_path, &shortcut.original_path),
+        };
+        match result {
+            Ok(_) => log::info!(
+                "Startup shortcut {} restored",
+                shortcut.original_path.display()
+            ),
+            Err(err) => {
+                log::error!(
+                    "Failed to restore startup shortcut {}: {}",
+                    shortcut.original_path.display(),
+                    err
+                );
+                remaining.push(shortcut.clone());
+            }
+        }
+    }
+    remaining
+}
+
+// +-----------------------+
+// |  private functions    |
+// +-----------------------+
+
+/// Rename a file, falling back to copy and delete when crossing volumes
+fn move_file(from: &Path, to: &Path) -> io::Result<()> {
+    if fs::rename(from, to).is_ok() {
+        return Ok(());
+    }
+    fs::copy(from, to)?;
+    fs::remove_file(from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempFolder;
+
+    fn startup_folder(root: &Path, scope: StartupFolderScope) -> StartupFolder {
+        let path: PathBuf = root.join(scope.folder_name()).join("Startup");
+        fs::create_dir_all(&path).unwrap();
+        StartupFolder { scope, path }
+    }
+
+    fn file_names(shortcuts: &[MovedShortcut]) -> Vec<String> {
+        let mut names: Vec<String> = shortcuts
+            .iter()
+            .map(|shortcut| {
+                shortcut
+                    .original_path
+                    .file_name()
+                    .unwrap()
+                    .to_string_lossy()
+                    .into_owned()
+            })
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn only_lnk_files_matching_the_predicate_are_picked() {
+        let temp = TempFolder::new("startup_folder_find");
+        let user = startup_folder(&temp.0, StartupFolderScope::User);
+        let common = startup_folder(&temp.0, StartupFolderScope::Common);
+        for name in ["Moein.lnk", "Other.LNK", "Keep.lnk", "notes.txt", "lnk"] {
+            fs::write(user.path.join(name), b"").unwrap();
+        }
+        fs::create_dir(user.path.join("Folder.lnk")).unwrap();
+        fs::write(common.path.join("Common.lnk"), b"").unwrap();
+        let missing = StartupFolder {
+            scope: StartupFolderScope::User,
+            path: temp.0.join("missing"),
+        };
+
+        let backup_root: PathBuf = temp.0.join("backup");
+        let shortcuts = find_startup_shortcuts(
+            &[user.clone(), common.clone(), missing],
+            &backup_root,
+            "run",
+            &|path: &Path| path.file_stem().is_some_and(|stem| stem != "Keep"),
+        );
+
+        assert_eq!(
+            file_names(&shortcuts),
+            vec!["Common.lnk", "Moein.lnk", "Other.LNK"]
+        );
+        let common_shortcut = shortcuts
+            .iter()
+            .find(|shortcut| shortcut.scope == StartupFolderScope::Common)
+            .unwrap();
+        assert_eq!(common_shortcut.run_id, "run");
+        assert_eq!(
+            common_shortcut.original_path,
+            common.path.join("Common.lnk")
+        );
+        assert_eq!(
+            common_shortcut.backup_path,
+            backup_root.join("run").join("common").join("Common.lnk")
+        );
+        // Finding the shortcuts does not move them
+        assert!(user.path.join("Moein.lnk").is_file());
+    }
+
+    #[test]
+    fn runs_back_up_shortcuts_of_the_same_name_to_different_files() {
+        let temp = TempFolder::new("startup_folder_runs");
+        let user = startup_folder(&temp.0, StartupFolderScope::User);
+        fs::write(user.path.join("Moein.lnk"), b"").unwrap();
+        let backup_root: PathBuf = temp.0.join("backup");
+
+        let first =
+            find_startup_shortcuts(std::slice::from_ref(&user), &backup_root, "first", &|_| {
+                true
+            });
+        let second = find_startup_shortcuts(&[user], &backup_root, "second", &|_| true);
+
+        assert_eq!(first.len(), 1);
+        assert_eq!(second.len(), 1);
+        assert_eq!(first[0].original_path, second[0].original_path);
+        assert_ne!(first[0].backup_path, second[0].backup_path);
+    }
+
+    #[test]
+    fn backed_up_shortcuts_are_moved_back() {
+        let temp = TempFolder::new("startup_folder_restore");
+        let shortcut = MovedShortcut {
+            run_id: "run".to_string(),
+            scope: StartupFolderScope::User,
+            original_path: temp.0.join("Startup").join("Moein.lnk"),
+            backup_path: temp.0.join("backup").join("Moein.lnk"),
+        };
+        fs::create_dir_all(shortcut.backup_path.parent().unwrap()).unwrap();
+        fs::write(&shortcut.backup_path, b"shortcut").unwrap();
+
+        let remaining = restore_startup_shortcuts(std::slice::from_ref(&shortcut));
+
+        assert!(remaining.is_empty());
+        assert_eq!(fs::read(&shortcut.original_path).unwrap(), b"shortcut");
+        assert!(!shortcut.backup_path.exists());
+    }
+
+    #[test]
+    fn shortcuts_that_can_not_be_moved_back_remain() {
+        let temp = TempFolder::new("startup_folder_remaining");
+        let startup: PathBuf = temp.0.join("Startup");
+        let backup: PathBuf = temp.0.join("backup");
+        fs::create_dir_all(&startup).unwrap();
+        fs::create_dir_all(&backup).unwrap();
+
+        // The original path is taken again, the backup is kept
+        let taken = MovedShortcut {
+            run_id: "run".to_string(),
+            scope: StartupFolderScope::User,
+            original_path: startup.join("Taken.lnk"),
+            backup_path: backup.join("Taken.lnk"),
+        };
+        fs::write(&taken.original_path, b"new").unwrap();
+        fs::write(&taken.backup_path, b"old").unwrap();
+
+        // The backup is gone, moving it fails
+        let lost = MovedShortcut {
+            run_id: "run".to_string(),
+            scope: StartupFolderScope::Common,
+            original_path: startup.join("Lost.lnk"),
+            backup_path: backup.join("Lost.lnk"),
+        };
+
+        let remaining = restore_startup_shortcuts(&[taken.clone(), lost.clone()]);
+
+        assert_eq!(remaining, vec![taken.clone(), lost.clone()]);
+        assert_eq!(fs::read(&taken.original_path).unwrap(), b"new");
+        assert_eq!(fs::read(&taken.backup_path).unwrap(), b"old");
+        assert!(!lost.original_path.exists());
+    }
+}
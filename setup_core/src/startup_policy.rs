@@ -0,0 +1,174 @@
+// serde
+use serde::{Deserialize, Serialize};
+
+// internal: utilities
+use crate::utilities::glob_match;
+
+/// How disabled startup entries are taken out of the startup
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StartupCleanMode {
+    /// Back up the `Run` values and delete them
+    #[default]
+    Delete,
+    /// Keep the `Run` values and flag them as disabled in `Explorer\StartupApproved`
+    StartupApproved,
+}
+
+/// What to do with a startup entry
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StartupAction {
+    /// Leave the entry untouched
+    Keep,
+    /// Back up and remove the entry
+    #[default]
+    Disable,
+}
+
+/// A rule matching startup entries by value name and/or command
+///
+/// Patterns are case-insensitive globs (`*` and `?`); a pattern without wildcards is an exact
+/// match. When both patterns are given, both have to match.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+#[serde(default)]
+pub struct StartupRule {
+    pub name: Option<String>,
+    pub command: Option<String>,
+}
+
+impl StartupRule {
+    fn matches(&self, name: &str, command: &str) -> bool {
+        if self.name.is_none() && self.command.is_none() {
+            return false;
+        }
+        self.name
+            .as_ref()
+            .is_none_or(|pattern| glob_match(pattern, name))
+            && self
+                .command
+                .as_ref()
+                .is_none_or(|pattern| glob_match(pattern, command))
+    }
+}
+
+/// Policy deciding which startup entries are kept and which are disabled
+///
+/// `keep` rules take precedence over `disable` rules; entries matching neither get the
+/// `default_action`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+#[serde(default)]
+pub struct StartupPolicy {
+    pub default_action: StartupAction,
+    pub keep: Vec<StartupRule>,
+    pub disable: Vec<StartupRule>,
+}
+
+impl StartupPolicy {
+    /// Decide what to do with a startup entry
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the registry value
+    /// * `command` - The command line stored in the value
+    ///
+    /// # Returns
+    ///
+    /// `StartupAction` - Whether the entry is kept or disabled
+    pub fn decide(&self, name: &str, command: &str) -> StartupAction {
+        if self.keep.iter().any(|rule| rule.matches(name, command)) {
+            StartupAction::Keep
+        } else if self.disable.iter().any(|rule| rule.matches(name, command)) {
+            StartupAction::Disable
+        } else {
+            self.default_action
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(name: Option<&str>, command: Option<&str>) -> StartupRule {
+        StartupRule {
+            name: name.map(str::to_string),
+            command: command.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn rules_match_by_name_or_by_command() {
+        let policy = StartupPolicy {
+            default_action: StartupAction::Disable,
+            keep: vec![
+                rule(Some("SecurityHealth"), None),
+                rule(None, Some("*\\antivirus\\*")),
+            ],
+            disable: Vec::new(),
+        };
+
+        assert_eq!(
+            policy.decide("securityhealth", "C:\\Windows\\health.exe"),
+            StartupAction::Keep
+        );
+        assert_eq!(
+            policy.decide("Guard", "C:\\Program Files\\Antivirus\\guard.exe"),
+            StartupAction::Keep
+        );
+        assert_eq!(
+            policy.decide("OneDrive", "C:\\OneDrive\\OneDrive.exe"),
+            StartupAction::Disable
+        );
+    }
+
+    #[test]
+    fn rules_with_both_patterns_need_both_to_match() {
+        let policy = StartupPolicy {
+            default_action: StartupAction::Disable,
+            keep: vec![rule(Some("Updater"), Some("*\\vendor\\*"))],
+            disable: Vec::new(),
+        };
+
+        assert_eq!(
+            policy.decide("Updater", "C:\\Vendor\\update.exe"),
+            StartupAction::Keep
+        );
+        assert_eq!(
+            policy.decide("Updater", "C:\\Other\\update.exe"),
+            StartupAction::Disable
+        );
+        // A rule without patterns matches nothing
+        let empty = StartupPolicy {
+            default_action: StartupAction::Disable,
+            keep: vec![rule(None, None)],
+            disable: Vec::new(),
+        };
+        assert_eq!(empty.decide("App", "app.exe"), StartupAction::Disable);
+    }
+
+    #[test]
+    fn keep_wins_and_unmatched_entries_get_the_default_action() {
+        let policy = StartupPolicy {
+            default_action: StartupAction::Keep,
+            keep: vec![rule(Some("Teams*"), None)],
+            disable: vec![rule(Some("*"), Some("*.bat"))],
+        };
+
+        assert_eq!(
+            policy.decide("TeamsUpdate", "update.bat"),
+            StartupAction::Keep
+        );
+        assert_eq!(
+            policy.decide("Cleanup", "cleanup.bat"),
+            StartupAction::Disable
+        );
+        assert_eq!(policy.decide("Cleanup", "cleanup.exe"), StartupAction::Keep);
+        assert_eq!(
+            StartupPolicy::default().decide("Cleanup", "cleanup.exe"),
+            StartupAction::Disable
+        );
+    }
+}
@@ -0,0 +1,198 @@
+// +------------------+
+// |    dependencies  |
+// +------------------+
+
+// std
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::path::Path;
+
+// serde
+use serde::{Deserialize, Serialize};
+
+// internal: config
+use crate::config::Config;
+
+// internal: executor
+use crate::executor::Executor;
+
+// internal: paths
+use crate::paths::AppPaths;
+
+// internal: run_report
+use crate::run_report::Artefact;
+
+// internal: run_state
+use crate::run_state::{RunState, SetupStep};
+
+// +------------------+
+// |      types       |
+// +------------------+
+
+/// What the runner does when a step fails
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StepPolicy {
+    /// Log the error, mark the step as done and carry on with the next step
+    Continue,
+    /// Stop the run, the step is retried on the next launch
+    Abort,
+    /// Stop the run and undo the steps done so far and the registry changes of the run
+    AbortAndRollback,
+}
+
+impl fmt::Display for StepPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            StepPolicy::Continue => "continue",
+            StepPolicy::Abort => "abort",
+            StepPolicy::AbortAndRollback => "abort_and_rollback",
+        })
+    }
+}
+
+/// A step selected in the config, in the order the steps run
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct StepSettings {
+    pub step: SetupStep,
+    /// What to do when the step fails, see `default_policy` if not set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub on_failure: Option<StepPolicy>,
+}
+
+impl StepSettings {
+    /// The configured policy, or the default one of the step
+    pub fn policy(&self) -> StepPolicy {
+        self.on_failure.unwrap_or_else(|| default_policy(self.step))
+    }
+}
+
+/// Everything a step works with
+pub struct StepContext<'a> {
+    pub config: &'a Config,
+    /// The executor carrying out the changes, or recording them for a plan
+    pub executor: &'a dyn Executor,
+    pub paths: &'a AppPaths,
+    /// Path of the setup_assistant executable
+    pub executable_path: &'a Path,
+}
+
+impl StepContext<'_> {
+    /// The policy of a step, the default one if the step is not in the config
+    pub fn policy(&self, step: SetupStep) -> StepPolicy {
+        self.config
+            .step_settings(step)
+            .map(StepSettings::policy)
+            .unwrap_or_else(|| default_policy(step))
+    }
+}
+
+/// A step of the setup run
+///
+/// The runner calls `apply` and, once the step is done, `verify`. `rollback` undoes what the
+/// step changed when a later step fails with `AbortAndRollback`; registry changes are undone
+/// through the journal afterwards and need no rollback of their own.
+pub trait Step {
+    /// The step as stored in the run state
+    fn step(&self) -> SetupStep;
+
+    /// Name of the step, e.g. `clean_startup`
+    fn name(&self) -> &'static str {
+        self.step().name()
+    }
+
+    /// Describe what `apply` would do with the config, in one line
+    fn plan(&self, context: &StepContext) -> String;
+
+    /// Carry out the step
+    ///
+    /// # Returns
+    ///
+    /// `std::io::Result<bool>` - Whether the step is done, `false` while it waits for the reboot
+    fn apply(&self, context: &StepContext, state: &mut RunState) -> io::Result<bool>;
+
+    /// Check that the step took effect
+    fn verify(&self, _context: &StepContext) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Undo what the step changed outside the registry
+    fn rollback(&self, _context: &StepContext, _state: &RunState) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// What the step left on the machine, for the run report
+    fn artefacts(&self, _context: &StepContext, _state: &RunState) -> Vec<Artefact> {
+        Vec::new()
+    }
+}
+
+/// A step that failed and the policy the run stopped by
+#[derive(Debug)]
+pub struct StepFailure {
+    pub step: SetupStep,
+    pub policy: StepPolicy,
+    pub error: io::Error,
+}
+
+impl fmt::Display for StepFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "step {} failed: {}", self.step, self.error)
+    }
+}
+
+impl Error for StepFailure {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+impl From<StepFailure> for io::Error {
+    fn from(failure: StepFailure) -> Self {
+        io::Error::new(failure.error.kind(), failure.to_string())
+    }
+}
+
+// +------------------+
+// | public functions |
+// +------------------+
+
+/// The steps a run carries out when the config does not list them
+///
+/// # Returns
+///
+/// `Vec<StepSettings>` - Every step in the order of `SetupStep::ALL`, with its default policy
+pub fn default_steps() -> Vec<StepSettings> {
+    SetupStep::ALL
+        .into_iter()
+        .map(|step| StepSettings {
+            step,
+            on_failure: None,
+        })
+        .collect()
+}
+
+/// What the runner does when a step fails and the config sets no policy
+///
+/// Before the reboot a failure puts the machine back as it was, except for the locale which is
+/// only logged; after the reboot the run stops and the step is retried on the next launch.
+///
+/// # Arguments
+///
+/// * `step` - The step
+///
+/// # Returns
+///
+/// `StepPolicy` - The default policy of the step
+pub fn default_policy(step: SetupStep) -> StepPolicy {
+    match step {
+        SetupStep::ChangeLocale | SetupStep::VerifyLocale => StepPolicy::Continue,
+        SetupStep::CleanStartup
+        | SetupStep::ChangeRegionalSettings
+        | SetupStep::ScheduleResume
+        | SetupStep::Reboot => StepPolicy::AbortAndRollback,
+        SetupStep::RestoreStartup | SetupStep::RunSetup => StepPolicy::Abort,
+    }
+}
@@ -0,0 +1,1339 @@
+// +------------------+
+// |    dependencies  |
+// +------------------+
+
+// std
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+// internal: backup_manifest
+use crate::backup_manifest::BackupManifest;
+
+// internal: config
+use crate::config::Config;
+
+// internal: constants
+use crate::constants::{
+    CONTROL_PANEL_PROGRAM, REBOOTED_KEY_NAME, REBOOT_REGISTRY_PATH, REGISTRY_RUNONCE_PATH,
+    RESUME_TASK_NAME,
+};
+
+// internal: executor
+use crate::executor::Executor;
+
+// internal: exit_code
+use crate::exit_code::ExitStatus;
+
+// internal: locale
+use crate::locale::{LocaleSettings, LocaleVerification};
+use crate::locale_verify::{verify_locale, LocaleMismatch};
+
+// internal: paths
+use crate::paths::AppPaths;
+
+// internal: regional
+use crate::regional::{apply_regional_settings, revert_regional_settings};
+
+// internal: registry_handler
+use crate::registry_handler::{
+    export_and_delete_startup_registry_keys, schedule_resume_task, set_rebooted_key,
+};
+
+// internal: registry_backend
+use crate::registry_backend::{Hive, RegistryView};
+
+// internal: run_report
+use crate::run_report::{error_chain, Artefact, RolledBackStep, RunReport, StepReport};
+
+// internal: run_state
+use crate::run_state::{RunState, SetupStep};
+
+// internal: startup_folder
+use crate::startup_folder::{find_startup_shortcuts, startup_folders, MovedShortcut};
+
+// internal: startup_policy
+use crate::startup_policy::StartupAction;
+
+// internal: step
+use crate::step::{Step, StepContext, StepFailure, StepPolicy};
+
+// internal: utilities
+use crate::utilities::{boot_time_utc, timestamp_utc};
+
+// internal: xml_handler
+use crate::xml_handler::{control_panel_command_line, LocaleXml};
+
+// +------------------+
+// |      types       |
+// +------------------+
+
+/// Backs up and disables the startup entries and shortcuts
+struct CleanStartupStep;
+
+impl Step for CleanStartupStep {
+    fn step(&self) -> SetupStep {
+        SetupStep::CleanStartup
+    }
+
+    fn plan(&self, context: &StepContext) -> String {
+        if !context.config.clean_startup_apps {
+            return "skipped, clean_startup_apps is off".to_string();
+        }
+        format!(
+            "back up and disable the startup entries of {} registry locations and the startup folders",
+            context.config.autostart_locations.len()
+        )
+    }
+
+    fn apply(&self, context: &StepContext, state: &mut RunState) -> io::Result<bool> {
+        clean_startup(
+            context.config,
+            context.executor,
+            &state.run_id,
+            context.paths,
+        )?;
+        Ok(true)
+    }
+
+    fn rollback(&self, context: &StepContext, state: &RunState) -> io::Result<()> {
+        move_shortcuts_back(context, Some(&state.run_id))
+    }
+
+    fn artefacts(&self, context: &StepContext, state: &RunState) -> Vec<Artefact> {
+        let manifest_path: PathBuf = context.paths.backup_manifest();
+        let manifest: BackupManifest = match BackupManifest::load(&manifest_path) {
+            Ok(Some(manifest)) => manifest,
+            _ => return Vec::new(),
+        };
+        let folder: &Path = manifest_path.parent().unwrap_or(Path::new(""));
+        let mut artefacts: Vec<Artefact> = vec![Artefact::File {
+            path: manifest_path.clone(),
+            description: "backup manifest".to_string(),
+        }];
+        artefacts.extend(
+            manifest
+                .entries
+                .iter()
+                .filter(|entry| entry.run_id == state.run_id)
+                .map(|entry| Artefact::File {
+                    path: folder.join(&entry.file),
+                    description: format!(
+                        "backup of {}\\{} ({} view)",
+                        entry.hive, entry.key_path, entry.view
+                    ),
+                }),
+        );
+        artefacts.extend(
+            manifest
+                .startup_shortcuts
+                .iter()
+                .filter(|shortcut| shortcut.run_id == state.run_id)
+                .map(|shortcut| Artefact::File {
+                    path: shortcut.backup_path.clone(),
+                    description: format!(
+                        "startup shortcut moved from {}",
+                        shortcut.original_path.display()
+                    ),
+                }),
+        );
+        artefacts
+    }
+}
+
+/// Applies the locale settings with `control.exe`
+struct ChangeLocaleStep;
+
+impl Step for ChangeLocaleStep {
+    fn step(&self) -> SetupStep {
+        SetupStep::ChangeLocale
+    }
+
+    fn plan(&self, context: &StepContext) -> String {
+        if !context.config.change_locale {
+            return "skipped, change_locale is off".to_string();
+        }
+        let locale: &LocaleSettings = &context.config.locale;
+        format!(
+            "apply display language {}, user locale {}, system locale {} and keyboards {}",
+            locale.mui_language,
+            locale.user_locale,
+            locale.system_locale,
+            locale.input_languages.join(", ")
+        )
+    }
+
+    fn apply(&self, context: &StepContext, _state: &mut RunState) -> io::Result<bool> {
+        change_locale(context.config, context.executor)?;
+        Ok(true)
+    }
+
+    fn verify(&self, context: &StepContext) -> io::Result<()> {
+        if !context.config.change_locale {
+            return Ok(());
+        }
+        check_locale(&context.config.locale, context.executor)
+    }
+}
+
+/// Applies the time zone, calendar and date formats
+struct ChangeRegionalSettingsStep;
+
+impl Step for ChangeRegionalSettingsStep {
+    fn step(&self) -> SetupStep {
+        SetupStep::ChangeRegionalSettings
+    }
+
+    fn plan(&self, context: &StepContext) -> String {
+        if !context.config.change_regional_settings {
+            return "skipped, change_regional_settings is off".to_string();
+        }
+        let mut changes: Vec<String> = context
+            .config
+            .regional
+            .registry_values()
+            .into_iter()
+            .map(|(_, name, data)| format!("{}={}", name, data))
+            .collect();
+        if let Some(time_zone) = &context.config.regional.time_zone {
+            changes.push(format!("time zone {}", time_zone));
+        }
+        format!("snapshot and set {}", changes.join(", "))
+    }
+
+    fn apply(&self, context: &StepContext, state: &mut RunState) -> io::Result<bool> {
+        change_regional_settings(
+            context.config,
+            context.executor,
+            &state.run_id,
+            context.paths,
+        )?;
+        Ok(true)
+    }
+
+    fn rollback(&self, context: &StepContext, state: &RunState) -> io::Result<()> {
+        revert_regional_settings(
+            context.executor,
+            &context.paths.regional_snapshot(),
+            Some(&state.run_id),
+        )
+        .map(|_| ())
+    }
+
+    fn artefacts(&self, context: &StepContext, _state: &RunState) -> Vec<Artefact> {
+        let snapshot_path: PathBuf = context.paths.regional_snapshot();
+        if !snapshot_path.exists() {
+            return Vec::new();
+        }
+        vec![Artefact::File {
+            path: snapshot_path,
+            description: "snapshot of the regional settings before the run".to_string(),
+        }]
+    }
+}
+
+/// Has setup_assistant started again after the reboot
+struct ScheduleResumeStep;
+
+impl Step for ScheduleResumeStep {
+    fn step(&self) -> SetupStep {
+        SetupStep::ScheduleResume
+    }
+
+    fn plan(&self, context: &StepContext) -> String {
+        format!(
+            "start {} once after the reboot",
+            context.executable_path.display()
+        )
+    }
+
+    fn apply(&self, context: &StepContext, _state: &mut RunState) -> io::Result<bool> {
+        schedule_resume_task(context.executor.registry(), context.executable_path)
+            .map_err(|err| io::Error::other(format!("failed to schedule resume task: {}", err)))?;
+        Ok(true)
+    }
+
+    fn artefacts(&self, context: &StepContext, _state: &RunState) -> Vec<Artefact> {
+        registry_artefact(
+            context,
+            Hive::CurrentUser,
+            REGISTRY_RUNONCE_PATH,
+            RESUME_TASK_NAME,
+            "starts setup_assistant once after the reboot",
+        )
+    }
+}
+
+/// Requests the reboot; done, and the rebooted key set, once the machine has booted since
+struct RebootStep;
+
+impl Step for RebootStep {
+    fn step(&self) -> SetupStep {
+        SetupStep::Reboot
+    }
+
+    fn plan(&self, context: &StepContext) -> String {
+        if context.config.first_time_reboot {
+            format!("reboot in {} seconds", context.config.reboot_timer)
+        } else {
+            "wait for the machine to be rebooted".to_string()
+        }
+    }
+
+    fn apply(&self, context: &StepContext, state: &mut RunState) -> io::Result<bool> {
+        reboot(context.config, context.executor, state)
+    }
+
+    fn rollback(&self, context: &StepContext, state: &RunState) -> io::Result<()> {
+        // Nothing to cancel once the reboot happened
+        let rebooted: bool = state
+            .completed_steps
+            .iter()
+            .any(|completed| completed.step == SetupStep::Reboot);
+        if rebooted || state.reboot_requested_at.is_none() || !context.config.first_time_reboot {
+            return Ok(());
+        }
+        log::info!("cancelling the planned reboot...");
+        match context
+            .executor
+            .run_command("shutdown", &["/a".to_string()])?
+        {
+            0 => Ok(()),
+            code => Err(io::Error::other(format!(
+                "cancelling the reboot exited with code {}",
+                code
+            ))),
+        }
+    }
+
+    fn artefacts(&self, context: &StepContext, _state: &RunState) -> Vec<Artefact> {
+        registry_artefact(
+            context,
+            Hive::LocalMachine,
+            REBOOT_REGISTRY_PATH,
+            REBOOTED_KEY_NAME,
+            "marks the machine as rebooted",
+        )
+    }
+}
+
+/// Checks the locale settings once more, some only show after the reboot
+struct VerifyLocaleStep;
+
+impl Step for VerifyLocaleStep {
+    fn step(&self) -> SetupStep {
+        SetupStep::VerifyLocale
+    }
+
+    fn plan(&self, context: &StepContext) -> String {
+        if !context.config.change_locale {
+            return "skipped, change_locale is off".to_string();
+        }
+        format!(
+            "check the locale settings, {:?} on mismatch",
+            context.config.locale.verification
+        )
+        .to_lowercase()
+    }
+
+    fn apply(&self, context: &StepContext, _state: &mut RunState) -> io::Result<bool> {
+        if context.config.change_locale {
+            log::info!("verifying locale after the reboot...");
+        } else {
+            log::info!("locale verification skipped, the locale was not changed!");
+        }
+        Ok(true)
+    }
+
+    fn verify(&self, context: &StepContext) -> io::Result<()> {
+        if !context.config.change_locale {
+            return Ok(());
+        }
+        check_locale(&context.config.locale, context.executor)
+    }
+}
+
+/// Puts the startup entries and shortcuts back with registry_restore
+struct RestoreStartupStep;
+
+impl Step for RestoreStartupStep {
+    fn step(&self) -> SetupStep {
+        SetupStep::RestoreStartup
+    }
+
+    fn plan(&self, context: &StepContext) -> String {
+        if !context.config.restore_startup_apps {
+            return "skipped, restore_startup_apps is off".to_string();
+        }
+        format!("run {}", context.paths.registry_restore().display())
+    }
+
+    fn apply(&self, context: &StepContext, _state: &mut RunState) -> io::Result<bool> {
+        restore_startup(context.config, context.executor, context.paths)
+    }
+
+    fn artefacts(&self, context: &StepContext, _state: &RunState) -> Vec<Artefact> {
+        let restored_path: PathBuf = context.paths.restored_manifest();
+        if !restored_path.exists() {
+            return Vec::new();
+        }
+        vec![Artefact::File {
+            path: restored_path,
+            description: "backup manifest of the restored entries".to_string(),
+        }]
+    }
+}
+
+/// Runs the Moein setup
+struct RunSetupStep;
+
+impl Step for RunSetupStep {
+    fn step(&self) -> SetupStep {
+        SetupStep::RunSetup
+    }
+
+    fn plan(&self, context: &StepContext) -> String {
+        format!("run {}", context.paths.setup_executable().display())
+    }
+
+    fn apply(&self, context: &StepContext, _state: &mut RunState) -> io::Result<bool> {
+        run_moein_setup(context.executor, context.paths)
+    }
+}
+
+// +------------------+
+// | public functions |
+// +------------------+
+
+/// The implementation of a step
+///
+/// # Arguments
+///
+/// * `step` - The step
+///
+/// # Returns
+///
+/// `Box<dyn Step>` - The step implementation
+pub fn step_for(step: SetupStep) -> Box<dyn Step> {
+    match step {
+        SetupStep::CleanStartup => Box::new(CleanStartupStep),
+        SetupStep::ChangeLocale => Box::new(ChangeLocaleStep),
+        SetupStep::ChangeRegionalSettings => Box::new(ChangeRegionalSettingsStep),
+        SetupStep::ScheduleResume => Box::new(ScheduleResumeStep),
+        SetupStep::Reboot => Box::new(RebootStep),
+        SetupStep::VerifyLocale => Box::new(VerifyLocaleStep),
+        SetupStep::RestoreStartup => Box::new(RestoreStartupStep),
+        SetupStep::RunSetup => Box::new(RunSetupStep),
+    }
+}
+
+/// Carry out the configured steps of the run that are not done yet
+///
+/// Every change goes through the executor, so the same steps are carried out or planned. The
+/// state is saved after every step; the run stops early while it waits for the reboot. A failed
+/// step is handled by its policy: `continue` marks it as done and carries on, the others stop
+/// the run. Rolling back is left to the caller, see `roll_back_steps`.
+///
+/// # Arguments
+///
+/// * `context` - The config, executor and paths of the run
+/// * `state` - The progress of the run
+/// * `report` - The report of the run, every step carried out is added to it
+///
+/// # Returns
+///
+/// `Result<(), StepFailure>` - The step that stopped the run and its policy
+pub fn run_setup(
+    context: &StepContext,
+    state: &mut RunState,
+    report: &mut RunReport,
+) -> Result<(), StepFailure> {
+    let order: Vec<SetupStep> = context.config.step_order();
+    while let Some(settings) = state
+        .next_step(&order)
+        .and_then(|step| context.config.step_settings(step))
+    {
+        let (step, policy) = (settings.step, settings.policy());
+        match run_step(context, state, step, report) {
+            Ok(true) => {}
+            Ok(false) => return Ok(()),
+            Err(error) if policy == StepPolicy::Continue => {
+                log::error!("step {} failed, continuing: {}", step, error);
+                state.complete(step, &order);
+                state
+                    .save(context.executor, &context.paths.state_file())
+                    .map_err(|error| StepFailure {
+                        step,
+                        policy: StepPolicy::Abort,
+                        error,
+                    })?;
+            }
+            Err(error) => {
+                return Err(StepFailure {
+                    step,
+                    policy,
+                    error,
+                })
+            }
+        }
+    }
+    log::info!("run {} finished", state.run_id);
+    Ok(())
+}
+
+/// Carry out and verify a single step, save the state and add the step to the report
+///
+/// Also used to run a step by hand, whether or not it is done already.
+///
+/// # Arguments
+///
+/// * `context` - The config, executor and paths of the run
+/// * `state` - The progress of the run
+/// * `step` - The step to carry out
+/// * `report` - The report of the run
+///
+/// # Returns
+///
+/// `std::io::Result<bool>` - Whether the step is done, `false` while it waits for the reboot
+pub fn run_step(
+    context: &StepContext,
+    state: &mut RunState,
+    step: SetupStep,
+    report: &mut RunReport,
+) -> io::Result<bool> {
+    let implementation: Box<dyn Step> = step_for(step);
+    state.phase = step.phase();
+    log::info!("step {} ({} phase)...", step, state.phase);
+    let started_at: String = timestamp_utc();
+    let started: Instant = Instant::now();
+    let result: io::Result<bool> = implementation.apply(context, state).and_then(|done| {
+        if done {
+            implementation.verify(context)?;
+        }
+        Ok(done)
+    });
+    report.record_step(StepReport::new(
+        step,
+        context.policy(step),
+        started_at,
+        started.elapsed(),
+        &result,
+        implementation.artefacts(context, state),
+    ));
+    // The report is written even if the state cannot be, it tells what went wrong
+    if let Err(err) = report.save(context.executor, &context.paths.report_file(&state.run_id)) {
+        log::error!("failed to write the run report: {}", err);
+    }
+    let done: bool = result?;
+    if done {
+        state.complete(step, &context.config.step_order());
+    }
+    state.save(context.executor, &context.paths.state_file())?;
+    if done {
+        log::info!("step {} done", step);
+    } else {
+        log::info!(
+            "step {} is waiting, the run resumes on the next launch",
+            step
+        );
+    }
+    Ok(done)
+}
+
+/// Undo the failed step and the steps done before it, newest first
+///
+/// Failures are logged and do not stop the rollback of the other steps.
+///
+/// # Arguments
+///
+/// * `context` - The config, executor and paths of the run
+/// * `state` - The progress of the run
+/// * `failed` - The step that failed, `None` when a run is rolled back by hand
+///
+/// # Returns
+///
+/// `Vec<RolledBackStep>` - The steps rolled back and why a rollback failed, for the report
+pub fn roll_back_steps(
+    context: &StepContext,
+    state: &RunState,
+    failed: Option<SetupStep>,
+) -> Vec<RolledBackStep> {
+    let done: Vec<SetupStep> = state
+        .completed_steps
+        .iter()
+        .map(|completed| completed.step)
+        .filter(|step| Some(*step) != failed)
+        .collect();
+    let mut rolled_back: Vec<RolledBackStep> = Vec::new();
+    for step in failed.into_iter().chain(done.into_iter().rev()) {
+        log::info!("rolling back step {}...", step);
+        let errors: Vec<String> = match step_for(step).rollback(context, state) {
+            Ok(_) => Vec::new(),
+            Err(err) => {
+                log::error!("failed to roll back step {}: {}", step, err);
+                error_chain(&err)
+            }
+        };
+        rolled_back.push(RolledBackStep { step, errors });
+    }
+    rolled_back
+}
+
+// +-----------------------+
+// |  private functions    |
+// +-----------------------+
+
+/// Back up and disable the startup registry entries and shortcuts
+///
+/// # Arguments
+///
+/// * `config` - The configuration of the run
+/// * `executor` - The executor carrying out the changes
+/// * `run_id` - Id of the current run
+/// * `paths` - Where the files of the assistant live
+///
+/// # Returns
+///
+/// `std::io::Result<()>` - An error if the startup could not be cleaned safely
+fn clean_startup(
+    config: &Config,
+    executor: &dyn Executor,
+    run_id: &str,
+    paths: &AppPaths,
+) -> std::io::Result<()> {
+    if !config.clean_startup_apps {
+        log::info!("registry keys export skipped!");
+        return Ok(());
+    }
+    let manifest_path: PathBuf = paths.backup_manifest();
+    // Starting a new manifest over an unreadable one would lose the backups it records
+    let mut manifest: BackupManifest =
+        BackupManifest::load_or_default(&manifest_path).map_err(|err| {
+            io::Error::other(format!(
+                "failed to read backup manifest {}, fix or move it before cleaning the startup again: {}",
+                manifest_path.display(),
+                err
+            ))
+        })?;
+
+    log::info!("exporting registry key...");
+    match export_and_delete_startup_registry_keys(
+        executor,
+        &config.autostart_locations,
+        &config.startup_policy,
+        config.startup_clean_mode,
+        run_id,
+        paths,
+        &mut manifest,
+    ) {
+        Ok(_) => {
+            log::info!("registry keys are successfully exported!");
+        }
+        Err(err) => {
+            // The backups written before the failure stay recorded for restore
+            if let Err(write_err) =
+                executor.write_file(&manifest_path, manifest.to_json()?.as_bytes())
+            {
+                log::error!("failed to write backup manifest: {}", write_err);
+            }
+            return Err(io::Error::other(format!(
+                "failed to export registry keys: {}",
+                err
+            )));
+        }
+    }
+
+    log::info!("moving startup folder shortcuts...");
+    let backup_root: PathBuf = paths.startup_backup();
+    let should_move = |path: &Path| {
+        let name: String = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_default();
+        config
+            .startup_policy
+            .decide(&name, &path.display().to_string())
+            == StartupAction::Disable
+    };
+    for shortcut in find_startup_shortcuts(&startup_folders(), &backup_root, run_id, &should_move) {
+        match executor.move_file(&shortcut.original_path, &shortcut.backup_path) {
+            Ok(_) => {
+                log::info!(
+                    "startup shortcut {} moved to {}",
+                    shortcut.original_path.display(),
+                    shortcut.backup_path.display()
+                );
+                manifest.startup_shortcuts.push(shortcut);
+            }
+            Err(err) => log::error!(
+                "failed to move startup shortcut {}: {}",
+                shortcut.original_path.display(),
+                err
+            ),
+        }
+    }
+
+    manifest.conflict_policy = config.restore_conflict_policy;
+    match executor.write_file(&manifest_path, manifest.to_json()?.as_bytes()) {
+        Ok(_) => log::info!("backup manifest written to {}", manifest_path.display()),
+        Err(err) => {
+            return Err(io::Error::other(format!(
+                "failed to write backup manifest: {}",
+                err
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Apply the locale settings
+///
+/// # Arguments
+///
+/// * `config` - The configuration of the run
+/// * `executor` - The executor carrying out the changes
+///
+/// # Returns
+///
+/// `std::io::Result<()>` - An error if the settings could not be applied, the step policy
+/// decides whether the run goes on
+fn change_locale(config: &Config, executor: &dyn Executor) -> std::io::Result<()> {
+    if !config.change_locale {
+        log::info!("locale change skipped!");
+        return Ok(());
+    }
+    log::info!("changing locale...");
+    if !apply_locale(&config.locale, executor) {
+        return Err(io::Error::other("failed to apply the locale settings"));
+    }
+    Ok(())
+}
+
+/// Write the locale XML to a temporary folder and have `control.exe` apply it
+///
+/// The XML and its folder are removed again on every path.
+///
+/// # Arguments
+///
+/// * `settings` - The locale settings to apply
+/// * `executor` - The executor carrying out the changes
+///
+/// # Returns
+///
+/// `bool` - Whether `control.exe` ran and exited successfully
+fn apply_locale(settings: &LocaleSettings, executor: &dyn Executor) -> bool {
+    let locale_xml: LocaleXml = match LocaleXml::write(executor, settings) {
+        Ok(locale_xml) => locale_xml,
+        Err(err) => {
+            log::error!("failed to create xml file: {}", err);
+            return false;
+        }
+    };
+    log::info!("xml file created at {}.", locale_xml.path().display());
+    let command_line: String = match control_panel_command_line(locale_xml.path()) {
+        Ok(command_line) => command_line,
+        Err(err) => {
+            log::error!("failed to build the control panel command: {}", err);
+            return false;
+        }
+    };
+    match executor.run_raw_command(CONTROL_PANEL_PROGRAM, &command_line) {
+        Ok(0) => {
+            log::info!("locale settings applied successfully!");
+            true
+        }
+        Ok(code) => {
+            log::error!("{} exited with code {}", CONTROL_PANEL_PROGRAM, code);
+            false
+        }
+        Err(err) => {
+            log::error!("failed to run {}: {}", CONTROL_PANEL_PROGRAM, err);
+            false
+        }
+    }
+}
+
+/// Compare the registry with the locale settings, applying them again if configured
+///
+/// # Arguments
+///
+/// * `settings` - The requested locale settings
+/// * `executor` - The executor carrying out the changes
+///
+/// # Returns
+///
+/// `std::io::Result<()>` - An error if settings are still not applied
+fn check_locale(settings: &LocaleSettings, executor: &dyn Executor) -> io::Result<()> {
+    if settings.verification == LocaleVerification::Off {
+        log::info!("locale verification skipped!");
+        return Ok(());
+    }
+    let mut mismatches: Vec<LocaleMismatch> = verify_locale(executor.registry(), settings);
+    if mismatches.is_empty() {
+        log::info!("locale settings verified.");
+        return Ok(());
+    }
+    for mismatch in &mismatches {
+        log::warn!("locale setting not applied: {}", mismatch);
+    }
+
+    if settings.verification == LocaleVerification::Reapply {
+        log::info!("applying the locale settings again...");
+        if apply_locale(settings, executor) {
+            mismatches = verify_locale(executor.registry(), settings);
+            if mismatches.is_empty() {
+                log::info!("locale settings verified after applying them again.");
+                return Ok(());
+            }
+            for mismatch in &mismatches {
+                log::error!("locale setting still not applied: {}", mismatch);
+            }
+        }
+    }
+    Err(io::Error::other(format!(
+        "{} locale settings are not applied",
+        mismatches.len()
+    )))
+}
+
+/// Snapshot the regional settings and apply the configured ones
+///
+/// # Arguments
+///
+/// * `config` - The configuration of the run
+/// * `executor` - The executor carrying out the changes
+/// * `run_id` - Id of the current run
+/// * `paths` - Where the files of the assistant live
+///
+/// # Returns
+///
+/// `std::io::Result<()>` - An error if the snapshot could not be written
+fn change_regional_settings(
+    config: &Config,
+    executor: &dyn Executor,
+    run_id: &str,
+    paths: &AppPaths,
+) -> std::io::Result<()> {
+    if !config.change_regional_settings {
+        log::info!("regional settings change skipped!");
+        return Ok(());
+    }
+    log::info!("changing regional settings...");
+    apply_regional_settings(
+        executor,
+        &config.regional,
+        run_id,
+        &paths.regional_snapshot(),
+    )
+}
+
+/// Request the reboot, or confirm it happened on a later launch
+///
+/// Without `first_time_reboot` the reboot is left to the user and only waited for. A launch
+/// before the requested reboot happened waits for it, the reboot is not requested again.
+///
+/// # Arguments
+///
+/// * `config` - The configuration of the run
+/// * `executor` - The executor carrying out the changes
+/// * `state` - The progress of the run
+///
+/// # Returns
+///
+/// `std::io::Result<bool>` - Whether the machine has rebooted since the reboot was requested
+fn reboot(config: &Config, executor: &dyn Executor, state: &mut RunState) -> std::io::Result<bool> {
+    if let Some(requested_at) = &state.reboot_requested_at {
+        // Only a boot after the request counts, an unknown boot time is not taken as a reboot
+        match boot_time_utc() {
+            Some(boot_time) if boot_time > *requested_at => {
+                log::info!("the machine has rebooted since {}", requested_at);
+                log::info!("setting rebooted key...");
+                set_rebooted_key(executor.registry(), 1).map_err(|err| {
+                    io::Error::other(format!("failed to set rebooted key: {}", err))
+                })?;
+                log::info!("rebooted key set successfully!");
+                return Ok(true);
+            }
+            Some(_) => log::info!(
+                "the reboot requested at {} has not happened yet",
+                requested_at
+            ),
+            None => log::warn!(
+                "the boot time is unknown, the reboot requested at {} is not confirmed",
+                requested_at
+            ),
+        }
+        // The request still stands, a second `shutdown /r` would be rejected by Windows
+        return Ok(false);
+    }
+
+    // The request time is taken before `shutdown`, a reboot within the timer counts as well
+    let requested_at: String = timestamp_utc();
+    if config.first_time_reboot {
+        log::info!("rebooting...");
+        let shutdown_args: Vec<String> = vec![
+            "/r".to_string(),
+            "/t".to_string(),
+            config.reboot_timer.to_string(),
+        ];
+        match executor.run_command("shutdown", &shutdown_args) {
+            Ok(0) => log::info!("reboot planned"),
+            Ok(code) => {
+                return Err(io::Error::other(format!(
+                    "shutdown exited with code {}",
+                    code
+                )))
+            }
+            Err(err) => {
+                return Err(io::Error::other(format!("failed to reboot: {}", err)));
+            }
+        }
+    } else {
+        log::info!("reboot skipped, waiting for the machine to be rebooted!");
+    }
+    state.reboot_requested_at = Some(requested_at);
+    Ok(false)
+}
+
+/// Put the startup entries and shortcuts back by running registry_restore
+///
+/// # Arguments
+///
+/// * `config` - The configuration of the run
+/// * `executor` - The executor carrying out the changes
+/// * `paths` - Where the files of the assistant live
+///
+/// # Returns
+///
+/// `std::io::Result<bool>` - `true` once the restore succeeded
+fn restore_startup(
+    config: &Config,
+    executor: &dyn Executor,
+    paths: &AppPaths,
+) -> std::io::Result<bool> {
+    if !config.restore_startup_apps {
+        log::info!("startup restore skipped!");
+        return Ok(true);
+    }
+    let restore_path: PathBuf = paths.registry_restore();
+    log::info!("running {}...", restore_path.display());
+    let warnings: u8 = ExitStatus::SuccessWithWarnings.code();
+    match executor.run_command(&restore_path.display().to_string(), &[]) {
+        Ok(0) => {
+            log::info!("startup restored successfully!");
+            Ok(true)
+        }
+        // The backups left are kept in the manifest, `restore` retries them
+        Ok(code) if code == i32::from(warnings) => {
+            log::warn!("startup restored, some backups are left to restore later");
+            Ok(true)
+        }
+        Ok(code) => Err(io::Error::other(format!(
+            "registry restore exited with code {}",
+            code
+        ))),
+        Err(err) => Err(io::Error::other(format!(
+            "failed to run registry restore: {}",
+            err
+        ))),
+    }
+}
+
+/// Run the Moein setup and wait for it to exit
+///
+/// # Arguments
+///
+/// * `executor` - The executor carrying out the changes
+/// * `paths` - Where the files of the assistant live
+///
+/// # Returns
+///
+/// `std::io::Result<bool>` - `true` once the setup has run, an error if it failed to start or
+/// exited with a non-zero code
+fn run_moein_setup(executor: &dyn Executor, paths: &AppPaths) -> std::io::Result<bool> {
+    let setup_path: PathBuf = paths.setup_executable();
+    log::info!("running {}...", setup_path.display());
+    match executor.run_command(&setup_path.display().to_string(), &[]) {
+        Ok(0) => {
+            log::info!("setup finished successfully!");
+            Ok(true)
+        }
+        Ok(code) => Err(io::Error::other(format!("setup exited with code {}", code))),
+        Err(err) => Err(io::Error::other(format!("failed to run setup: {}", err))),
+    }
+}
+
+/// A registry value left by a step, if it exists
+///
+/// # Arguments
+///
+/// * `context` - The config, executor and paths of the run
+/// * `hive` - Hive of the key
+/// * `key_path` - Path of the key inside the hive
+/// * `name` - Name of the value
+/// * `description` - What the value is for
+///
+/// # Returns
+///
+/// `Vec<Artefact>` - The value, or nothing if it does not exist
+fn registry_artefact(
+    context: &StepContext,
+    hive: Hive,
+    key_path: &str,
+    name: &str,
+    description: &str,
+) -> Vec<Artefact> {
+    if context
+        .executor
+        .registry()
+        .get_value(hive, key_path, RegistryView::X64, name)
+        .is_err()
+    {
+        return Vec::new();
+    }
+    vec![Artefact::RegistryValue {
+        hive,
+        view: RegistryView::X64,
+        key_path: key_path.to_string(),
+        name: name.to_string(),
+        description: description.to_string(),
+    }]
+}
+
+/// Move the startup shortcuts of a run back and drop the run from the manifest
+///
+/// The registry values are put back by the journal, so only the shortcuts are moved here.
+/// Shortcuts of other runs stay in the manifest for `restore`.
+///
+/// # Arguments
+///
+/// * `context` - The config, executor and paths of the run
+/// * `run_id` - Id of the run being rolled back, every run if `None`
+///
+/// # Returns
+///
+/// `std::io::Result<()>` - An error if the manifest could not be read or written
+pub fn move_shortcuts_back(context: &StepContext, run_id: Option<&str>) -> std::io::Result<()> {
+    let manifest_path: PathBuf = context.paths.backup_manifest();
+    let Some(mut manifest) = BackupManifest::load(&manifest_path)? else {
+        return Ok(());
+    };
+    let mut kept: Vec<MovedShortcut> = Vec::new();
+    for shortcut in manifest.startup_shortcuts.drain(..) {
+        if run_id.is_some_and(|run_id| shortcut.run_id != run_id) {
+            kept.push(shortcut);
+            continue;
+        }
+        match context
+            .executor
+            .move_file(&shortcut.backup_path, &shortcut.original_path)
+        {
+            Ok(_) => log::info!(
+                "startup shortcut {} moved back",
+                shortcut.original_path.display()
+            ),
+            Err(err) => {
+                log::error!(
+                    "failed to move startup shortcut {} back: {}",
+                    shortcut.original_path.display(),
+                    err
+                );
+                kept.push(shortcut);
+            }
+        }
+    }
+    manifest.startup_shortcuts = kept;
+    manifest
+        .entries
+        .retain(|entry| run_id.is_some_and(|run_id| entry.run_id != run_id));
+    context
+        .executor
+        .write_file(&manifest_path, manifest.to_json()?.as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::executor::SystemExecutor;
+    use crate::plan::{PlanExecutor, PlannedAction};
+    use crate::registry_backend::{MemoryBackend, RegistryBackend};
+    use crate::startup_folder::StartupFolderScope;
+    use crate::test_support::TempFolder;
+
+    /// Plans like `PlanExecutor`, but every command exits with code 1
+    struct FailingCommands<'a>(PlanExecutor<'a>);
+
+    impl Executor for FailingCommands<'_> {
+        fn registry(&self) -> &dyn RegistryBackend {
+            self.0.registry()
+        }
+
+        fn write_file(&self, path: &Path, data: &[u8]) -> io::Result<()> {
+            self.0.write_file(path, data)
+        }
+
+        fn move_file(&self, from: &Path, to: &Path) -> io::Result<()> {
+            self.0.move_file(from, to)
+        }
+
+        fn remove_file(&self, path: &Path) -> io::Result<()> {
+            self.0.remove_file(path)
+        }
+
+        fn remove_folder(&self, path: &Path) -> io::Result<()> {
+            self.0.remove_folder(path)
+        }
+
+        fn run_command(&self, program: &str, args: &[String]) -> io::Result<i32> {
+            self.0.run_command(program, args)?;
+            Ok(1)
+        }
+
+        fn run_raw_command(&self, program: &str, command_line: &str) -> io::Result<i32> {
+            self.0.run_raw_command(program, command_line)?;
+            Ok(1)
+        }
+    }
+
+    /// Startup shortcuts moved by runs, in a temp folder removed on drop
+    struct ShortcutFixture {
+        temp: TempFolder,
+        paths: AppPaths,
+    }
+
+    impl ShortcutFixture {
+        fn new(name: &str) -> ShortcutFixture {
+            let temp = TempFolder::new(name);
+            let paths = AppPaths::new(&temp.0.join("data"));
+            ShortcutFixture { temp, paths }
+        }
+
+        /// A shortcut moved out of the user startup folder by `run_id`, its backup on disk
+        fn backed_up_shortcut(&self, run_id: &str, name: &str) -> MovedShortcut {
+            let shortcut = MovedShortcut {
+                run_id: run_id.to_string(),
+                scope: StartupFolderScope::User,
+                original_path: self.temp.0.join("Startup").join(name),
+                backup_path: self
+                    .paths
+                    .startup_backup()
+                    .join(run_id)
+                    .join("user")
+                    .join(name),
+            };
+            std::fs::create_dir_all(shortcut.backup_path.parent().unwrap()).unwrap();
+            std::fs::write(&shortcut.backup_path, name).unwrap();
+            shortcut
+        }
+
+        fn save_manifest(&self, startup_shortcuts: Vec<MovedShortcut>) {
+            BackupManifest {
+                startup_shortcuts,
+                ..BackupManifest::default()
+            }
+            .save(&self.paths.backup_manifest())
+            .unwrap();
+        }
+    }
+
+    fn step_context<'a>(
+        config: &'a Config,
+        executor: &'a dyn Executor,
+        paths: &'a AppPaths,
+    ) -> StepContext<'a> {
+        StepContext {
+            config,
+            executor,
+            paths,
+            executable_path: Path::new("setup_assistant.exe"),
+        }
+    }
+
+    #[test]
+    fn change_locale_plans_the_control_panel_call() {
+        let backend = MemoryBackend::default();
+        let executor = PlanExecutor::new(&backend);
+        let config = Config::default();
+        let paths = AppPaths::new(Path::new("data"));
+        let mut state = RunState::new("run");
+
+        let done = ChangeLocaleStep
+            .apply(&step_context(&config, &executor, &paths), &mut state)
+            .unwrap();
+
+        assert!(done);
+        let actions: Vec<PlannedAction> = executor.into_plan("run", Vec::new()).actions;
+        assert!(matches!(
+            actions.first(),
+            Some(PlannedAction::WriteFile { path, .. }) if path.extension().is_some_and(|extension| extension == "xml")
+        ));
+        assert!(actions.iter().any(|action| matches!(
+            action,
+            PlannedAction::RunCommand { program, .. } if program == CONTROL_PANEL_PROGRAM
+        )));
+        // The XML is removed again
+        assert!(matches!(
+            actions.last(),
+            Some(PlannedAction::RemoveFolder { .. })
+        ));
+    }
+
+    #[test]
+    fn change_locale_fails_when_the_control_panel_fails() {
+        let backend = MemoryBackend::default();
+        let executor = FailingCommands(PlanExecutor::new(&backend));
+        let config = Config::default();
+        let paths = AppPaths::new(Path::new("data"));
+        let mut state = RunState::new("run");
+
+        let result = ChangeLocaleStep.apply(&step_context(&config, &executor, &paths), &mut state);
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn change_locale_does_nothing_when_turned_off() {
+        let backend = MemoryBackend::default();
+        let executor = FailingCommands(PlanExecutor::new(&backend));
+        let config = Config {
+            change_locale: false,
+            ..Config::default()
+        };
+        let paths = AppPaths::new(Path::new("data"));
+        let mut state = RunState::new("run");
+
+        let done = ChangeLocaleStep
+            .apply(&step_context(&config, &executor, &paths), &mut state)
+            .unwrap();
+
+        assert!(done);
+        assert!(executor.0.into_plan("run", Vec::new()).actions.is_empty());
+    }
+
+    #[test]
+    fn reboot_is_requested_once_shutdown_accepts_it() {
+        let backend = MemoryBackend::default();
+        let executor = PlanExecutor::new(&backend);
+        let config = Config::default();
+        let paths = AppPaths::new(Path::new("data"));
+        let mut state = RunState::new("run");
+
+        let done = RebootStep
+            .apply(&step_context(&config, &executor, &paths), &mut state)
+            .unwrap();
+
+        assert!(!done);
+        assert!(state.reboot_requested_at.is_some());
+        assert!(matches!(
+            executor.into_plan("run", Vec::new()).actions.as_slice(),
+            [PlannedAction::RunCommand { program, .. }] if program == "shutdown"
+        ));
+    }
+
+    #[test]
+    fn pending_reboot_is_not_requested_again() {
+        let backend = MemoryBackend::default();
+        let executor = PlanExecutor::new(&backend);
+        let config = Config::default();
+        let paths = AppPaths::new(Path::new("data"));
+        let mut state = RunState::new("run");
+        // Later than any boot time, the reboot has not happened yet
+        state.reboot_requested_at = Some("9999-12-31T23:59:59Z".to_string());
+
+        let done = RebootStep
+            .apply(&step_context(&config, &executor, &paths), &mut state)
+            .unwrap();
+
+        assert!(!done);
+        assert_eq!(
+            state.reboot_requested_at.as_deref(),
+            Some("9999-12-31T23:59:59Z")
+        );
+        assert!(!executor
+            .into_plan("run", Vec::new())
+            .actions
+            .iter()
+            .any(|action| matches!(action, PlannedAction::RunCommand { .. })));
+    }
+
+    #[test]
+    fn reboot_fails_when_shutdown_fails() {
+        let backend = MemoryBackend::default();
+        let executor = FailingCommands(PlanExecutor::new(&backend));
+        let config = Config::default();
+        let paths = AppPaths::new(Path::new("data"));
+        let mut state = RunState::new("run");
+
+        let result = RebootStep.apply(&step_context(&config, &executor, &paths), &mut state);
+
+        assert!(result.is_err());
+        assert!(state.reboot_requested_at.is_none());
+    }
+
+    #[test]
+    fn a_failing_setup_fails_the_step() {
+        let backend = MemoryBackend::default();
+        let executor = FailingCommands(PlanExecutor::new(&backend));
+        let config = Config::default();
+        let paths = AppPaths::new(Path::new("data"));
+        let mut state = RunState::new("run");
+
+        let error = RunSetupStep
+            .apply(&step_context(&config, &executor, &paths), &mut state)
+            .unwrap_err();
+
+        assert_eq!(error.to_string(), "setup exited with code 1");
+    }
+
+    #[test]
+    fn rollback_moves_back_the_shortcuts_of_the_run_only() {
+        let fixture = ShortcutFixture::new("steps_rollback");
+        let ours: MovedShortcut = fixture.backed_up_shortcut("run", "Ours.lnk");
+        let earlier: MovedShortcut = fixture.backed_up_shortcut("earlier", "Earlier.lnk");
+        fixture.save_manifest(vec![ours.clone(), earlier.clone()]);
+
+        let backend = MemoryBackend::default();
+        let executor = SystemExecutor::new(&backend);
+        let config = Config::default();
+        CleanStartupStep
+            .rollback(
+                &step_context(&config, &executor, &fixture.paths),
+                &RunState::new("run"),
+            )
+            .unwrap();
+
+        assert!(ours.original_path.is_file());
+        assert!(earlier.backup_path.is_file());
+        assert!(!earlier.original_path.exists());
+        let manifest = BackupManifest::load(&fixture.paths.backup_manifest());
+        assert_eq!(manifest.unwrap().unwrap().startup_shortcuts, vec![earlier]);
+    }
+
+    #[test]
+    fn artefacts_list_the_shortcuts_of_the_run_only() {
+        let fixture = ShortcutFixture::new("steps_artefacts");
+        let ours: MovedShortcut = fixture.backed_up_shortcut("run", "Ours.lnk");
+        let earlier: MovedShortcut = fixture.backed_up_shortcut("earlier", "Earlier.lnk");
+        fixture.save_manifest(vec![ours.clone(), earlier]);
+
+        let backend = MemoryBackend::default();
+        let executor = PlanExecutor::new(&backend);
+        let config = Config::default();
+        let artefacts: Vec<Artefact> = CleanStartupStep.artefacts(
+            &step_context(&config, &executor, &fixture.paths),
+            &RunState::new("run"),
+        );
+
+        let files: Vec<PathBuf> = artefacts
+            .into_iter()
+            .filter_map(|artefact| match artefact {
+                Artefact::File { path, .. } => Some(path),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            files,
+            vec![fixture.paths.backup_manifest(), ours.backup_path]
+        );
+    }
+}
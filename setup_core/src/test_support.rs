@@ -0,0 +1,109 @@
+//! Helpers shared by the unit tests
+
+// std
+use std::env;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+// internal: registry_backend
+use crate::registry_backend::{Hive, MemoryBackend, RegValue, RegistryBackend, RegistryView};
+
+/// A fresh folder under the system temp folder, removed again on drop
+pub struct TempFolder(pub PathBuf);
+
+impl TempFolder {
+    /// Create the folder, `name` keeps the folders of concurrent tests apart
+    pub fn new(name: &str) -> TempFolder {
+        let path: PathBuf =
+            env::temp_dir().join(format!("setup_core_{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).unwrap();
+        TempFolder(path)
+    }
+}
+
+impl Drop for TempFolder {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+/// A registry in memory refusing to read, set or delete the values named `name`
+pub struct LockedValue {
+    pub inner: MemoryBackend,
+    pub name: &'static str,
+}
+
+impl LockedValue {
+    fn check(&self, name: &str) -> io::Result<()> {
+        if name == self.name {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                "access denied",
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl RegistryBackend for LockedValue {
+    fn open_key(&self, hive: Hive, path: &str, view: RegistryView) -> io::Result<()> {
+        self.inner.open_key(hive, path, view)
+    }
+
+    fn create_key(&self, hive: Hive, path: &str, view: RegistryView) -> io::Result<()> {
+        self.inner.create_key(hive, path, view)
+    }
+
+    fn enum_keys(&self, hive: Hive, path: &str, view: RegistryView) -> io::Result<Vec<String>> {
+        self.inner.enum_keys(hive, path, view)
+    }
+
+    fn enum_values(
+        &self,
+        hive: Hive,
+        path: &str,
+        view: RegistryView,
+    ) -> io::Result<Vec<(String, RegValue)>> {
+        self.inner.enum_values(hive, path, view)
+    }
+
+    fn get_value(
+        &self,
+        hive: Hive,
+        path: &str,
+        view: RegistryView,
+        name: &str,
+    ) -> io::Result<RegValue> {
+        self.check(name)?;
+        self.inner.get_value(hive, path, view, name)
+    }
+
+    fn set_value(
+        &self,
+        hive: Hive,
+        path: &str,
+        view: RegistryView,
+        name: &str,
+        value: &RegValue,
+    ) -> io::Result<()> {
+        self.check(name)?;
+        self.inner.set_value(hive, path, view, name, value)
+    }
+
+    fn delete_value(
+        &self,
+        hive: Hive,
+        path: &str,
+        view: RegistryView,
+        name: &str,
+    ) -> io::Result<()> {
+        self.check(name)?;
+        self.inner.delete_value(hive, path, view, name)
+    }
+
+    fn delete_key(&self, hive: Hive, path: &str, view: RegistryView) -> io::Result<()> {
+        self.inner.delete_key(hive, path, view)
+    }
+}
@@ -0,0 +1,331 @@
+// std
+use std::time::{SystemTime, UNIX_EPOCH};
+#[cfg(windows)]
+use std::ffi::OsStr;
+#[cfg(windows)]
+use std::os::windows::ffi::OsStrExt;
+#[cfg(windows)]
+use std::ptr;
+
+// winapi
+#[cfg(windows)]
+use winapi::um::winuser::{
+    MessageBoxW,
+    MB_ICONINFORMATION,
+    MB_ICONWARNING,
+    MB_ICONERROR,
+    MB_OK,
+};
+#[cfg(windows)]
+use winapi::um::wow64apiset::IsWow64Process;
+#[cfg(windows)]
+use winapi::um::processthreadsapi::{GetCurrentProcess, OpenProcessToken};
+#[cfg(windows)]
+use winapi::um::securitybaseapi::GetTokenInformation;
+#[cfg(windows)]
+use winapi::um::handleapi::CloseHandle;
+#[cfg(windows)]
+use winapi::um::winnt::{TokenElevation, HANDLE, TOKEN_ELEVATION, TOKEN_QUERY};
+#[cfg(windows)]
+use winapi::um::sysinfoapi::GetTickCount64;
+
+
+/// Enum representing different window types
+pub enum WindowType {
+    Error,
+    Information,
+    _Warning,
+}
+
+
+/// Display an OK message box with the specified window title, message, and type
+/// 
+/// # Arguments
+/// 
+/// * `window_title` - The title to be displayed in the message box
+/// * `window_message` - The message to be displayed in the message box
+/// * `window_type` - The type of the message box (Error, Information, or Warning)
+#[cfg(windows)]
+pub fn message_box(window_title: &str, window_message: &str, window_type: WindowType) {
+    let wide_error_message: Vec<u16> = OsStr::new(window_message)
+        .encode_wide()
+        .chain(Some(0))
+        .collect();
+    let wide_window_title: Vec<u16> = OsStr::new(window_title)
+        .encode_wide()
+        .chain(Some(0))
+        .collect();
+    unsafe {
+        MessageBoxW(
+            ptr::null_mut(),
+            wide_error_message.as_ptr(),
+            wide_window_title.as_ptr(),
+            MB_OK | match window_type {
+                WindowType::Error => MB_ICONERROR,
+                WindowType::Information => MB_ICONINFORMATION,
+                WindowType::_Warning => MB_ICONWARNING,
+            },
+        );
+    };
+}
+
+
+/// Fallback for non-Windows builds: the message is written to the log instead
+#[cfg(not(windows))]
+pub fn message_box(window_title: &str, window_message: &str, window_type: WindowType) {
+    match window_type {
+        WindowType::Error => log::error!("{}: {}", window_title, window_message),
+        WindowType::Information => log::info!("{}: {}", window_title, window_message),
+        WindowType::_Warning => log::warn!("{}: {}", window_title, window_message),
+    }
+}
+
+
+/// Check whether the operating system is 64-bit
+///
+/// # Returns
+///
+/// `bool` - `true` when running as a WOW64 process on a 64-bit Windows
+#[cfg(windows)]
+pub fn is_64bit_os() -> bool {
+    let mut is_wow64: i32 = 0;
+    unsafe {
+        IsWow64Process(GetCurrentProcess(), &mut is_wow64);
+    }
+    is_wow64 != 0
+}
+
+
+/// Check whether the operating system is 64-bit
+///
+/// # Returns
+///
+/// `bool` - `true` when compiled for a 64-bit target
+#[cfg(not(windows))]
+pub fn is_64bit_os() -> bool {
+    cfg!(target_pointer_width = "64")
+}
+
+
+/// Check whether the process runs elevated
+///
+/// # Returns
+///
+/// `Option<bool>` - Whether the process token is elevated, `None` if it could not be queried
+#[cfg(windows)]
+pub fn is_elevated() -> Option<bool> {
+    let mut token: HANDLE = ptr::null_mut();
+    let mut elevation = TOKEN_ELEVATION { TokenIsElevated: 0 };
+    let mut size: u32 = 0;
+    unsafe {
+        if OpenProcessToken(GetCurrentProcess(), TOKEN_QUERY, &mut token) == 0 {
+            return None;
+        }
+        let queried: i32 = GetTokenInformation(
+            token,
+            TokenElevation,
+            &mut elevation as *mut TOKEN_ELEVATION as *mut _,
+            std::mem::size_of::<TOKEN_ELEVATION>() as u32,
+            &mut size,
+        );
+        CloseHandle(token);
+        if queried == 0 {
+            return None;
+        }
+    }
+    Some(elevation.TokenIsElevated != 0)
+}
+
+
+/// Check whether the process runs elevated
+///
+/// # Returns
+///
+/// `Option<bool>` - Always `None`, elevation is only known on Windows
+#[cfg(not(windows))]
+pub fn is_elevated() -> Option<bool> {
+    None
+}
+
+
+/// Match a text against a case-insensitive glob pattern supporting `*` and `?`
+///
+/// # Arguments
+///
+/// * `pattern` - The glob pattern
+/// * `text` - The text to be matched
+///
+/// # Returns
+///
+/// `bool` - Whether the whole text matches the pattern
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.to_lowercase().chars().collect();
+    let text: Vec<char> = text.to_lowercase().chars().collect();
+    let (mut p, mut t) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, t));
+            p += 1;
+        } else if let Some((star, matched)) = backtrack {
+            p = star + 1;
+            t = matched + 1;
+            backtrack = Some((star, matched + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|&character| character == '*')
+}
+
+
+/// Current time as a Windows FILETIME (100-nanosecond intervals since 1601-01-01 UTC)
+///
+/// # Returns
+///
+/// `u64` - The current FILETIME
+pub fn filetime_now() -> u64 {
+    const UNIX_EPOCH_AS_FILETIME: u64 = 116_444_736_000_000_000;
+    let since_epoch = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    UNIX_EPOCH_AS_FILETIME + since_epoch.as_nanos() as u64 / 100
+}
+
+
+/// Current UTC time as an RFC 3339 timestamp, e.g. `2024-03-01T08:30:00Z`
+///
+/// # Returns
+///
+/// `String` - The formatted timestamp
+pub fn timestamp_utc() -> String {
+    format_timestamp(unix_now())
+}
+
+
+/// Time the system was booted at as an RFC 3339 timestamp, comparable with `timestamp_utc`
+///
+/// # Returns
+///
+/// `Option<String>` - The boot time, `None` if it is unknown
+#[cfg(windows)]
+pub fn boot_time_utc() -> Option<String> {
+    let uptime_seconds: u64 = unsafe { GetTickCount64() } / 1000;
+    Some(format_timestamp(unix_now().saturating_sub(uptime_seconds)))
+}
+
+
+/// Time the system was booted at as an RFC 3339 timestamp, comparable with `timestamp_utc`
+///
+/// # Returns
+///
+/// `Option<String>` - The boot time, `None` if it is unknown
+#[cfg(not(windows))]
+pub fn boot_time_utc() -> Option<String> {
+    let uptime: String = std::fs::read_to_string("/proc/uptime").ok()?;
+    let uptime_seconds: f64 = uptime.split_whitespace().next()?.parse().ok()?;
+    Some(format_timestamp(
+        unix_now().saturating_sub(uptime_seconds as u64),
+    ))
+}
+
+
+/// Format seconds since the Unix epoch as an RFC 3339 UTC timestamp
+fn format_timestamp(seconds: u64) -> String {
+    let (year, month, day, hour, minute, second) = utc_parts(seconds);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year, month, day, hour, minute, second
+    )
+}
+
+
+/// Create an id for the current run from the UTC time and the process id
+///
+/// # Returns
+///
+/// `String` - The run id, e.g. `20240301T083000Z-1a2c`
+pub fn new_run_id() -> String {
+    let (year, month, day, hour, minute, second) = utc_parts(unix_now());
+    format!(
+        "{:04}{:02}{:02}T{:02}{:02}{:02}Z-{:x}",
+        year,
+        month,
+        day,
+        hour,
+        minute,
+        second,
+        std::process::id()
+    )
+}
+
+
+/// Seconds since the Unix epoch
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+
+/// Split seconds since the Unix epoch into UTC year, month, day, hour, minute and second
+fn utc_parts(seconds: u64) -> (i64, u32, u32, u64, u64, u64) {
+    let days: i64 = (seconds / 86_400) as i64;
+    let time: u64 = seconds % 86_400;
+
+    // Civil date from days since 1970-01-01, proleptic Gregorian calendar
+    let z: i64 = days + 719_468;
+    let era: i64 = z.div_euclid(146_097);
+    let day_of_era: i64 = z.rem_euclid(146_097);
+    let year_of_era: i64 =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year: i64 = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index: i64 = (5 * day_of_year + 2) / 153;
+    let day: u32 = (day_of_year - (153 * month_index + 2) / 5 + 1) as u32;
+    let month: u32 = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    } as u32;
+    let year: i64 = year_of_era + era * 400 + i64::from(month <= 2);
+
+    (year, month, day, time / 3_600, time % 3_600 / 60, time % 60)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stars_match_any_run_of_characters() {
+        assert!(glob_match("*drive", "OneDrive"));
+        assert!(glob_match("One*", "OneDrive"));
+        assert!(glob_match("O*e*e", "OneDrive"));
+        assert!(glob_match("*", ""));
+        assert!(glob_match(
+            "C:\\*\\app*.exe",
+            "C:\\Program Files\\App\\app64.exe"
+        ));
+        assert!(!glob_match("*drive", "OneDrive Sync"));
+        assert!(!glob_match("One*x", "OneDrive"));
+    }
+
+    #[test]
+    fn question_marks_match_one_character() {
+        assert!(glob_match("app?.exe", "app1.exe"));
+        assert!(!glob_match("app?.exe", "app.exe"));
+        assert!(!glob_match("app?.exe", "app12.exe"));
+    }
+
+    #[test]
+    fn patterns_match_the_whole_text_ignoring_case() {
+        assert!(glob_match("ONEDRIVE", "OneDrive"));
+        assert!(glob_match("onedrive", "ONEDRIVE"));
+        assert!(!glob_match("OneDrive", "OneDrive2"));
+        assert!(!glob_match("Drive", "OneDrive"));
+    }
+}
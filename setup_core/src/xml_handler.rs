@@ -0,0 +1,102 @@
+// std
+use std::env;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::constants::*;
+
+// internal: executor
+use crate::executor::Executor;
+
+// internal: locale
+use crate::locale::{render_locale_xml, LocaleSettings};
+
+
+/// Counts the temporary folders created by this process, keeping their names unique
+static TEMP_FOLDER_COUNT: AtomicU32 = AtomicU32::new(0);
+
+
+/// The locale XML in a temporary folder of its own
+///
+/// The file and the folder are removed when the guard is dropped, whether or not applying the
+/// settings succeeded.
+pub struct LocaleXml<'a> {
+    executor: &'a dyn Executor,
+    folder: PathBuf,
+    path: PathBuf,
+}
+
+
+impl<'a> LocaleXml<'a> {
+    /// Writes the XML file containing locale and language preferences
+    ///
+    /// # Arguments
+    /// * `executor` - The executor writing and removing the files
+    /// * `settings` - The locale settings the XML is generated from
+    ///
+    /// # Returns
+    /// `Result<LocaleXml, std::io::Error>` - The guard of the written file
+    pub fn write(executor: &'a dyn Executor, settings: &LocaleSettings) -> Result<LocaleXml<'a>, std::io::Error> {
+        let folder: PathBuf = unique_temp_folder();
+        let locale_xml = LocaleXml {
+            executor,
+            path: folder.join(XML_FILE_NAME),
+            folder,
+        };
+        // The guard exists before anything is written, so a partial write is cleaned up too
+        executor.write_file(&locale_xml.path, render_locale_xml(settings).as_bytes())?;
+        Ok(locale_xml)
+    }
+
+    /// Path of the written XML file
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+
+impl Drop for LocaleXml<'_> {
+    fn drop(&mut self) {
+        if let Err(err) = self.executor.remove_file(&self.path) {
+            log::error!("failed to remove {}: {}", self.path.display(), err);
+        }
+        match self.executor.remove_folder(&self.folder) {
+            Ok(_) => log::info!("locale files removed."),
+            Err(err) => log::error!("failed to remove {}: {}", self.folder.display(), err),
+        }
+    }
+}
+
+
+/// Builds the `control.exe` command line applying a locale XML
+///
+/// `control.exe` parses its command line itself, the path is quoted inside the `/f:` switch.
+///
+/// # Arguments
+/// * `xml_path` - Path of the locale XML
+///
+/// # Returns
+/// `Result<String, std::io::Error>` - The command line, an error if the path cannot be quoted
+pub fn control_panel_command_line(xml_path: &Path) -> Result<String, std::io::Error> {
+    let path: String = xml_path.display().to_string();
+    if path.contains('"') {
+        return Err(std::io::Error::other(format!("cannot quote path {}", path)));
+    }
+    Ok(format!("intl.cpl,,/f:\"{}\"", path))
+}
+
+
+/// Picks a folder under the temp folder that no other run or process uses
+///
+/// # Returns
+/// `PathBuf` - Path of the folder, it is not created here
+fn unique_temp_folder() -> PathBuf {
+    let nanos: u128 = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_nanos())
+        .unwrap_or_default();
+    let count: u32 = TEMP_FOLDER_COUNT.fetch_add(1, Ordering::Relaxed);
+    env::temp_dir().join(format!("{}_{}_{}_{}", TEMP_FOLDER_PREFIX, process::id(), nanos, count))
+}
@@ -0,0 +1,103 @@
+// +------------------+
+// |    dependencies  |
+// +------------------+
+
+// std
+use std::path::PathBuf;
+
+// clap
+use clap::{Parser, Subcommand};
+
+// internal: setup_core
+use setup_core::exit_code::exit_code_table;
+use setup_core::run_state::SetupStep;
+
+// +------------------+
+// |      types       |
+// +------------------+
+
+/// Prepares the machine, reboots it and installs Moein
+///
+/// Without a command the remaining steps of the run are carried out, as when started by RunOnce.
+#[derive(Debug, Parser)]
+#[command(
+    name = "setup_assistant",
+    version,
+    after_long_help = format!("Exit codes:\n{}", exit_code_table())
+)]
+pub struct Cli {
+    /// Read the config from this file instead of `config.json` in the data folder
+    #[arg(long, global = true, value_name = "PATH")]
+    pub config: Option<PathBuf>,
+
+    /// Use this data folder instead of the one next to the executable
+    #[arg(long, global = true, value_name = "PATH")]
+    pub data_dir: Option<PathBuf>,
+
+    /// Do not show the message box or print progress
+    #[arg(long, global = true)]
+    pub quiet: bool,
+
+    /// Do not reboot the machine, wait for a manual reboot instead
+    #[arg(long, global = true)]
+    pub no_reboot: bool,
+
+    /// Override a config field, e.g. `--set reboot_timer=120`; values are read as JSON
+    #[arg(long = "set", global = true, value_name = "KEY=VALUE", value_parser = parse_setting)]
+    pub settings: Vec<(String, String)>,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+/// What setup_assistant is asked to do
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Carry out the remaining steps of the run
+    Run {
+        /// Only carry out this step, even if it is done already
+        #[arg(long, value_name = "STEP", value_parser = parse_step)]
+        step: Option<SetupStep>,
+    },
+    /// Print what the remaining steps would change, without changing anything
+    Plan {
+        /// Print the plan as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Put the startup entries and shortcuts from the backup manifest back
+    Restore,
+    /// Print the state of the current run, the backups and the journal
+    Status,
+    /// Undo the steps, the moved startup shortcuts and the journaled registry changes of a run
+    Rollback {
+        /// Undo the changes of this run instead of the current one
+        #[arg(long, value_name = "RUN_ID", conflicts_with = "all")]
+        run: Option<String>,
+        /// Undo the changes of every run
+        #[arg(long)]
+        all: bool,
+    },
+    /// Check the config and the data folder without changing anything
+    Check,
+}
+
+// +-----------------------+
+// |  private functions    |
+// +-----------------------+
+
+/// Parse a `KEY=VALUE` config override given on the command line
+fn parse_setting(setting: &str) -> Result<(String, String), String> {
+    match setting.split_once('=') {
+        Some((key, value)) if !key.is_empty() => Ok((key.to_string(), value.to_string())),
+        _ => Err("expected KEY=VALUE".to_string()),
+    }
+}
+
+/// Parse a step name given on the command line
+fn parse_step(name: &str) -> Result<SetupStep, String> {
+    SetupStep::from_name(name).ok_or_else(|| {
+        let names: Vec<&str> = SetupStep::ALL.iter().map(|step| step.name()).collect();
+        format!("unknown step, expected one of: {}", names.join(", "))
+    })
+}
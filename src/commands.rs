@@ -0,0 +1,683 @@
+// +------------------+
+// |    dependencies  |
+// +------------------+
+
+// std
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+// internal: setup_core
+use setup_core::backup_manifest::BackupManifest;
+use setup_core::config::{Config, ConfigSource};
+use setup_core::config_schema::ConfigError;
+use setup_core::executor::{Executor, SystemExecutor};
+use setup_core::exit_code::ExitStatus;
+use setup_core::paths::AppPaths;
+use setup_core::plan::{Plan, PlanExecutor, PlannedStep};
+use setup_core::regional::{revert_regional_settings, RegionalSnapshot};
+use setup_core::registry_backend::{RegistryBackend, SystemBackend};
+use setup_core::registry_journal::{rollback_journal, Journal, JournalingBackend};
+use setup_core::restore::restore_backups;
+use setup_core::run_report::{
+    error_chain, Environment, RollbackReport, RunReport, SessionOutcome, SessionReport,
+};
+use setup_core::run_state::{Phase, RunState, SetupStep, ASSISTANT_VERSION};
+use setup_core::step::{StepContext, StepPolicy};
+use setup_core::steps::{move_shortcuts_back, roll_back_steps, run_setup, run_step, step_for};
+use setup_core::utilities::{message_box, new_run_id, WindowType};
+
+// +------------------+
+// |      types       |
+// +------------------+
+
+/// Everything a command needs, resolved from the command line and the config
+pub struct Context {
+    pub paths: AppPaths,
+    pub config: Config,
+    /// The config layers applied, in order
+    pub config_layers: Vec<ConfigSource>,
+    /// Why the config is invalid, only set for `check`, every other command stops on it
+    pub config_error: Option<ConfigError>,
+    pub executable_path: PathBuf,
+    pub quiet: bool,
+}
+
+/// A failed command and the status setup_assistant exits with
+#[derive(Debug)]
+pub struct CommandError {
+    pub status: ExitStatus,
+    pub error: io::Error,
+}
+
+impl CommandError {
+    fn new(status: ExitStatus, error: io::Error) -> CommandError {
+        CommandError { status, error }
+    }
+}
+
+impl fmt::Display for CommandError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.error.fmt(f)
+    }
+}
+
+/// Errors without a more specific status exit with `failure`
+impl From<io::Error> for CommandError {
+    fn from(error: io::Error) -> Self {
+        CommandError::new(ExitStatus::Failure, error)
+    }
+}
+
+impl Context {
+    /// Log a progress message and print it unless `--quiet` is given
+    fn say(&self, message: &str) {
+        log::info!("{}", message);
+        if !self.quiet {
+            println!("{}", message);
+        }
+    }
+
+    /// What the steps work with, changing the machine through `executor`
+    fn step_context<'a>(&'a self, executor: &'a dyn Executor) -> StepContext<'a> {
+        StepContext {
+            config: &self.config,
+            executor,
+            paths: &self.paths,
+            executable_path: &self.executable_path,
+        }
+    }
+}
+
+// +------------------+
+// | public functions |
+// +------------------+
+
+/// Carry out the remaining steps of the run, or a single step by hand
+///
+/// A failed step is handled by its policy: with `abort_and_rollback` the steps done so far and
+/// the registry changes of the run are undone and the state is removed; with `abort` the state
+/// is kept so the failed step is retried on the next launch. A step run by hand is never rolled
+/// back, use `rollback` for that. Every launch adds a session to the report of the run.
+///
+/// # Arguments
+///
+/// * `context` - The resolved paths and config
+/// * `step` - Only carry out this step
+///
+/// # Returns
+///
+/// `Result<ExitStatus, CommandError>` - Whether the run finished, with warnings, or waits for
+/// the reboot; an error if a step failed
+pub fn run(context: &Context, step: Option<SetupStep>) -> Result<ExitStatus, CommandError> {
+    let system_backend = SystemBackend::default();
+    let mut state: RunState = load_state(&system_backend, &context.paths)
+        .map_err(|err| CommandError::new(ExitStatus::PreflightFailure, err))?;
+    if step.is_none() && state.phase == Phase::Finished {
+        context.say(&format!(
+            "run {} is already finished, nothing to do.",
+            state.run_id
+        ));
+        return Ok(ExitStatus::Success);
+    }
+
+    // Every registry change goes through the journal so a failed run can be rolled back
+    let journal_path: PathBuf = context.paths.journal_file();
+    let backend: JournalingBackend =
+        JournalingBackend::new(&system_backend, &journal_path, &state.run_id).map_err(|err| {
+            CommandError::new(
+                ExitStatus::PreflightFailure,
+                io::Error::other(format!(
+                    "failed to open registry journal, nothing is changed: {}",
+                    err
+                )),
+            )
+        })?;
+    let executor: SystemExecutor = SystemExecutor::new(&backend);
+
+    let report_path: PathBuf = context.paths.report_file(&state.run_id);
+    let mut report: RunReport = RunReport::load_or_new(&report_path, &state.run_id);
+    report.start_session(SessionReport::new(
+        state.phase,
+        step,
+        &context.config,
+        Environment::collect(&system_backend),
+    ));
+
+    if let Some(step) = step {
+        context.say(&format!("running step {} of run {}...", step, state.run_id));
+        let result: io::Result<bool> = run_step(
+            &context.step_context(&executor),
+            &mut state,
+            step,
+            &mut report,
+        );
+        let outcome: SessionOutcome = match result {
+            Ok(true) => SessionOutcome::Done,
+            Ok(false) => SessionOutcome::WaitingForReboot,
+            Err(_) => SessionOutcome::Stopped,
+        };
+        finish_report(&mut report, outcome, None, &executor, &report_path);
+        let done: bool = result.map_err(|err| CommandError::new(ExitStatus::StepFailure, err))?;
+        if done {
+            context.say(&format!("step {} done", step));
+            return Ok(ExitStatus::Success);
+        }
+        context.say(&format!("step {} is waiting for the reboot", step));
+        return Ok(ExitStatus::RebootPending);
+    }
+
+    // Display a message box to show the execution of the app
+    if !context.quiet {
+        let message: &str = "شروع فرایند نصب نرم افزار معین";
+        let title: &str = "معین";
+        message_box(title, message, WindowType::Information);
+    }
+
+    context.say(&format!("running run {}...", state.run_id));
+    if let Err(failure) = run_setup(&context.step_context(&executor), &mut state, &mut report) {
+        log::error!("{}", failure);
+        let status: ExitStatus = if failure.policy == StepPolicy::AbortAndRollback {
+            // The rollback itself is not journaled, it goes to the registry directly
+            let rollback_executor: SystemExecutor = SystemExecutor::new(&system_backend);
+            let steps = roll_back_steps(
+                &context.step_context(&rollback_executor),
+                &state,
+                Some(failure.step),
+            );
+            let rollback: RollbackReport = RollbackReport {
+                steps,
+                ..roll_back_run(&system_backend, &journal_path, &state.run_id)
+            };
+            remove_state_file(&context.paths);
+            finish_report(
+                &mut report,
+                SessionOutcome::RolledBack,
+                Some(rollback),
+                &executor,
+                &report_path,
+            );
+            ExitStatus::RolledBack
+        } else {
+            log::error!("the run stops here and resumes on the next launch");
+            finish_report(
+                &mut report,
+                SessionOutcome::Stopped,
+                None,
+                &executor,
+                &report_path,
+            );
+            ExitStatus::StepFailure
+        };
+        return Err(CommandError::new(status, failure.into()));
+    }
+    if state.phase != Phase::Finished {
+        finish_report(
+            &mut report,
+            SessionOutcome::WaitingForReboot,
+            None,
+            &executor,
+            &report_path,
+        );
+        context.say(&format!(
+            "run {} is waiting for the reboot, it resumes on the next launch",
+            state.run_id
+        ));
+        return Ok(ExitStatus::RebootPending);
+    }
+    finish_report(
+        &mut report,
+        SessionOutcome::Done,
+        None,
+        &executor,
+        &report_path,
+    );
+    let failed: Vec<SetupStep> = report.continued_failures();
+    if failed.is_empty() {
+        context.say(&format!("run {} finished", state.run_id));
+        return Ok(ExitStatus::Success);
+    }
+    let names: Vec<&str> = failed.iter().map(|step| step.name()).collect();
+    context.say(&format!(
+        "run {} finished, but these steps failed: {}",
+        state.run_id,
+        names.join(", ")
+    ));
+    Ok(ExitStatus::SuccessWithWarnings)
+}
+
+/// Print what the remaining steps would change, without changing anything
+///
+/// # Arguments
+///
+/// * `context` - The resolved paths and config
+/// * `json` - Print the plan as JSON instead of text
+///
+/// # Returns
+///
+/// `Result<ExitStatus, CommandError>` - An error if the state is unreadable
+pub fn plan(context: &Context, json: bool) -> Result<ExitStatus, CommandError> {
+    let system_backend = SystemBackend::default();
+    let mut state: RunState = load_state(&system_backend, &context.paths)?;
+    log::info!("planning setup...");
+    let executor: PlanExecutor = PlanExecutor::new(&system_backend);
+    let steps: Vec<PlannedStep> = context
+        .config
+        .steps
+        .iter()
+        .filter(|settings| !state.is_completed(settings.step))
+        .map(|settings| PlannedStep {
+            step: settings.step,
+            on_failure: settings.policy(),
+            description: step_for(settings.step).plan(&context.step_context(&executor)),
+        })
+        .collect();
+    // The report is planned like every other write, it is not kept
+    let mut report: RunReport = RunReport::new(&state.run_id);
+    report.start_session(SessionReport::new(
+        state.phase,
+        None,
+        &context.config,
+        Environment::collect(&system_backend),
+    ));
+    if let Err(failure) = run_setup(&context.step_context(&executor), &mut state, &mut report) {
+        log::error!("the run would stop here: {}", failure);
+    }
+    let plan: Plan = executor.into_plan(&state.run_id, steps);
+    if json {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&plan).map_err(io::Error::from)?
+        );
+    } else {
+        print!("{}", plan);
+    }
+    Ok(ExitStatus::Success)
+}
+
+/// Put the startup entries and shortcuts from the backup manifest back
+///
+/// # Arguments
+///
+/// * `context` - The resolved paths and config
+///
+/// # Returns
+///
+/// `Result<ExitStatus, CommandError>` - `success_with_warnings` if backups are left to restore,
+/// an error if the manifest is unusable
+pub fn restore(context: &Context) -> Result<ExitStatus, CommandError> {
+    let backend = SystemBackend::default();
+    match restore_backups(&backend, &context.paths)? {
+        0 => {
+            context.say("startup restored successfully!");
+            Ok(ExitStatus::Success)
+        }
+        left => {
+            context.say(&format!(
+                "{} backups could not be restored, they are kept in {}",
+                left,
+                context.paths.backup_manifest().display()
+            ));
+            Ok(ExitStatus::SuccessWithWarnings)
+        }
+    }
+}
+
+/// Print the state of the current run, the backups and the journal
+///
+/// # Arguments
+///
+/// * `context` - The resolved paths and config
+///
+/// # Returns
+///
+/// `Result<ExitStatus, CommandError>` - An error if one of the files is unreadable
+pub fn status(context: &Context) -> Result<ExitStatus, CommandError> {
+    let backend = SystemBackend::default();
+    match RunState::load(&backend, &context.paths.state_file())? {
+        Some(state) => {
+            println!("run:              {}", state.run_id);
+            println!("version:          {}", state.assistant_version);
+            println!("phase:            {}", state.phase);
+            println!("started:          {}", state.started_at);
+            println!("updated:          {}", state.updated_at);
+            println!(
+                "reboot requested: {}",
+                state.reboot_requested_at.as_deref().unwrap_or("no")
+            );
+            println!("steps:");
+            for step in context.config.step_order() {
+                match state.completed_steps.iter().find(|done| done.step == step) {
+                    Some(done) => println!("  [x] {} ({})", step, done.completed_at),
+                    None => println!("  [ ] {}", step),
+                }
+            }
+        }
+        None => println!("run:              none started"),
+    }
+
+    let manifest_path: PathBuf = context.paths.backup_manifest();
+    match BackupManifest::load(&manifest_path)? {
+        Some(manifest) => println!(
+            "backups:          {} registry backups, {} startup shortcuts",
+            manifest.entries.len(),
+            manifest.startup_shortcuts.len()
+        ),
+        None => println!("backups:          none"),
+    }
+
+    let journal: Journal = Journal::load(&context.paths.journal_file())?;
+    println!(
+        "journal:          {} registry changes",
+        journal.entries.len()
+    );
+
+    if let Some(state) = RunState::load(&backend, &context.paths.state_file())? {
+        let report_path: PathBuf = context.paths.report_file(&state.run_id);
+        if report_path.exists() {
+            println!("report:           {}", report_path.display());
+        }
+    }
+    Ok(ExitStatus::Success)
+}
+
+/// Undo the changes of a run, or of every run
+///
+/// The steps of the current run are rolled back the way `run` does on `abort_and_rollback`,
+/// the startup shortcuts of other runs are moved back from the manifest. The journaled
+/// registry changes and the regional settings are undone afterwards. Rolling back the current
+/// run also removes its state, so the next launch starts over.
+///
+/// # Arguments
+///
+/// * `context` - The resolved paths and config
+/// * `run` - Undo the changes of this run instead of the current one
+/// * `all` - Undo the changes of every run
+///
+/// # Returns
+///
+/// `Result<ExitStatus, CommandError>` - An error if a change could not be undone
+pub fn rollback(
+    context: &Context,
+    run: Option<String>,
+    all: bool,
+) -> Result<ExitStatus, CommandError> {
+    let backend = SystemBackend::default();
+    let current: Option<RunState> = RunState::load(&backend, &context.paths.state_file())?;
+    let run_id: Option<String> = match (run, all) {
+        (_, true) => None,
+        (Some(run_id), false) => Some(run_id),
+        (None, false) => match &current {
+            Some(state) => Some(state.run_id.clone()),
+            None => {
+                return Err(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    "no run is started, pass --run or --all",
+                )
+                .into())
+            }
+        },
+    };
+    let current: Option<RunState> =
+        current.filter(|state| run_id.as_ref().is_none_or(|run_id| *run_id == state.run_id));
+
+    // The rollback itself is not journaled, it goes to the registry directly
+    let executor: SystemExecutor = SystemExecutor::new(&backend);
+    let step_context: StepContext = context.step_context(&executor);
+    let mut failed_steps: usize = 0;
+    if let Some(state) = &current {
+        for rolled_back in roll_back_steps(&step_context, state, None) {
+            if rolled_back.errors.is_empty() {
+                context.say(&format!("step {} rolled back", rolled_back.step));
+            } else {
+                failed_steps += 1;
+            }
+        }
+    }
+    move_shortcuts_back(&step_context, run_id.as_deref())?;
+
+    let count: usize =
+        rollback_journal(&backend, &context.paths.journal_file(), run_id.as_deref())?;
+    match &run_id {
+        Some(run_id) => context.say(&format!(
+            "{} registry changes of run {} rolled back",
+            count, run_id
+        )),
+        None => context.say(&format!("{} registry changes rolled back", count)),
+    }
+    if revert_regional_settings(
+        &executor,
+        &context.paths.regional_snapshot(),
+        run_id.as_deref(),
+    )? {
+        context.say("regional settings reverted");
+    }
+    if failed_steps > 0 {
+        return Err(io::Error::other(format!(
+            "{} steps could not be rolled back, see the log",
+            failed_steps
+        ))
+        .into());
+    }
+    if current.is_some() {
+        remove_state_file(&context.paths);
+    }
+    Ok(ExitStatus::Success)
+}
+
+/// Check the config and the data folder without changing anything
+///
+/// # Arguments
+///
+/// * `context` - The resolved paths and config
+///
+/// # Returns
+///
+/// `Result<ExitStatus, CommandError>` - `config_error` if the config is invalid,
+/// `preflight_failure` if another check failed
+pub fn check(context: &Context) -> Result<ExitStatus, CommandError> {
+    let backend = SystemBackend::default();
+    let mut failures: usize = 0;
+    let mut report = |name: &str, result: Result<String, String>| match result {
+        Ok(detail) => println!("ok      {}: {}", name, detail),
+        Err(detail) => {
+            log::error!("check {} failed: {}", name, detail);
+            println!("FAILED  {}: {}", name, detail);
+            failures += 1;
+        }
+    };
+
+    let data: &Path = &context.paths.data;
+    report(
+        "data folder",
+        if data.is_dir() {
+            Ok(data.display().to_string())
+        } else {
+            Err(format!("{} does not exist", data.display()))
+        },
+    );
+    match &context.config_error {
+        None => {
+            let layers: Vec<String> = context
+                .config_layers
+                .iter()
+                .map(|layer| layer.to_string())
+                .collect();
+            report("config", Ok(layers.join(", ")));
+        }
+        Some(ConfigError::Invalid(issues)) => {
+            for issue in issues {
+                report("config", Err(issue.to_string()));
+            }
+        }
+        Some(err) => report("config", Err(err.to_string())),
+    }
+    let executables: [(&str, PathBuf, bool); 2] = [
+        (
+            "registry restore",
+            context.paths.registry_restore(),
+            context.config.restore_startup_apps,
+        ),
+        ("setup", context.paths.setup_executable(), true),
+    ];
+    for (name, path, needed) in executables {
+        report(
+            name,
+            if path.is_file() || !needed {
+                Ok(path.display().to_string())
+            } else {
+                Err(format!("{} does not exist", path.display()))
+            },
+        );
+    }
+    report(
+        "run state",
+        match RunState::load(&backend, &context.paths.state_file()) {
+            Ok(Some(state)) => Ok(format!("run {} in the {} phase", state.run_id, state.phase)),
+            Ok(None) => Ok("no run started".to_string()),
+            Err(err) => Err(err.to_string()),
+        },
+    );
+    report(
+        "backup manifest",
+        match BackupManifest::load(&context.paths.backup_manifest()) {
+            Ok(Some(manifest)) => Ok(format!(
+                "{} registry backups, {} startup shortcuts",
+                manifest.entries.len(),
+                manifest.startup_shortcuts.len()
+            )),
+            Ok(None) => Ok("no backups".to_string()),
+            Err(err) => Err(err.to_string()),
+        },
+    );
+    report(
+        "regional snapshot",
+        match RegionalSnapshot::load(&context.paths.regional_snapshot()) {
+            Ok(Some(snapshot)) => Ok(format!(
+                "taken by run {} at {}",
+                snapshot.run_id, snapshot.taken_at
+            )),
+            Ok(None) => Ok("no snapshot".to_string()),
+            Err(err) => Err(err.to_string()),
+        },
+    );
+    report(
+        "journal",
+        match Journal::load(&context.paths.journal_file()) {
+            Ok(journal) => Ok(format!("{} registry changes", journal.entries.len())),
+            Err(err) => Err(err.to_string()),
+        },
+    );
+
+    if failures > 0 {
+        let status: ExitStatus = match context.config_error {
+            Some(_) => ExitStatus::ConfigError,
+            None => ExitStatus::PreflightFailure,
+        };
+        return Err(CommandError::new(
+            status,
+            io::Error::other(format!("{} checks failed", failures)),
+        ));
+    }
+    Ok(ExitStatus::Success)
+}
+
+// +-----------------------+
+// |  private functions    |
+// +-----------------------+
+
+/// Pick up where the last launch stopped, or start a new run
+fn load_state(backend: &dyn RegistryBackend, paths: &AppPaths) -> io::Result<RunState> {
+    let state: RunState = match RunState::load(backend, &paths.state_file()) {
+        Ok(Some(state)) => {
+            log::info!(
+                "resuming run {} in the {} phase, started at {} by version {}",
+                state.run_id,
+                state.phase,
+                state.started_at,
+                state.assistant_version
+            );
+            if state.assistant_version != ASSISTANT_VERSION {
+                log::error!(
+                    "the run was started by version {}, resuming it with version {}",
+                    state.assistant_version,
+                    ASSISTANT_VERSION
+                );
+            }
+            state
+        }
+        Ok(None) => RunState::new(&new_run_id()),
+        Err(err) => {
+            return Err(io::Error::other(format!(
+                "failed to read the run state, nothing is changed: {}",
+                err
+            )));
+        }
+    };
+    log::info!("run id: {}", state.run_id);
+    Ok(state)
+}
+
+/// Undo the registry changes made by this run after a fatal error
+///
+/// # Arguments
+///
+/// * `backend` - The registry backend to undo the changes through
+/// * `journal_path` - Path of the registry journal
+/// * `run_id` - Id of the current run
+///
+/// # Returns
+///
+/// `RollbackReport` - How many changes were undone, or why they could not be
+fn roll_back_run(
+    backend: &dyn RegistryBackend,
+    journal_path: &Path,
+    run_id: &str,
+) -> RollbackReport {
+    log::error!("fatal error, rolling back the registry changes of this run...");
+    let mut rollback: RollbackReport = RollbackReport::default();
+    match rollback_journal(backend, journal_path, Some(run_id)) {
+        Ok(count) => {
+            log::info!("{} registry changes rolled back", count);
+            rollback.registry_changes = count;
+        }
+        Err(err) => {
+            log::error!("failed to roll back registry changes: {}", err);
+            rollback.errors = error_chain(&err);
+        }
+    }
+    rollback
+}
+
+/// Close the current session of the report and write it
+///
+/// # Arguments
+///
+/// * `report` - The report of the run
+/// * `outcome` - How the session ended
+/// * `rollback` - What was undone, if the run was rolled back
+/// * `executor` - The executor writing the report
+/// * `report_path` - Path of the report file
+fn finish_report(
+    report: &mut RunReport,
+    outcome: SessionOutcome,
+    rollback: Option<RollbackReport>,
+    executor: &dyn Executor,
+    report_path: &Path,
+) {
+    report.finish_session(outcome, rollback);
+    match report.save(executor, report_path) {
+        Ok(_) => log::info!("run report written to {}", report_path.display()),
+        Err(err) => log::error!("failed to write the run report: {}", err),
+    }
+}
+
+/// Remove the state file so the next launch starts a new run
+fn remove_state_file(paths: &AppPaths) {
+    match fs::remove_file(paths.state_file()) {
+        Ok(_) => log::info!("run state removed"),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+        Err(err) => log::error!("failed to remove the run state: {}", err),
+    }
+}
@@ -1,166 +1,146 @@
-// internal: constants
-pub mod constants;
-use constants::{DATA_FOLDER_NAME, SCRIPT_PATH};
-
-// internal: xml_handler
-mod xml_handler;
-use xml_handler::{remove_files, write_xml_file};
-
-// internal: registry_handler
-mod registry_handler;
-use registry_handler::{
-    export_and_delete_startup_registry_keys, schedule_setup_task, set_rebooted_key,
-};
-
-// internal: utilities
-mod utilities;
-use utilities::{message_box, setup_logging, WindowType};
-
 // std
-use std::fs;
-use std::path::Path;
-use std::process::Command;
-
-// serde-json
-use serde::{Deserialize, Serialize};
-
-#[derive(Debug, Serialize, Deserialize)]
-struct Config {
-    clean_startup_apps: bool,
-    restore_startup_apps: bool,
-    first_time_reboot: bool,
-    reboot_timer: u32,
-    change_locale: bool,
-}
-
-impl Default for Config {
-    fn default() -> Self {
-        Self {
-            clean_startup_apps: true,
-            restore_startup_apps: true,
-            first_time_reboot: true,
-            reboot_timer: 60,
-            change_locale: true,
+use std::env;
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+// clap
+use clap::Parser;
+
+// internal: cli
+mod cli;
+use cli::{Cli, Command};
+
+// internal: commands
+mod commands;
+use commands::Context;
+
+// internal: setup_core
+use setup_core::config::{ConfigLayers, LayeredConfig};
+use setup_core::config_schema::ConfigError;
+use setup_core::exit_code::ExitStatus;
+use setup_core::logging::setup_logging;
+use setup_core::paths::{enter_executable_folder, AppPaths};
+use setup_core::utilities::{message_box, WindowType};
+
+/// Exits with one of the codes of `ExitStatus`, `--help` prints the table
+fn main() -> ExitCode {
+    let mut cli: Cli = Cli::parse();
+
+    // Relative paths are given from the folder the assistant was started in, resolve them
+    // before changing to the executable folder
+    for path in [&mut cli.data_dir, &mut cli.config].into_iter().flatten() {
+        match std::path::absolute(&*path) {
+            Ok(absolute) => *path = absolute,
+            Err(err) => {
+                eprintln!("error: failed to resolve {}: {}", path.display(), err);
+                return ExitStatus::PreflightFailure.into();
+            }
         }
     }
-}
 
-fn main() {
+    // RunOnce starts the assistant in another folder, the data folder is next to the executable
+    let paths: AppPaths = match &cli.data_dir {
+        Some(data_dir) => AppPaths::new(data_dir),
+        None => match AppPaths::for_setup_assistant() {
+            Ok(paths) => paths,
+            Err(err) => {
+                eprintln!("error: failed to find the data folder: {}", err);
+                return ExitStatus::PreflightFailure.into();
+            }
+        },
+    };
+    let entered_folder = enter_executable_folder();
+
     // Initialize logging
-    setup_logging().expect("Failed to initialize logger.");
+    if let Err(err) = setup_logging(&paths.log_file()) {
+        eprintln!("error: failed to initialize logger: {}", err);
+        return ExitStatus::PreflightFailure.into();
+    }
     log::info!("================================================");
     log::info!("Starting setup assistant.");
+    log::info!("{:?}", cli);
+    if let Err(err) = entered_folder {
+        log::error!("failed to change to the executable folder: {}", err);
+    }
 
-    // Read the config file or use default values
-    let config_path = Path::new(DATA_FOLDER_NAME).join("config.json");
-    let config_data: String = match fs::read_to_string(config_path) {
-        Ok(data) => {
-            log::info!("config file found!");
-            data
+    // Layer the config: defaults, file, machine overlay, environment and command line
+    let command: Command = cli.command.take().unwrap_or(Command::Run { step: None });
+    let (config, config_error) = match load_config(&cli, &paths) {
+        Ok(layered) => {
+            layered.log_sources();
+            (layered, None)
         }
+        // `check` reports the problems itself
+        Err(err) if matches!(command, Command::Check) => (
+            ConfigLayers::new()
+                .finish()
+                .expect("the defaults are valid"),
+            Some(err),
+        ),
         Err(err) => {
-            log::error!("failed to read config file: {}", err);
-            String::from("")
-        }
-    };
-
-    let config: Config = if !config_data.is_empty() {
-        match serde_json::from_str(&config_data) {
-            Ok(config) => {
-                log::info!("config file parsed successfully!");
-                config
-            }
-            Err(err) => {
-                log::error!("failed to parse config file: {}", err);
-                Config::default()
+            log::error!("{}", err);
+            if !cli.quiet {
+                eprintln!("error: {}", err);
+                message_box("setup_assistant", &err.to_string(), WindowType::Error);
             }
+            log::info!("Setup assistant finished with {}.", ExitStatus::ConfigError);
+            return ExitStatus::ConfigError.into();
         }
-    } else {
-        log::info!("config file not found!");
-        Config::default()
     };
-    log::info!("{:?}", config);
-
-    // Display a message box to show the execution of the app
-    let message: &str = "شروع فرایند نصب نرم افزار معین";
-    let title: &str = "معین";
-    message_box(title, message, WindowType::Information);
 
-    // Create the reboot command
-    // todo: set the reboot time based on an external config file
-    let mut shutdown_command = Command::new("shutdown");
-    shutdown_command.args(["/r", "/t", config.reboot_timer.to_string().as_str()]);
-
-    // Create the script execution command
-    let mut script_execution_command = Command::new("cmd");
-    script_execution_command.arg("/C").arg(SCRIPT_PATH);
-
-    if config.clean_startup_apps {
-        log::info!("exporting registry key...");
-        match export_and_delete_startup_registry_keys() {
-            Ok(_) => {
-                log::info!("registry keys are successfully exported!");
-            }
-            Err(err) => {
-                log::error!("failed to export registry keys: {}", err);
-            }
-        }
-    } else {
-        log::info!("registry keys export skipped!");
-    }
+    let context = Context {
+        paths,
+        config: config.config,
+        config_layers: config.layers,
+        config_error,
+        executable_path: env::current_exe().unwrap_or_default(),
+        quiet: cli.quiet,
+    };
+    let result = match command {
+        Command::Run { step } => commands::run(&context, step),
+        Command::Plan { json } => commands::plan(&context, json),
+        Command::Restore => commands::restore(&context),
+        Command::Status => commands::status(&context),
+        Command::Rollback { run, all } => commands::rollback(&context, run, all),
+        Command::Check => commands::check(&context),
+    };
 
-    if config.change_locale {
-        log::info!("changing locale...");
-        match write_xml_file() {
-            Ok(_) => {
-                log::info!("xml file created.");
-                match script_execution_command.status() {
-                    Ok(_) => {
-                        log::info!("script executed successfully!");
-                        match remove_files() {
-                            Ok(_) => log::info!("files removed successfully!"),
-                            Err(err) => {
-                                log::error!("failed to remove files: {}", err);
-                            }
-                        }
-                    }
-                    Err(err) => {
-                        log::error!("failed to execute script: {}", err);
-                    }
-                }
+    let status: ExitStatus = match result {
+        Ok(status) => status,
+        Err(err) => {
+            log::error!("{}", err);
+            if !context.quiet {
+                eprintln!("error: {}", err);
             }
-            Err(err) => log::error!("failed to create xml file: {}", err),
+            err.status
         }
-    } else {
-        log::info!("locale change skipped!");
-    }
+    };
+    log::info!("Setup assistant finished with {}.", status);
+    status.into()
+}
 
-    log::info!("scheduling setup task...");
-    match schedule_setup_task() {
-        Ok(_) => log::info!("setup task scheduled successfully!"),
-        Err(err) => {
-            log::error!("failed to schedule setup task: {}", err);
-        }
+/// Layer the config: defaults, the config file, the machine overlay, `MOEIN_ASSISTANT_*`
+/// environment variables and the command-line flags
+///
+/// # Arguments
+///
+/// * `cli` - The parsed command line
+/// * `paths` - Where the files of the assistant live
+///
+/// # Returns
+///
+/// `Result<LayeredConfig, ConfigError>` - The effective config, or why it is invalid
+fn load_config(cli: &Cli, paths: &AppPaths) -> Result<LayeredConfig, ConfigError> {
+    let mut layers: ConfigLayers = ConfigLayers::new();
+    let config_path: PathBuf = cli.config.clone().unwrap_or_else(|| paths.config_file());
+    layers.add_file(&config_path)?;
+    layers.add_machine_overlay(&paths.machine_config_file())?;
+    layers.add_environment(env::vars())?;
+    for (key, value) in &cli.settings {
+        layers.add_override(key, value, &format!("--set {}={}", key, value))?;
     }
-
-    if config.first_time_reboot {
-        log::info!("setting rebooted key...");
-        match set_rebooted_key(1) {
-            Ok(_) => log::info!("rebooted key set successfully!"),
-            Err(err) => {
-                log::error!("failed to set rebooted key: {}", err);
-            }
-        }
-        log::info!("rebooting...");
-        match shutdown_command.status() {
-            Ok(_) => log::info!("reboot planned"),
-            Err(err) => {
-                log::error!("failed to reboot: {}", err);
-            }
-        }
-    } else {
-        log::info!("reboot skipped!");
+    if cli.no_reboot {
+        layers.add_override("first_time_reboot", "false", "--no-reboot")?;
     }
-
-    log::info!("Setup assistant finished.");
+    layers.finish()
 }